@@ -24,7 +24,15 @@ mod day02 {
         });
     }
 
-    criterion_group!(solutions, part_1, part_2);
+    pub fn part_2_symbolic(c: &mut Criterion) {
+        let memory: intcode::Memory = day02::PUZZLE_INPUT.parse().unwrap();
+
+        c.bench_function("day02::part_2_symbolic", |b| {
+            b.iter(|| day02::solve_for_noun_and_verb(black_box(memory.clone()), black_box(0)))
+        });
+    }
+
+    criterion_group!(solutions, part_1, part_2, part_2_symbolic);
 }
 
 mod day05 {
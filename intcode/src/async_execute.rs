@@ -1,17 +1,29 @@
 use super::{
     decode::{decode, BinaryOperands, Decoded, InputOperands, JumpIfOperands, OutputOperands},
+    decode::{Output, Parameter},
     execute::*,
     ops::Instruction,
-    Address, Memory, Relative, Word,
+    state, Address, CancelHandle, DebugInfo, History, Image, MachineState, Memory, Observer,
+    Relative, Undone, Word,
+};
+use futures::{
+    channel::mpsc::{channel, Receiver, Sender},
+    future::{self, Either},
+    sink::SinkExt,
+    stream::{self, Stream, StreamExt},
+    task::{Context, Poll},
 };
-use futures::stream::{Stream, StreamExt};
 use snafu::ResultExt;
 use std::{
     convert::TryFrom,
-    ops,
-    sync::atomic::{AtomicUsize, Ordering},
+    fmt,
+    future::Future,
+    pin::Pin,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
 };
-use tokio::sync::mpsc::{channel, Receiver, Sender};
 
 pub struct AsyncBuffer {
     last_output: Option<Word>,
@@ -39,7 +51,7 @@ impl AsyncBuffer {
     }
 
     pub async fn execute(mut self) -> Option<Word> {
-        while let Some(value) = self.rx.recv().await {
+        while let Some(value) = self.rx.next().await {
             self.last_output = Some(value);
 
             // Ignore if the listener has stopped listening
@@ -52,97 +64,312 @@ impl AsyncBuffer {
 
 static NEXT_EXECUTABLE_ID: AtomicUsize = AtomicUsize::new(0);
 
+/// The number of instructions executed between yields to the executor
+///
+/// A program which never waits on input or output would otherwise hold its
+/// thread indefinitely, starving any task which could cancel it.
+const YIELD_INTERVAL: usize = 1024;
+
+/// A future which is pending once, giving other tasks a chance to run
+struct YieldNow(bool);
+
+impl Future for YieldNow {
+    type Output = ();
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context) -> Poll<()> {
+        if self.0 {
+            return Poll::Ready(());
+        }
+
+        self.0 = true;
+        cx.waker().wake_by_ref();
+        Poll::Pending
+    }
+}
+
 /// The Intcode interpreter
 ///
 /// Executes programs, keeps track of current position, and relays input and
 /// output during execution.
 #[derive(Debug)]
-pub struct AsyncExecutable<Input = Receiver<Word>> {
+pub struct AsyncExecutable<Input = Receiver<Word>, O = ()> {
     id: usize,
+    image: Memory,
     memory: Memory,
     pc: ProgramCounter,
+    /// Where execution begins, and resumes after a reset
+    entry: ProgramCounter,
     rel: Address,
     input: Input,
-    output: Sender<Word>,
+    /// Where outputs are sent, or `None` if writing output should fail
+    output: Option<Sender<Word>>,
     steps: usize,
+    cancel: Option<CancelHandle>,
+    debug: Option<Arc<DebugInfo>>,
+    observer: O,
 }
 
 impl From<Memory> for AsyncExecutable<Receiver<Word>> {
     fn from(memory: Memory) -> Self {
+        Self::from(Image::new(memory))
+    }
+}
+
+impl From<Image> for AsyncExecutable<Receiver<Word>> {
+    /// Starts execution at the image's entry point, describing errors in
+    /// terms of its symbols
+    fn from(image: Image) -> Self {
+        let entry = ProgramCounter::at(image.entry());
+        let debug = image
+            .symbols()
+            .next()
+            .map(|_| Arc::new(DebugInfo::from(&image)));
+        let memory = image.into_memory();
         Self {
             id: NEXT_EXECUTABLE_ID.fetch_add(1, Ordering::AcqRel),
+            image: memory.clone(),
             memory,
-            pc: ProgramCounter::START,
+            pc: entry,
+            entry,
             rel: Address::new(0),
             input: channel(1).1,
-            output: channel(1).0,
+            output: None,
             steps: 0,
+            cancel: None,
+            debug,
+            observer: (),
         }
     }
 }
 
+impl<Input, O> fmt::Display for AsyncExecutable<Input, O> {
+    /// Summarises the machine on one line, ending with the instruction it
+    /// will execute next
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "#{} ", self.id)?;
+        state::write_status(f, self.pc.address(), self.rel, self.steps, &self.memory)
+    }
+}
+
 impl AsyncExecutable {
+    pub fn buffer_to(&mut self, target: &mut AsyncExecutable) -> AsyncBuffer {
+        AsyncBuffer::between(self, target)
+    }
+}
+
+impl<O> AsyncExecutable<Receiver<Word>, O> {
     pub fn reset_pc(&mut self) {
-        self.pc = ProgramCounter::START;
+        self.pc = self.entry;
     }
 
+    /// Supplies a single input value, after which input is closed
     pub fn single_input(&mut self, value: Word) {
-        let (mut tx, rx) = channel(1);
+        let (mut tx, rx) = channel(0);
+        // Each sender is guaranteed a slot, so the value is queued at once
+        tx.try_send(value)
+            .expect("a new channel has room for one value");
         self.input = rx;
-        tokio::spawn(async move { tx.send(value).await });
-    }
-
-    pub fn buffer_to(&mut self, target: &mut AsyncExecutable) -> AsyncBuffer {
-        AsyncBuffer::between(self, target)
     }
 
     pub fn pipe_inputs_from(&mut self, source: Receiver<Word>) {
         self.input = source;
     }
+}
+
+impl<Input, O> AsyncExecutable<Input, O> {
+    /// Restores memory to the program image the executable was created
+    /// from and rewinds the program counter and relative base
+    ///
+    /// Input and output pipes are left in place, so a single executable can
+    /// be reused to evaluate the same program against many inputs.
+    pub fn reset(&mut self) {
+        self.memory.reset_to(&self.image);
+        self.pc = self.entry;
+        self.rel = Address::ZERO;
+        self.steps = 0;
+    }
+
+    /// Obtains a handle which can be used to stop execution
+    ///
+    /// See `execute_cancellable` for obtaining the state of a stopped
+    /// executable. If the current handle has already been cancelled, it is
+    /// replaced with a fresh handle so that execution can be resumed.
+    pub fn cancel_handle(&mut self) -> CancelHandle {
+        match &self.cancel {
+            Some(cancel) if !cancel.is_cancelled() => cancel.clone(),
+            _ => self.cancel.insert(CancelHandle::default()).clone(),
+        }
+    }
+
+    /// Yields to the executor every `YIELD_INTERVAL` instructions, then
+    /// checks whether execution has been cancelled
+    async fn cooperate(&mut self) -> Result<(), ExecutionErrorInner> {
+        #[allow(clippy::manual_is_multiple_of)]
+        if self.steps != 0 && self.steps % YIELD_INTERVAL == 0 {
+            YieldNow(false).await;
+        }
+        self.check_cancelled()
+    }
+
+    fn check_cancelled(&self) -> Result<(), ExecutionErrorInner> {
+        match &self.cancel {
+            Some(cancel) if cancel.is_cancelled() => {
+                Err(ExecutionErrorInner::Cancelled { pc: self.pc })
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Attaches an observer which will be notified of execution events
+    pub fn with_observer<P: Observer>(self, observer: P) -> AsyncExecutable<Input, P> {
+        AsyncExecutable {
+            id: self.id,
+            image: self.image,
+            memory: self.memory,
+            pc: self.pc,
+            entry: self.entry,
+            rel: self.rel,
+            input: self.input,
+            output: self.output,
+            steps: self.steps,
+            cancel: self.cancel,
+            debug: self.debug,
+            observer,
+        }
+    }
+
+    /// Attaches debug information, which errors use to describe where
+    /// execution failed
+    pub fn with_debug_info(mut self, debug: Arc<DebugInfo>) -> Self {
+        self.debug = Some(debug);
+        self
+    }
+
+    /// Provides access to the attached observer
+    pub fn observer(&self) -> &O {
+        &self.observer
+    }
+
+    /// Provides mutable access to the attached observer
+    pub fn observer_mut(&mut self) -> &mut O {
+        &mut self.observer
+    }
+
+    /// Detaches the observer, discarding the executable
+    pub fn into_observer(self) -> O {
+        self.observer
+    }
+
+    /// A number which identifies the executable among all those created
+    pub fn id(&self) -> usize {
+        self.id
+    }
+
+    /// The address of the next instruction to execute
+    pub fn pc(&self) -> Address {
+        self.pc.address()
+    }
+
+    /// The base address for relative mode parameters
+    pub fn relative_base(&self) -> Address {
+        self.rel
+    }
+
+    /// The number of instructions executed so far
+    pub fn steps(&self) -> usize {
+        self.steps
+    }
+
+    /// The current contents of memory
+    pub fn memory(&self) -> &Memory {
+        &self.memory
+    }
+
+    /// Takes a copy of the registers and memory
+    pub fn state(&self) -> MachineState {
+        MachineState {
+            pc: self.pc.address(),
+            relative_base: self.rel,
+            steps: self.steps,
+            memory: self.memory.clone(),
+        }
+    }
+
+    /// Replaces the registers and memory with those of `state`
+    ///
+    /// The attached observer is not notified, so any history it keeps of
+    /// earlier execution no longer applies.
+    pub fn restore_state(&mut self, state: MachineState) {
+        self.pc = ProgramCounter::at(state.pc);
+        self.rel = state.relative_base;
+        self.steps = state.steps;
+        self.memory = state.memory;
+    }
+
+    pub(crate) fn memory_mut(&mut self) -> &mut Memory {
+        &mut self.memory
+    }
+
+    /// Disconnects the output pipe, returning it
+    ///
+    /// A drain of the pipe finishes once the returned sender is dropped, and
+    /// any further output fails as though the pipe were closed.
+    pub(crate) fn take_output(&mut self) -> Option<Sender<Word>> {
+        self.output.take()
+    }
 
     pub fn drain(&mut self) -> AsyncOutputDrain {
         let (tx, rx) = channel(1);
         self.pipe_outputs_to(tx);
         AsyncOutputDrain(rx)
     }
-}
 
-impl<Input> AsyncExecutable<Input> {
-    pub fn pipe_to(&mut self, target: &mut AsyncExecutable) -> Sender<Word> {
+    pub fn pipe_to<P>(&mut self, target: &mut AsyncExecutable<Receiver<Word>, P>) -> Sender<Word> {
         let (tx, rx) = channel(1);
-        self.output = tx.clone();
+        self.output = Some(tx.clone());
         target.input = rx;
         tx
     }
 
     pub fn pipe_outputs_to(&mut self, target: Sender<Word>) {
-        self.output = target;
+        self.output = Some(target);
     }
 
-    pub fn input_stream<S>(self, source: S) -> AsyncExecutable<S> {
+    pub fn input_stream<S>(self, source: S) -> AsyncExecutable<S, O> {
         AsyncExecutable {
             id: self.id,
+            image: self.image,
             memory: self.memory,
             pc: self.pc,
+            entry: self.entry,
             rel: self.rel,
             input: source,
             output: self.output,
             steps: self.steps,
+            cancel: self.cancel,
+            debug: self.debug,
+            observer: self.observer,
         }
     }
 
+    #[cfg(feature = "tokio-runtime")]
     pub fn watch_inputs_from(
         self,
         source: tokio::sync::watch::Receiver<Word>,
-    ) -> AsyncExecutable<tokio::sync::watch::Receiver<Word>> {
+    ) -> AsyncExecutable<tokio::sync::watch::Receiver<Word>, O> {
         AsyncExecutable {
             id: self.id,
+            image: self.image,
             memory: self.memory,
             pc: self.pc,
+            entry: self.entry,
             rel: self.rel,
             input: source,
             output: self.output,
             steps: self.steps,
+            cancel: self.cancel,
+            debug: self.debug,
+            observer: self.observer,
         }
     }
 
@@ -156,35 +383,55 @@ impl<Input> AsyncExecutable<Input> {
 
         decode(i, self.pc, &self.memory)
     }
+}
+
+impl<Input, O: Observer> AsyncExecutable<Input, O> {
+    fn load(&mut self, param: Parameter) -> Result<Word, ExecutionErrorInner> {
+        let address = match param {
+            Parameter::Immediate(value) => return Ok(value),
+            _ => param
+                .resolve(self.rel)
+                .context(InvalidAddress { pc: self.pc })?
+                .expect("non-immediate parameters refer to memory"),
+        };
+
+        let value = self.memory.read_or_default(address);
+        self.observer.on_read(self.pc.address(), address, value);
+
+        Ok(value)
+    }
+
+    fn store(&mut self, target: Output, value: Word) -> Result<(), ExecutionErrorInner> {
+        let address = target
+            .resolve(self.rel)
+            .context(InvalidAddress { pc: self.pc })?;
+
+        let prior = self.memory.write_arbitrary(address, value);
+        self.observer
+            .on_write(self.pc.address(), address, prior, value);
+
+        Ok(())
+    }
 
     fn execute_binary_op(
         &mut self,
         operands: BinaryOperands,
         f: fn(Word, Word) -> Word,
     ) -> Result<(), ExecutionErrorInner> {
-        let left = operands
-            .left
-            .load(self.rel, &self.memory)
-            .context(InvalidAddress { pc: self.pc })?;
-        let right = operands
-            .right
-            .load(self.rel, &self.memory)
-            .context(InvalidAddress { pc: self.pc })?;
+        let left = self.load(operands.left)?;
+        let right = self.load(operands.right)?;
         let result = f(left, right);
 
         log::trace!("{}@{}: {} {} = {}", self.id, self.pc, left, right, result);
 
-        operands.target.store(self.rel, &mut self.memory, result);
+        self.store(operands.target, result)?;
 
         self.pc.advance(4);
         Ok(())
     }
 
     fn execute_add_rel(&mut self, operands: OutputOperands) -> Result<(), ExecutionErrorInner> {
-        let value = operands
-            .source
-            .load(self.rel, &self.memory)
-            .context(InvalidAddress { pc: self.pc })?;
+        let value = self.load(operands.source)?;
 
         let next = (self.rel + Relative::from(value)).context(InvalidAddress { pc: self.pc })?;
 
@@ -197,6 +444,8 @@ impl<Input> AsyncExecutable<Input> {
             next
         );
 
+        self.observer
+            .on_relative_base(self.pc.address(), self.rel, next);
         self.rel = next;
         self.pc.advance(2);
         Ok(())
@@ -207,17 +456,14 @@ impl<Input> AsyncExecutable<Input> {
         operands: JumpIfOperands,
         non_zero: bool,
     ) -> Result<(), ExecutionErrorInner> {
-        let value = operands
-            .value
-            .load(self.rel, &self.memory)
-            .context(InvalidAddress { pc: self.pc })?;
-        let target_raw = operands
-            .jump_target
-            .load(self.rel, &self.memory)
-            .context(InvalidAddress { pc: self.pc })?;
+        let value = self.load(operands.value)?;
+        let target_raw = self.load(operands.jump_target)?;
         let target = Address::try_from(target_raw).context(InvalidAddress { pc: self.pc })?;
 
-        if (value != 0) == non_zero {
+        let taken = (value != 0) == non_zero;
+        self.observer.on_jump(self.pc.address(), target, taken);
+
+        if taken {
             log::trace!("{}@{}: {} ~> {}", self.id, self.pc, value, target);
 
             self.pc.jump(target);
@@ -235,20 +481,14 @@ impl<Input> AsyncExecutable<Input> {
         operands: BinaryOperands,
         f: fn(&Word, &Word) -> bool,
     ) -> Result<(), ExecutionErrorInner> {
-        let left = operands
-            .left
-            .load(self.rel, &self.memory)
-            .context(InvalidAddress { pc: self.pc })?;
-        let right = operands
-            .right
-            .load(self.rel, &self.memory)
-            .context(InvalidAddress { pc: self.pc })?;
+        let left = self.load(operands.left)?;
+        let right = self.load(operands.right)?;
 
         let result = if f(&left, &right) { 1 } else { 0 };
 
         log::trace!("{}@{}: {} {} = {}", self.id, self.pc, left, right, result);
 
-        operands.target.store(self.rel, &mut self.memory, result);
+        self.store(operands.target, result)?;
 
         self.pc.advance(4);
         Ok(())
@@ -258,47 +498,199 @@ impl<Input> AsyncExecutable<Input> {
         &mut self,
         operands: OutputOperands,
     ) -> Result<(), ExecutionErrorInner> {
-        let value = operands
-            .source
-            .load(self.rel, &self.memory)
-            .context(InvalidAddress { pc: self.pc })?;
+        let value = self.load(operands.source)?;
 
         log::trace!("{}@{}: => {}", self.id, self.pc, value);
 
-        self.output
-            .send(value)
-            .await
-            .map_err(|e| ExecutionErrorInner::OutputPipeClosed {
-                source: std::sync::mpsc::SendError(e.0),
-                pc: self.pc,
-            })?;
+        // Only wait for room in the pipe, so that cancellation can never
+        // leave the value queued to be sent again on resume
+        let pc = self.pc;
+        let closed = || ExecutionErrorInner::OutputPipeClosed {
+            source: std::sync::mpsc::SendError(value),
+            pc,
+        };
+        let output = self.output.as_mut().ok_or_else(closed)?;
+        let ready = future::poll_fn(|cx| output.poll_ready(cx));
+        let ready = match &self.cancel {
+            Some(cancel) => match future::select(ready, cancel.cancelled()).await {
+                Either::Left((ready, _)) => ready,
+                Either::Right(_) => return Err(ExecutionErrorInner::Cancelled { pc }),
+            },
+            None => ready.await,
+        };
+
+        ready
+            .and_then(|()| output.start_send(value))
+            .map_err(|_| closed())?;
+        self.observer.on_output(self.pc.address(), value);
         self.pc.advance(2);
         Ok(())
     }
 }
 
-impl<S> AsyncExecutable<S>
+impl<S> AsyncExecutable<S, History> {
+    /// Reverts the effects of the most recently executed instruction
+    ///
+    /// Returns `None` once there is no further history to undo.
+    pub fn step_back(&mut self) -> Option<Undone> {
+        self.observer.undo(
+            &mut self.memory,
+            &mut self.pc,
+            &mut self.rel,
+            &mut self.steps,
+        )
+    }
+
+    /// Rewinds execution to just before the `count`th most recent
+    /// instruction that consumed input
+    ///
+    /// Returns the undone inputs in the order they were originally received;
+    /// these must be provided again for execution to follow the same path.
+    pub fn rewind_inputs(&mut self, count: usize) -> Vec<Word> {
+        self.observer.rewind_inputs(
+            count,
+            &mut self.memory,
+            &mut self.pc,
+            &mut self.rel,
+            &mut self.steps,
+        )
+    }
+}
+
+impl<S, O> AsyncExecutable<S, O>
 where
     S: Stream<Item = Word> + Unpin,
+    O: Observer,
 {
     /// Executes the Intcode program in memory until a halt instruction is
     /// encountered or an invalid operation causes termination due to an
     /// `ExecutionError`
+    ///
+    /// If execution is stopped through a `CancelHandle`, an error is returned
+    /// for which `ExecutionError::is_cancelled` is `true`.
     pub async fn execute(mut self) -> Result<Memory, ExecutionError> {
-        while self.step().await? {}
+        self.run().await?;
 
         Ok(self.memory)
     }
 
+    /// Executes the Intcode program in memory until a halt instruction is
+    /// encountered, without consuming the executable
+    ///
+    /// Combined with `reset`, this allows the same executable to be run
+    /// repeatedly.
+    pub async fn run(&mut self) -> Result<&Memory, ExecutionError> {
+        while self.step().await? {}
+
+        Ok(&self.memory)
+    }
+
+    /// Executes the Intcode program in memory until a halt instruction is
+    /// encountered, execution is cancelled, or an invalid operation causes
+    /// termination due to an `ExecutionError`
+    ///
+    /// A cancelled executable is returned at an instruction boundary and may
+    /// be resumed.
+    pub async fn execute_cancellable(mut self) -> Result<Termination<S, O>, ExecutionError> {
+        loop {
+            match self.step().await {
+                Ok(true) => {}
+                Ok(false) => return Ok(Termination::Halted(self.memory)),
+                Err(e) if e.is_cancelled() => return Ok(Termination::Cancelled(self)),
+                Err(e) => return Err(e),
+            }
+        }
+    }
+
+    /// Converts the executable into a stream of the values it outputs
+    ///
+    /// The program runs only while the stream is polled, up to its next
+    /// output, so it proceeds at the pace of the consumer without a task or
+    /// channel of its own. Outputs are yielded by the stream rather than sent
+    /// to any pipe set up with `pipe_outputs_to`, while inputs are still read
+    /// as usual. The stream ends when the program halts, or after yielding
+    /// the error which stopped it.
+    ///
+    /// ## Example
+    ///
+    /// ```
+    /// use futures::stream::{StreamExt, TryStreamExt};
+    /// use intcode::{AsyncExecutable, Memory};
+    ///
+    /// # tokio::runtime::Runtime::new().unwrap().block_on(async {
+    /// // Counts upwards from 1 forever
+    /// let memory: Memory = "101,1,9,9,4,9,1105,1,0,0".parse().expect("valid data");
+    /// let exe = AsyncExecutable::from(memory);
+    ///
+    /// let squares: Vec<_> = exe
+    ///     .into_stream()
+    ///     .map_ok(|n| n * n)
+    ///     .take(4)
+    ///     .try_collect()
+    ///     .await
+    ///     .expect("successful execution");
+    /// assert_eq!(vec![1, 4, 9, 16], squares);
+    /// # });
+    /// ```
+    pub fn into_stream(self) -> impl Stream<Item = Result<Word, ExecutionError>> {
+        stream::unfold(Some(self), |exe| async move {
+            let mut exe = exe?;
+            match exe.run_to_output().await {
+                Ok(Some(value)) => Some((Ok(value), Some(exe))),
+                Ok(None) => None,
+                Err(e) => {
+                    let error = ExecutionError::new(e, exe.debug.clone());
+                    Some((Err(error), None))
+                }
+            }
+        })
+    }
+
+    /// Executes instructions until the program outputs a value, which is
+    /// returned rather than sent, or until it halts
+    async fn run_to_output(&mut self) -> Result<Option<Word>, ExecutionErrorInner> {
+        loop {
+            self.cooperate().await?;
+            let op = self.read_instruction()?;
+            self.observer.on_fetch(self.pc.address(), &op);
+            if let Decoded::Output(operands) = op {
+                let value = self.load(operands.source)?;
+
+                log::trace!("{}@{}: => {}", self.id, self.pc, value);
+
+                self.observer.on_output(self.pc.address(), value);
+                self.pc.advance(2);
+                self.steps += 1;
+                return Ok(Some(value));
+            }
+
+            if !self.execute_op(op).await? {
+                return Ok(None);
+            }
+        }
+    }
+
     pub async fn step(&mut self) -> Result<bool, ExecutionError> {
-        Ok(self.execute_op(self.read_instruction()?).await?)
+        match self.try_step().await {
+            Ok(running) => Ok(running),
+            Err(e) => Err(ExecutionError::new(e, self.debug.clone())),
+        }
+    }
+
+    async fn try_step(&mut self) -> Result<bool, ExecutionErrorInner> {
+        self.cooperate().await?;
+        let op = self.read_instruction()?;
+        self.observer.on_fetch(self.pc.address(), &op);
+        self.execute_op(op).await
     }
 
     async fn execute_op(&mut self, op: Decoded) -> Result<bool, ExecutionErrorInner> {
-        self.steps += 1;
+        // Steps are only counted once an instruction retires, as one which
+        // is cancelled while waiting on I/O runs again on resume
         match op {
-            Decoded::Add(params) => self.execute_binary_op(params, ops::Add::add),
-            Decoded::Mul(params) => self.execute_binary_op(params, ops::Mul::mul),
+            // Overflow wraps regardless of build profile
+            Decoded::Add(params) => self.execute_binary_op(params, Word::wrapping_add),
+            Decoded::Mul(params) => self.execute_binary_op(params, Word::wrapping_mul),
             Decoded::Input(params) => self.execute_input(params).await,
             Decoded::Output(params) => self.execute_output(params).await,
             Decoded::JumpNonZero(params) => self.execute_jump_if(params, true),
@@ -307,6 +699,7 @@ where
             Decoded::Equal(params) => self.execute_cmp(params, Word::eq),
             Decoded::AddRel(params) => self.execute_add_rel(params),
             Decoded::Halt => {
+                self.steps += 1;
                 log::trace!("{}@{}: halt", self.id, self.pc);
                 log::debug!(
                     "halted (steps = {}; memory size = {})",
@@ -317,43 +710,93 @@ where
             }
         }?;
 
+        self.steps += 1;
         Ok(true)
     }
 
     async fn execute_input(&mut self, operands: InputOperands) -> Result<(), ExecutionErrorInner> {
-        let value = self
-            .input
-            .next()
-            .await
-            .ok_or(ExecutionErrorInner::UnexpectedEndOfInput {
-                source: std::sync::mpsc::RecvError,
-                pc: self.pc,
-            })?;
+        let next = self.input.next();
+        let received = match &self.cancel {
+            Some(cancel) => match future::select(next, cancel.cancelled()).await {
+                Either::Left((received, _)) => received,
+                Either::Right(_) => {
+                    return Err(ExecutionErrorInner::Cancelled { pc: self.pc });
+                }
+            },
+            None => next.await,
+        };
+
+        let value = received.ok_or(ExecutionErrorInner::UnexpectedEndOfInput {
+            source: std::sync::mpsc::RecvError,
+            pc: self.pc,
+        })?;
 
         log::trace!("{}@{}: {} =>", self.id, self.pc, value);
 
-        operands.target.store(self.rel, &mut self.memory, value);
+        self.observer.on_input(self.pc.address(), value);
+        self.store(operands.target, value)?;
 
         self.pc.advance(2);
         Ok(())
     }
 }
 
+/// The outcome of an execution which did not fail
+#[derive(Debug)]
+pub enum Termination<S = Receiver<Word>, O = ()> {
+    /// The program encountered a halt instruction
+    Halted(Memory),
+    /// Execution was stopped through a `CancelHandle` before the program
+    /// halted
+    Cancelled(AsyncExecutable<S, O>),
+}
+
+/// The outputs of an executable, as a stream which ends once the executable
+/// has halted and been dropped
+///
+/// The stream must be polled alongside the executable, as with
+/// `futures::join!`, or the executable will wait to send its next output.
+///
+/// ## Example
+///
+/// ```
+/// use futures::{executor::block_on, StreamExt};
+/// use intcode::{AsyncExecutable, Memory};
+///
+/// // Outputs its input doubled
+/// let memory: Memory = "3,9,102,2,9,9,4,9,99,0".parse().expect("valid data");
+/// let mut exe = AsyncExecutable::from(memory);
+/// exe.single_input(21);
+/// let drain = exe.drain();
+///
+/// let (result, outputs) = block_on(async {
+///     futures::join!(exe.execute(), drain.collect::<Vec<_>>())
+/// });
+/// result.expect("successful execution");
+/// assert_eq!(vec![42], outputs);
+/// ```
+#[derive(Debug)]
 pub struct AsyncOutputDrain(Receiver<Word>);
 
+impl Stream for AsyncOutputDrain {
+    type Item = Word;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Word>> {
+        Pin::new(&mut self.0).poll_next(cx)
+    }
+}
+
+#[cfg(feature = "tokio-runtime")]
 impl AsyncOutputDrain {
-    /// Blocks until the executable has halted
+    /// Collects outputs on a separate Tokio task until the executable has
+    /// halted
+    ///
+    /// ## Panics
+    ///
+    /// Panics if not called from within a Tokio runtime.
     pub fn into_vec(
-        mut self,
+        self,
     ) -> impl std::future::Future<Output = Result<Vec<Word>, tokio::task::JoinError>> {
-        tokio::spawn(async move {
-            let mut outputs = Vec::new();
-
-            while let Some(x) = self.0.recv().await {
-                outputs.push(x)
-            }
-
-            outputs
-        })
+        tokio::spawn(self.collect())
     }
 }
@@ -0,0 +1,253 @@
+use super::{Address, Executable, ExecutionError, Memory, Word};
+use std::{
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        mpsc::channel,
+        Mutex,
+    },
+    thread,
+};
+
+/// The result of evaluating a program against a single set of inputs
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Evaluation {
+    /// Values output by the program, in order
+    pub outputs: Vec<Word>,
+    /// The state of memory after the program halted
+    pub memory: Memory,
+}
+
+/// Runs the same Intcode program against many independent inputs across a
+/// pool of threads
+///
+/// Each worker thread owns a single `Executable` which is `reset` between
+/// evaluations, so the program image is shared rather than copied.
+///
+/// ## Example
+///
+/// ```
+/// use intcode::{Batch, Memory};
+///
+/// const ECHO_DOUBLE: &str = "3,9,102,2,9,9,4,9,99,0";
+/// let memory: Memory = ECHO_DOUBLE.parse().expect("valid data");
+///
+/// let results = Batch::new(memory).evaluate((1..=3).map(|i| vec![i]));
+/// let outputs: Vec<_> = results.into_iter().map(|r| r.unwrap().outputs).collect();
+///
+/// assert_eq!(vec![vec![2], vec![4], vec![6]], outputs);
+/// ```
+#[derive(Clone, Debug)]
+pub struct Batch {
+    program: Memory,
+    threads: usize,
+    patches: Vec<Address>,
+}
+
+impl Batch {
+    /// Creates a batch runner for the given program, using one thread per
+    /// available CPU
+    pub fn new(program: Memory) -> Self {
+        let threads = thread::available_parallelism()
+            .map(|n| n.get())
+            .unwrap_or(1);
+
+        Self {
+            program,
+            threads,
+            patches: Vec::new(),
+        }
+    }
+
+    /// Sets the number of worker threads
+    pub fn threads(mut self, threads: usize) -> Self {
+        self.threads = threads.max(1);
+        self
+    }
+
+    /// Writes the leading values of each input vector into memory at the
+    /// given addresses before execution, rather than providing them as input
+    ///
+    /// Any remaining values are provided to the program as input.
+    pub fn patching(mut self, addresses: &[Address]) -> Self {
+        self.patches = addresses.to_vec();
+        self
+    }
+
+    /// Evaluates the program against every set of inputs
+    ///
+    /// Results are returned in the same order as the inputs.
+    pub fn evaluate<I>(&self, inputs: I) -> Vec<Result<Evaluation, ExecutionError>>
+    where
+        I: IntoIterator<Item = Vec<Word>>,
+        I::IntoIter: Send,
+    {
+        let mut results = self.run_pool(inputs, |_| false);
+        results.sort_unstable_by_key(|&(idx, _)| idx);
+        results.into_iter().map(|(_, r)| r).collect()
+    }
+
+    /// Evaluates the program against each set of inputs until one satisfies
+    /// `predicate`
+    ///
+    /// Returns the index and evaluation of the first matching set of inputs,
+    /// in input order, or the first error encountered before a match.
+    /// Evaluations beyond the first match are abandoned as soon as possible.
+    pub fn find_first<I, F>(
+        &self,
+        inputs: I,
+        predicate: F,
+    ) -> Result<Option<(usize, Evaluation)>, ExecutionError>
+    where
+        I: IntoIterator<Item = Vec<Word>>,
+        I::IntoIter: Send,
+        F: Fn(&Evaluation) -> bool + Sync,
+    {
+        let results = self.run_pool(inputs, |r| r.as_ref().map_or(true, &predicate));
+
+        let first = results
+            .into_iter()
+            .filter(|(_, r)| r.as_ref().map_or(true, &predicate))
+            .min_by_key(|&(idx, _)| idx);
+
+        match first {
+            Some((idx, Ok(evaluation))) => Ok(Some((idx, evaluation))),
+            Some((_, Err(err))) => Err(err),
+            None => Ok(None),
+        }
+    }
+
+    fn run_pool<I, F>(&self, inputs: I, stop: F) -> Vec<(usize, Result<Evaluation, ExecutionError>)>
+    where
+        I: IntoIterator<Item = Vec<Word>>,
+        I::IntoIter: Send,
+        F: Fn(&Result<Evaluation, ExecutionError>) -> bool + Sync,
+    {
+        let queue = Mutex::new(inputs.into_iter().enumerate());
+        let first_stop = AtomicUsize::new(usize::MAX);
+        let results = Mutex::new(Vec::new());
+
+        thread::scope(|s| {
+            for _ in 0..self.threads {
+                s.spawn(|| {
+                    let mut exe = Executable::from(self.program.clone());
+                    loop {
+                        let next = queue.lock().expect("queue lock poisoned").next();
+                        let (idx, input) = match next {
+                            Some(job) => job,
+                            None => break,
+                        };
+
+                        if idx > first_stop.load(Ordering::Acquire) {
+                            break;
+                        }
+
+                        let result = self.evaluate_one(&mut exe, input);
+                        if stop(&result) {
+                            first_stop.fetch_min(idx, Ordering::AcqRel);
+                        }
+
+                        results
+                            .lock()
+                            .expect("results lock poisoned")
+                            .push((idx, result));
+                    }
+                });
+            }
+        });
+
+        results.into_inner().expect("results lock poisoned")
+    }
+
+    fn evaluate_one(
+        &self,
+        exe: &mut Executable,
+        input: Vec<Word>,
+    ) -> Result<Evaluation, ExecutionError> {
+        exe.reset();
+
+        let mut input = input.into_iter();
+        for (&address, value) in self.patches.iter().zip(&mut input) {
+            exe.memory_mut().write_arbitrary(address, value);
+        }
+
+        let (tx, rx) = channel();
+        for value in input {
+            tx.send(value).expect("receiver is held by the executable");
+        }
+        drop(tx);
+        exe.pipe_inputs_from(rx);
+
+        let drain = exe.drain();
+        let result = exe.run().cloned();
+
+        // Release the sender so that the drain terminates
+        drop(exe.take_output());
+
+        Ok(Evaluation {
+            outputs: drain.to_vec(),
+            memory: result?,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Batch;
+    use crate::{Address, Memory};
+    use anyhow::Result;
+    use pretty_assertions::assert_eq;
+
+    const IS_INPUT_EQUAL_TO_8: &str = "3,9,8,9,10,9,4,9,99,-1,8";
+
+    #[test]
+    fn results_are_in_input_order() -> Result<()> {
+        crate::init_logging();
+        let memory: Memory = IS_INPUT_EQUAL_TO_8.parse()?;
+
+        let results = Batch::new(memory)
+            .threads(4)
+            .evaluate((0..20).map(|i| vec![i]));
+
+        let outputs = results
+            .into_iter()
+            .map(|r| Ok(r?.outputs))
+            .collect::<Result<Vec<_>>>()?;
+        let expected: Vec<_> = (0..20).map(|i| vec![if i == 8 { 1 } else { 0 }]).collect();
+
+        assert_eq!(expected, outputs);
+
+        Ok(())
+    }
+
+    #[test]
+    fn find_first_returns_earliest_match() -> Result<()> {
+        crate::init_logging();
+        let memory: Memory = "1,0,0,0,99".parse()?;
+
+        let found = Batch::new(memory)
+            .threads(3)
+            .patching(&[Address::new(1), Address::new(2)])
+            .find_first((0..5).flat_map(|a| (0..5).map(move |b| vec![a, b])), |e| {
+                e.memory.read_or_default(Address::new(0)) == 100
+            })?;
+
+        // Addresses 0 and 4 hold 1 and 99 respectively
+        let (idx, evaluation) = found.expect("a match");
+        assert_eq!(4, idx);
+        assert_eq!(100, evaluation.memory.read_or_default(Address::ZERO));
+
+        Ok(())
+    }
+
+    #[test]
+    fn find_first_reports_earlier_errors() -> Result<()> {
+        crate::init_logging();
+        let memory: Memory = "3,0,99".parse()?;
+
+        let result = Batch::new(memory).find_first(vec![vec![], vec![1]], |_| true);
+
+        assert!(result.is_err());
+
+        Ok(())
+    }
+}
@@ -0,0 +1,310 @@
+use anyhow::{anyhow, Context, Result};
+use futures::{
+    channel::mpsc::{channel, Receiver, Sender},
+    SinkExt, StreamExt,
+};
+use intcode::{
+    Address, AsciiTerminal, AsyncExecutable, ErrorKind, ExecutionError, Image, Memory,
+    NonAsciiPolicy, Recording, SelfModification, Word,
+};
+use std::{
+    fs,
+    io::{self, Write},
+    path::PathBuf,
+    process,
+    str::FromStr,
+};
+use structopt::StructOpt;
+use tokio::{
+    io::{AsyncBufReadExt, BufReader},
+    runtime::Runtime,
+};
+
+/// Runs an Intcode program
+///
+/// By default, input is read from stdin as integers separated by commas or
+/// whitespace, and each output is written to stdout on its own line.
+#[derive(StructOpt, Debug)]
+struct Opt {
+    /// The program file to run, as text or a binary image
+    #[structopt(parse(from_os_str))]
+    program: PathBuf,
+
+    /// Exchange input and output as ASCII text
+    #[structopt(short, long)]
+    ascii: bool,
+
+    /// Provides input instead of reading from stdin
+    ///
+    /// Values may be separated by commas. In ASCII mode, each value is sent
+    /// as a line of text before input is read from stdin.
+    #[structopt(short, long, number_of_values = 1)]
+    input: Vec<String>,
+
+    /// Writes a value into memory before execution, as `address=value`
+    #[structopt(short, long, number_of_values = 1)]
+    patch: Vec<Patch>,
+
+    /// Writes the final memory to a file after the program halts, or to
+    /// stdout if given `-`
+    #[structopt(long, parse(from_os_str))]
+    dump: Option<PathBuf>,
+
+    /// Dumps memory as a compact binary image rather than as text
+    #[structopt(long, requires = "dump")]
+    image: bool,
+
+    /// Records an ASCII session to a file in asciinema format
+    #[structopt(long, parse(from_os_str), requires = "ascii")]
+    record: Option<PathBuf>,
+
+    /// Re-runs the program with the input from a recorded session, failing
+    /// if its output differs from the recording
+    #[structopt(long, parse(from_os_str), conflicts_with_all = &["ascii", "input", "record"])]
+    replay: Option<PathBuf>,
+
+    /// How to handle non-ASCII output in ASCII mode: `format` writes values
+    /// inline, `collect` prints them after the program halts, and `stop`
+    /// prints the first one and stops the program
+    #[structopt(long, parse(try_from_str = parse_policy), requires = "ascii")]
+    non_ascii: Option<NonAsciiPolicy>,
+
+    /// Reports to stderr each write to code which has executed and each
+    /// instruction executed from data the program wrote
+    #[structopt(long, conflicts_with_all = &["ascii", "replay"])]
+    self_modification: bool,
+}
+
+fn parse_policy(s: &str) -> Result<NonAsciiPolicy> {
+    match s {
+        "format" => Ok(NonAsciiPolicy::Format),
+        "collect" => Ok(NonAsciiPolicy::Collect),
+        "stop" => Ok(NonAsciiPolicy::Stop),
+        _ => Err(anyhow!("expected `format`, `collect` or `stop`")),
+    }
+}
+
+#[derive(Debug)]
+struct Patch {
+    address: Address,
+    value: Word,
+}
+
+impl FromStr for Patch {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let (address, value) = s
+            .split_once('=')
+            .ok_or_else(|| anyhow!("expected `address=value`"))?;
+        Ok(Self {
+            address: Address::new(address.trim().parse()?),
+            value: value.trim().parse()?,
+        })
+    }
+}
+
+fn main() {
+    env_logger::init();
+    let opt = Opt::from_args();
+
+    // The runtime is never dropped, as doing so would wait for any pending
+    // read from stdin to complete after the program has halted
+    let mut runtime = match tokio::runtime::Runtime::new() {
+        Ok(runtime) => runtime,
+        Err(e) => {
+            eprintln!("error: unable to start runtime: {}", e);
+            process::exit(1);
+        }
+    };
+
+    let code = match run(opt, &mut runtime) {
+        Ok(()) => 0,
+        Err(e) => match e.downcast_ref::<ExecutionError>() {
+            Some(e) => {
+                eprintln!("error: {}", e);
+                exit_code(e.kind())
+            }
+            None => {
+                eprintln!("error: {:#}", e);
+                1
+            }
+        },
+    };
+
+    process::exit(code);
+}
+
+/// Distinguishes the ways in which execution can fail
+///
+/// A status of `1` is reserved for failures outside of execution, such as an
+/// unreadable program file.
+fn exit_code(kind: ErrorKind) -> i32 {
+    match kind {
+        ErrorKind::InvalidInstruction => 2,
+        ErrorKind::DecodeError => 3,
+        ErrorKind::InvalidAddress => 4,
+        ErrorKind::OutOfBoundsAccess => 5,
+        ErrorKind::UnexpectedEndOfProgram => 6,
+        ErrorKind::UnexpectedEndOfInput => 7,
+        ErrorKind::OutputPipeClosed => 8,
+        ErrorKind::Cancelled => 9,
+    }
+}
+
+fn run(opt: Opt, runtime: &mut Runtime) -> Result<()> {
+    let file = fs::File::open(&opt.program)
+        .with_context(|| format!("unable to read {}", opt.program.display()))?;
+    let mut image = Image::load(&mut io::BufReader::new(file))
+        .with_context(|| format!("unable to load {}", opt.program.display()))?;
+
+    for patch in &opt.patch {
+        image
+            .memory_mut()
+            .write_arbitrary(patch.address, patch.value);
+    }
+
+    if let Some(path) = opt.replay {
+        let file =
+            fs::File::open(&path).with_context(|| format!("unable to read {}", path.display()))?;
+        let recording = Recording::from_reader(io::BufReader::new(file))
+            .with_context(|| format!("unable to parse {}", path.display()))?;
+        return match recording.replay(image)? {
+            Some(diff) => Err(anyhow!("{}", diff)),
+            None => Ok(()),
+        };
+    }
+
+    let memory = if opt.ascii {
+        let policy = opt.non_ascii.unwrap_or_default();
+        let term = AsciiTerminal::with_input(image, opt.input).non_ascii(policy);
+        let exit = match &opt.record {
+            Some(path) => {
+                let (result, recording) = runtime.block_on(term.execute_recorded());
+                let file = fs::File::create(path)
+                    .with_context(|| format!("unable to write {}", path.display()))?;
+                recording.to_writer(io::BufWriter::new(file))?;
+                result?
+            }
+            None => runtime.block_on(term.execute())?,
+        };
+        // Formatted values have already been written inline
+        if policy != NonAsciiPolicy::Format {
+            for value in &exit.values {
+                println!("{}", value);
+            }
+        }
+        match exit.memory {
+            Some(memory) => memory,
+            // Stopped on a value, so there is no final memory to dump
+            None => return Ok(()),
+        }
+    } else {
+        let inputs = parse_words(&opt.input.join(","))?;
+        let read_stdin = opt.input.is_empty();
+        runtime.block_on(run_numeric(
+            image,
+            inputs,
+            read_stdin,
+            opt.self_modification,
+        ))?
+    };
+
+    if let Some(path) = opt.dump {
+        let mut dump = Vec::new();
+        if opt.image {
+            memory.to_writer(&mut dump)?;
+        } else {
+            let text = memory
+                .iter()
+                .map(|w| w.to_string())
+                .collect::<Vec<_>>()
+                .join(",");
+            dump.extend_from_slice(text.as_bytes());
+            dump.push(b'\n');
+        }
+
+        if path.as_os_str() == "-" {
+            io::stdout().write_all(&dump)?;
+        } else {
+            fs::write(&path, dump)
+                .with_context(|| format!("unable to write {}", path.display()))?;
+        }
+    }
+
+    Ok(())
+}
+
+async fn run_numeric(
+    image: Image,
+    inputs: Vec<Word>,
+    read_stdin: bool,
+    self_modification: bool,
+) -> Result<Memory> {
+    let mut exe = AsyncExecutable::from(image);
+    let (input_tx, input_rx) = channel(20);
+    let (output_tx, output_rx) = channel(20);
+    exe.pipe_inputs_from(input_rx);
+    exe.pipe_outputs_to(output_tx);
+
+    tokio::spawn(async move {
+        if let Err(e) = send_inputs(input_tx, inputs, read_stdin).await {
+            eprintln!("error: {:#}", e);
+        }
+    });
+    let out = tokio::spawn(print_outputs(output_rx));
+    let mut detector = SelfModification::default();
+    let result = if self_modification {
+        exe.with_observer(&mut detector).execute().await
+    } else {
+        exe.execute().await
+    };
+    out.await?;
+    if self_modification {
+        eprintln!("{}", detector);
+    }
+
+    Ok(result?)
+}
+
+async fn send_inputs(mut input: Sender<Word>, inputs: Vec<Word>, read_stdin: bool) -> Result<()> {
+    for value in inputs {
+        if input.send(value).await.is_err() {
+            return Ok(());
+        }
+    }
+
+    if !read_stdin {
+        return Ok(());
+    }
+
+    let mut lines = BufReader::new(tokio::io::stdin()).lines();
+    while let Some(line) = lines.next_line().await? {
+        for value in parse_words(&line)? {
+            if input.send(value).await.is_err() {
+                return Ok(());
+            }
+        }
+    }
+
+    Ok(())
+}
+
+async fn print_outputs(mut output: Receiver<Word>) {
+    let stdout = std::io::stdout();
+    while let Some(value) = output.next().await {
+        let mut out = stdout.lock();
+        let _ = writeln!(out, "{}", value);
+        let _ = out.flush();
+    }
+}
+
+fn parse_words(s: &str) -> Result<Vec<Word>> {
+    s.split(|c: char| c == ',' || c.is_whitespace())
+        .filter(|v| !v.is_empty())
+        .map(|v| {
+            v.parse()
+                .with_context(|| format!("invalid input value `{}`", v))
+        })
+        .collect()
+}
@@ -0,0 +1,62 @@
+use futures::task::AtomicWaker;
+use std::{
+    future::Future,
+    pin::Pin,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    task::{Context, Poll},
+};
+
+#[derive(Debug, Default)]
+struct CancelState {
+    cancelled: AtomicBool,
+    waker: AtomicWaker,
+}
+
+/// A handle which can request that a running `AsyncExecutable` stop
+///
+/// Execution stops at the next instruction boundary. An executable which is
+/// waiting on input or output is woken and stops without executing the
+/// pending instruction, so it may later be resumed.
+#[derive(Clone, Debug, Default)]
+pub struct CancelHandle(Arc<CancelState>);
+
+impl CancelHandle {
+    /// Requests that the associated executable stop
+    pub fn cancel(&self) {
+        self.0.cancelled.store(true, Ordering::Release);
+        self.0.waker.wake();
+    }
+
+    /// Whether cancellation has been requested
+    pub fn is_cancelled(&self) -> bool {
+        self.0.cancelled.load(Ordering::Acquire)
+    }
+
+    /// A future which resolves once cancellation has been requested
+    pub(crate) fn cancelled(&self) -> Cancelled<'_> {
+        Cancelled(&self.0)
+    }
+}
+
+pub(crate) struct Cancelled<'a>(&'a CancelState);
+
+impl Future for Cancelled<'_> {
+    type Output = ();
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context) -> Poll<()> {
+        if self.0.cancelled.load(Ordering::Acquire) {
+            return Poll::Ready(());
+        }
+
+        self.0.waker.register(cx.waker());
+
+        if self.0.cancelled.load(Ordering::Acquire) {
+            Poll::Ready(())
+        } else {
+            Poll::Pending
+        }
+    }
+}
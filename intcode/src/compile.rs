@@ -0,0 +1,428 @@
+mod codegen;
+mod lexer;
+mod parser;
+
+use super::{DebugInfo, Image, Memory, Module};
+use thiserror::Error;
+
+/// A problem with a program's source, and where it was found
+#[derive(Error, Clone, Debug, PartialEq, Eq)]
+#[error("line {line}, column {column}: {message}")]
+pub struct CompileError {
+    line: usize,
+    column: usize,
+    message: String,
+}
+
+impl CompileError {
+    pub(crate) fn new(line: usize, column: usize, message: impl Into<String>) -> Self {
+        Self {
+            line,
+            column,
+            message: message.into(),
+        }
+    }
+
+    /// The line of source, counting from 1
+    pub fn line(&self) -> usize {
+        self.line
+    }
+
+    /// The column within the line, counting from 1
+    pub fn column(&self) -> usize {
+        self.column
+    }
+
+    /// A description of the problem
+    pub fn message(&self) -> &str {
+        &self.message
+    }
+}
+
+/// A compiled program, along with debug information relating it to its
+/// source
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Compiled {
+    /// The program
+    pub memory: Memory,
+    /// Labels for each function, the line of source for each statement, and
+    /// the data the program uses
+    pub debug_info: DebugInfo,
+    source_hash: u64,
+    relocations: Vec<usize>,
+    imports: Vec<(usize, String)>,
+    stack: usize,
+}
+
+impl Compiled {
+    /// Packages the program as an image, with functions as symbols
+    pub fn image(&self) -> Image {
+        self.debug_info.labels().fold(
+            Image::new(self.memory.clone()).with_source_hash(self.source_hash),
+            |image, (address, name)| image.with_symbol(address, name),
+        )
+    }
+
+    /// Packages the program as a module, to link with libraries such as
+    /// `runtime_library()`
+    ///
+    /// Words holding addresses within the program are relocated, and
+    /// external functions are imported. The stack is moved from the end of
+    /// the program, where it would grow into whatever is linked next, to
+    /// `stack_start`, which `heap_start()` reserves. The start of the program
+    /// is exported as `start`.
+    ///
+    /// ## Example
+    ///
+    /// ```
+    /// use intcode::{compile, heap_start, runtime_library, Executable, Linker};
+    ///
+    /// let compiled = compile(
+    ///     "extern fn print_number(n);
+    ///      fn main() { print_number(6 * 7); }",
+    /// )
+    /// .expect("valid source");
+    ///
+    /// let linked = Linker::default()
+    ///     .with_module(compiled.module("main"))
+    ///     .with_module(runtime_library())
+    ///     .with_module(heap_start())
+    ///     .link()
+    ///     .expect("all symbols are defined");
+    ///
+    /// let mut exe = Executable::from(linked.into_memory());
+    /// let drain = exe.drain();
+    /// exe.run().expect("successful execution");
+    /// drop(exe);
+    /// let text: String = drain.to_vec().into_iter().map(|c| c as u8 as char).collect();
+    /// assert_eq!("42", text);
+    /// ```
+    pub fn module(&self, name: impl Into<String>) -> Module {
+        let mut words = self.memory.to_vec();
+        words[self.stack] = 0;
+        let module = Module::new(name, Memory::from_vec(words)).with_export("start", 0);
+        let module = self
+            .relocations
+            .iter()
+            .fold(module, |m, &at| m.with_relocation(at));
+        let module = self
+            .imports
+            .iter()
+            .fold(module, |m, (at, name)| m.with_import(*at, name.as_str()));
+        module.with_import(self.stack, "stack_start")
+    }
+}
+
+impl From<Compiled> for Memory {
+    fn from(compiled: Compiled) -> Self {
+        compiled.memory
+    }
+}
+
+/// Compiles a program written in a small structured language to Intcode
+///
+/// A program is a list of functions, one of which must be `main`. Every
+/// value is a `Word`, and a name is introduced with `let` before it is used.
+///
+/// ```text
+/// fn main() {
+///     let n = input();
+///     // Count down, stopping early at 3
+///     while n > 0 {
+///         if n == 3 {
+///             break;
+///         }
+///         output(square(n));
+///         n = n - 1;
+///     }
+///     print("done\n");
+/// }
+///
+/// fn square(x) {
+///     return x * x;
+/// }
+/// ```
+///
+/// Statements are `let`, assignment, `if`/`else`, `while` with `break` and
+/// `continue`, `return`, `output(value)`, `print("text")` and bare
+/// expressions. Expressions support `+`, `-`, `*`, comparisons, `&&`, `||`
+/// and `!`, with the usual precedence, as well as function calls, `input()`,
+/// numbers and character literals such as `'a'`. A comparison is `1` when it
+/// holds and `0` otherwise, and any non-zero value is taken as true.
+///
+/// Functions keep their parameters, locals and temporaries in a frame
+/// addressed through the relative base, so they may recurse. The stack of
+/// frames grows upwards from the end of the program. Returning from `main`
+/// halts the program.
+///
+/// A function declared as `extern fn name(a, b);` is defined by another
+/// module, such as `runtime_library()`, and follows its calling convention,
+/// so it takes at most two arguments. A program using one must be linked
+/// through `Compiled::module` before it can run.
+///
+/// ## Example
+///
+/// ```
+/// use intcode::{compile, Address, Executable};
+///
+/// let compiled = compile("fn main() { output(6 * 7); }").expect("valid source");
+/// assert_eq!(Some("main"), compiled.debug_info.label(Address::new(10)));
+///
+/// let mut exe = Executable::from(compiled.memory);
+/// let drain = exe.drain();
+/// exe.run().expect("successful execution");
+/// drop(exe);
+/// assert_eq!(vec![42], drain.to_vec());
+/// ```
+pub fn compile(source: &str) -> Result<Compiled, CompileError> {
+    let tokens = lexer::tokenize(source)?;
+    let functions = parser::parse(&tokens)?;
+    let generated = codegen::Generator::new().program(&functions)?;
+    Ok(Compiled {
+        memory: Memory::from_vec(generated.words),
+        debug_info: generated.debug_info.with_source(source),
+        source_hash: Image::hash_source(source),
+        relocations: generated.relocations,
+        imports: generated.imports,
+        stack: generated.stack,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::compile;
+    use crate::{heap_start, runtime_library, Address, Executable, Linker, Module, Word};
+    use anyhow::Result;
+    use pretty_assertions::assert_eq;
+    use std::sync::{mpsc::channel, Arc};
+
+    fn run(source: &str, inputs: &[Word]) -> Result<Vec<Word>> {
+        let compiled = compile(source)?;
+        let mut exe =
+            Executable::from(compiled.memory).with_debug_info(Arc::new(compiled.debug_info));
+        let (tx, rx) = channel();
+        for &value in inputs {
+            tx.send(value)?;
+        }
+        exe.pipe_inputs_from(rx);
+        let drain = exe.drain();
+        exe.run()?;
+        drop(exe);
+        Ok(drain.to_vec())
+    }
+
+    #[test]
+    fn evaluates_expressions() -> Result<()> {
+        crate::init_logging();
+        const SOURCE: &str = "
+            fn main() {
+                let a = input();
+                let b = input();
+                output(a + b);
+                output(a - b);
+                output(a * b + 1);
+                output(-a);
+                output(a < b);
+                output(a >= b);
+                output(a != b && b != 0);
+                output(!(a == b) || input());
+                output(2 * (3 + 4) - 'A');
+            }";
+
+        assert_eq!(vec![10, 4, 22, -7, 0, 1, 1, 1, -51], run(SOURCE, &[7, 3])?);
+
+        Ok(())
+    }
+
+    #[test]
+    fn runs_loops_and_branches() -> Result<()> {
+        crate::init_logging();
+        const SOURCE: &str = "
+            fn main() {
+                let n = input();
+                let total = 0;
+                let i = 0;
+                while 1 {
+                    i = i + 1;
+                    if i > n {
+                        break;
+                    } else if i == 3 {
+                        continue;
+                    }
+                    total = total + i;
+                }
+                output(total);
+            }";
+
+        assert_eq!(vec![52], run(SOURCE, &[10])?);
+        assert_eq!(vec![0], run(SOURCE, &[0])?);
+
+        Ok(())
+    }
+
+    #[test]
+    fn functions_recurse_on_the_stack() -> Result<()> {
+        crate::init_logging();
+        const SOURCE: &str = "
+            fn fib(n) {
+                if n < 2 {
+                    return n;
+                }
+                return fib(n - 1) + fib(n - 2);
+            }
+
+            fn pow(base, exp) {
+                let result = 1;
+                while exp > 0 {
+                    result = result * base;
+                    exp = exp - 1;
+                }
+                return result;
+            }
+
+            fn main() {
+                let x = input();
+                output(fib(x));
+                output(pow(2, fib(x)) + pow(3, 2));
+            }";
+
+        assert_eq!(vec![55, (1 << 55) + 9], run(SOURCE, &[10])?);
+
+        Ok(())
+    }
+
+    #[test]
+    fn prints_strings() -> Result<()> {
+        crate::init_logging();
+        const SOURCE: &str = r#"
+            fn main() {
+                print("hi\n");
+                output(input());
+            }"#;
+
+        let outputs = run(SOURCE, &[-1])?;
+
+        assert_eq!(vec!['h' as Word, 'i' as Word, '\n' as Word, -1], outputs);
+
+        Ok(())
+    }
+
+    #[test]
+    fn reports_errors_with_location() {
+        crate::init_logging();
+        let cases = [
+            (
+                "fn main() {\n  x = 1;\n}",
+                "line 2, column 3: unknown variable `x`",
+            ),
+            (
+                "fn main() { f(1); }",
+                "line 1, column 13: unknown function `f`",
+            ),
+            (
+                "fn f(a) {}\nfn main() { f(); }",
+                "line 2, column 13: `f` takes 1 argument(s) but 0 were given",
+            ),
+            (
+                "fn main() { break; }",
+                "line 1, column 13: not inside a loop",
+            ),
+            (
+                "fn main() { let 1 = 2; }",
+                "line 1, column 17: expected a name",
+            ),
+            ("fn f() {}", "line 1, column 1: no `main` function"),
+            (
+                "fn main() { print(\"x); }",
+                "line 1, column 19: unterminated string",
+            ),
+            (
+                "extern fn f(a, b, c);\nfn main() {}",
+                "line 1, column 1: external function `f` takes more than 2 arguments",
+            ),
+        ];
+
+        for (source, expected) in &cases {
+            let error = compile(source).unwrap_err();
+            assert_eq!(*expected, error.to_string(), "{}", source);
+        }
+    }
+
+    #[test]
+    fn links_against_the_runtime_library() -> Result<()> {
+        crate::init_logging();
+        const SOURCE: &str = r#"
+            extern fn malloc(size);
+            extern fn print_number(n);
+
+            fn fib(n) {
+                if n < 2 {
+                    return n;
+                }
+                return fib(n - 1) + fib(n - 2);
+            }
+
+            fn main() {
+                let block = malloc(4);
+                print_number(fib(input()));
+                print(" ");
+                print_number(block);
+            }"#;
+        let compiled = compile(SOURCE)?;
+
+        // Placing the program after another module moves every address in it
+        let boot = Module::new("boot", "1105,1,0".parse()?).with_import(2, "start");
+        let linked = Linker::default()
+            .with_module(boot)
+            .with_module(compiled.module("main"))
+            .with_module(runtime_library())
+            .with_module(heap_start())
+            .link()?;
+        let heap = linked.symbol("heap_start").expect("heap is linked");
+
+        let mut exe = Executable::from(linked.into_memory());
+        exe.single_input(20);
+        let drain = exe.drain();
+        exe.run()?;
+        drop(exe);
+        let text: String = drain
+            .to_vec()
+            .into_iter()
+            .map(|c| c as u8 as char)
+            .collect();
+        // The recursion runs on the reserved stack, clear of both the
+        // runtime and the block
+        assert_eq!(format!("6765 {}", heap.value() + 1), text);
+
+        Ok(())
+    }
+
+    #[test]
+    fn debug_info_maps_to_source() -> Result<()> {
+        crate::init_logging();
+        const SOURCE: &str = "fn main() {\n    let x = input();\n    output(x - 1);\n}";
+        let compiled = compile(SOURCE)?;
+
+        let main = compiled
+            .debug_info
+            .labels()
+            .find(|(_, name)| *name == "main")
+            .map(|(address, _)| address)
+            .expect("main is labelled");
+        let image = compiled.image();
+        assert_eq!(Some("main"), image.symbol(main));
+        assert_eq!(Some(crate::Image::hash_source(SOURCE)), image.source_hash());
+
+        // The input instruction is the first thing main does
+        assert_eq!(
+            "main (line 2: let x = input();)",
+            compiled.debug_info.locate(main).to_string()
+        );
+        assert_eq!(
+            Some(3),
+            compiled.debug_info.line(Address::new(main.value() + 2))
+        );
+
+        Ok(())
+    }
+}
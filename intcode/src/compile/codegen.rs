@@ -0,0 +1,535 @@
+use super::{
+    parser::{BinOp, Expr, Function, Stmt, StmtKind},
+    CompileError,
+};
+use crate::{
+    emit::{self, Emitter},
+    ops::OpCode,
+    Address, DebugInfo, Word,
+};
+use std::collections::HashMap;
+
+type Label = usize;
+type Operand = emit::Operand<Label>;
+
+/// The cells through which external functions take their arguments, in
+/// order, following the convention of the runtime library
+const EXTERNAL_ARGS: [&str; 2] = ["rt_arg0", "rt_arg1"];
+
+struct Signature {
+    label: Label,
+    arity: usize,
+    external: bool,
+}
+
+/// A program laid out by `Generator::program`
+pub(super) struct Generated {
+    pub words: Vec<Word>,
+    pub debug_info: DebugInfo,
+    /// The words holding an address within the program
+    pub relocations: Vec<usize>,
+    /// The words holding the address of a symbol defined by another module
+    pub imports: Vec<(usize, String)>,
+    /// The word holding the address the stack starts at
+    pub stack: usize,
+}
+
+pub(super) struct Generator<'a> {
+    code: Emitter<Label>,
+    next_label: Label,
+    /// Words holding the size of the current frame, optionally negated, plus
+    /// an offset, filled in once the current function has been laid out
+    frame_fixups: Vec<(usize, bool, Word)>,
+    functions: HashMap<&'a str, Signature>,
+    ret: Label,
+    /// Labels standing for symbols defined by other modules
+    externals: HashMap<&'a str, Label>,
+    debug: DebugInfo,
+    // State for the function being generated
+    scopes: Vec<HashMap<&'a str, Word>>,
+    next_slot: Word,
+    frame_size: Word,
+    loops: Vec<(Label, Label)>,
+}
+
+impl<'a> Generator<'a> {
+    pub(super) fn new() -> Self {
+        Self {
+            code: Emitter::default(),
+            next_label: 0,
+            frame_fixups: Vec::new(),
+            functions: HashMap::new(),
+            ret: 0,
+            externals: HashMap::new(),
+            debug: DebugInfo::default(),
+            scopes: Vec::new(),
+            next_slot: 0,
+            frame_size: 0,
+            loops: Vec::new(),
+        }
+    }
+
+    /// Lays out the whole program
+    ///
+    /// The program sets the relative base to the start of the stack, which
+    /// lies past the end of the program, then calls `main` with a return
+    /// address pointing at a halt instruction. External functions are left
+    /// for the linker to resolve.
+    pub(super) fn program(mut self, functions: &'a [Function]) -> Result<Generated, CompileError> {
+        for f in functions {
+            if f.external && f.params.len() > EXTERNAL_ARGS.len() {
+                return Err(CompileError::new(
+                    f.line,
+                    f.column,
+                    format!(
+                        "external function `{}` takes more than {} arguments",
+                        f.name,
+                        EXTERNAL_ARGS.len()
+                    ),
+                ));
+            }
+            let label = if f.external {
+                self.external(&f.name)
+            } else {
+                self.label()
+            };
+            let signature = Signature {
+                label,
+                arity: f.params.len(),
+                external: f.external,
+            };
+            if self.functions.insert(&f.name, signature).is_some() {
+                return Err(CompileError::new(
+                    f.line,
+                    f.column,
+                    format!("function `{}` is defined twice", f.name),
+                ));
+            }
+        }
+        let main = match functions.iter().find(|f| f.name == "main" && !f.external) {
+            Some(f) if !f.params.is_empty() => {
+                return Err(CompileError::new(
+                    f.line,
+                    f.column,
+                    "`main` takes no parameters",
+                ))
+            }
+            Some(_) => self.functions["main"].label,
+            None => return Err(CompileError::new(1, 1, "no `main` function")),
+        };
+
+        let (halt, stack) = (self.label(), self.label());
+        self.ret = self.label();
+        self.debug = std::mem::take(&mut self.debug).with_label(Address::ZERO, "start");
+        let stack_pointer = self.code.emit(OpCode::AddRel, &[Operand::Addr(stack)]) + 1;
+        self.code.emit(
+            OpCode::Add,
+            &[Operand::Addr(halt), Operand::Imm(0), Operand::Rel(0)],
+        );
+        self.code.jump(main);
+        self.place_named(halt, "halt");
+        self.code.emit(OpCode::Halt, &[]);
+
+        for f in functions.iter().filter(|f| !f.external) {
+            self.function(f)?;
+        }
+
+        let data_start = Address::new(self.code.here());
+        self.debug = std::mem::take(&mut self.debug).with_label(data_start, "ret");
+        self.code.data(self.ret, &[0]);
+        self.debug =
+            std::mem::take(&mut self.debug).with_data(data_start..Address::new(self.code.here()));
+        self.place(stack);
+
+        let emitted = self.code.finish();
+        let names: HashMap<Label, &str> = self
+            .externals
+            .into_iter()
+            .map(|(name, label)| (label, name))
+            .collect();
+        let imports = emitted
+            .unresolved
+            .into_iter()
+            .map(|(at, label)| (at, names[&label].to_string()))
+            .collect();
+        Ok(Generated {
+            words: emitted.words,
+            debug_info: self.debug,
+            relocations: emitted.relocations,
+            imports,
+            stack: stack_pointer,
+        })
+    }
+
+    fn function(&mut self, f: &'a Function) -> Result<(), CompileError> {
+        self.place_named(self.functions[f.name.as_str()].label, &f.name);
+        let mut params = HashMap::new();
+        for (i, p) in f.params.iter().enumerate() {
+            if params.insert(p.as_str(), i as Word + 1).is_some() {
+                return Err(CompileError::new(
+                    f.line,
+                    f.column,
+                    format!("parameter `{}` is declared twice", p),
+                ));
+            }
+        }
+        self.scopes = vec![params];
+        self.next_slot = f.params.len() as Word + 1;
+        self.frame_size = self.next_slot;
+
+        self.line(f.line);
+        self.block(&f.body)?;
+        self.line(f.line);
+        self.ret(Operand::Imm(0));
+
+        for (at, negate, offset) in self.frame_fixups.drain(..) {
+            let size = if negate {
+                -self.frame_size
+            } else {
+                self.frame_size
+            };
+            self.code.patch(at, size + offset);
+        }
+        Ok(())
+    }
+
+    fn block(&mut self, stmts: &'a [Stmt]) -> Result<(), CompileError> {
+        let mark = self.next_slot;
+        self.scopes.push(HashMap::new());
+        for stmt in stmts {
+            self.statement(stmt)?;
+        }
+        self.scopes.pop();
+        self.next_slot = mark;
+        Ok(())
+    }
+
+    fn statement(&mut self, stmt: &'a Stmt) -> Result<(), CompileError> {
+        self.line(stmt.line);
+        let mark = self.next_slot;
+        match &stmt.kind {
+            StmtKind::Let(name, value) => {
+                let value = self.expr(value)?;
+                self.next_slot = mark;
+                let slot = self.alloc();
+                self.code.copy(value, Operand::Rel(slot));
+                self.scopes
+                    .last_mut()
+                    .expect("inside a block")
+                    .insert(name, slot);
+                return Ok(());
+            }
+            StmtKind::Assign(name, value) => {
+                let slot = self.lookup(name, stmt.line, stmt.column)?;
+                let value = self.expr(value)?;
+                self.code.copy(value, Operand::Rel(slot));
+            }
+            StmtKind::If(cond, then, otherwise) => {
+                let (other, end) = (self.label(), self.label());
+                let cond = self.expr(cond)?;
+                self.next_slot = mark;
+                self.code
+                    .emit(OpCode::JumpZero, &[cond, Operand::Addr(other)]);
+                self.block(then)?;
+                if !otherwise.is_empty() {
+                    self.code.jump(end);
+                }
+                self.place(other);
+                self.block(otherwise)?;
+                self.place(end);
+            }
+            StmtKind::While(cond, body) => {
+                let (top, end) = (self.label(), self.label());
+                self.place(top);
+                let cond = self.expr(cond)?;
+                self.next_slot = mark;
+                self.code
+                    .emit(OpCode::JumpZero, &[cond, Operand::Addr(end)]);
+                self.loops.push((top, end));
+                self.block(body)?;
+                self.loops.pop();
+                self.line(stmt.line);
+                self.code.jump(top);
+                self.place(end);
+            }
+            StmtKind::Break | StmtKind::Continue => {
+                let (top, end) = *self.loops.last().ok_or_else(|| {
+                    CompileError::new(stmt.line, stmt.column, "not inside a loop")
+                })?;
+                self.code.jump(if stmt.kind == StmtKind::Break {
+                    end
+                } else {
+                    top
+                });
+            }
+            StmtKind::Return(value) => {
+                let value = match value {
+                    Some(value) => self.expr(value)?,
+                    None => Operand::Imm(0),
+                };
+                self.ret(value);
+            }
+            StmtKind::Output(value) => {
+                let value = self.expr(value)?;
+                self.code.emit(OpCode::Output, &[value]);
+            }
+            StmtKind::Print(text) => {
+                for c in text.chars() {
+                    self.code.emit(OpCode::Output, &[Operand::Imm(c as Word)]);
+                }
+            }
+            StmtKind::Expr(value) => {
+                self.expr(value)?;
+            }
+        }
+        self.next_slot = mark;
+        Ok(())
+    }
+
+    /// Generates code to compute an expression, returning where to find
+    /// its value
+    ///
+    /// Constant subexpressions are folded, and variables are read in place.
+    fn expr(&mut self, expr: &'a Expr) -> Result<Operand, CompileError> {
+        Ok(match expr {
+            Expr::Number(n) => Operand::Imm(*n),
+            Expr::Var(name, line, column) => Operand::Rel(self.lookup(name, *line, *column)?),
+            Expr::Input => {
+                let t = Operand::Rel(self.alloc());
+                self.code.emit(OpCode::Input, &[t]);
+                t
+            }
+            Expr::Neg(value) => {
+                let value = self.expr(value)?;
+                self.negate(value)
+            }
+            Expr::Not(value) => {
+                let value = self.expr(value)?;
+                self.binary(OpCode::Equal, value, Operand::Imm(0))
+            }
+            Expr::Binary(BinOp::And, lhs, rhs) => self.short_circuit(false, lhs, rhs)?,
+            Expr::Binary(BinOp::Or, lhs, rhs) => self.short_circuit(true, lhs, rhs)?,
+            Expr::Binary(op, lhs, rhs) => {
+                let (a, b) = (self.expr(lhs)?, self.expr(rhs)?);
+                match op {
+                    BinOp::Add => self.binary(OpCode::Add, a, b),
+                    BinOp::Sub => {
+                        let b = self.negate(b);
+                        self.binary(OpCode::Add, a, b)
+                    }
+                    BinOp::Mul => self.binary(OpCode::Mul, a, b),
+                    BinOp::Eq => self.binary(OpCode::Equal, a, b),
+                    BinOp::Ne => self.invert(OpCode::Equal, a, b),
+                    BinOp::Lt => self.binary(OpCode::LessThan, a, b),
+                    BinOp::Gt => self.binary(OpCode::LessThan, b, a),
+                    BinOp::Le => self.invert(OpCode::LessThan, b, a),
+                    BinOp::Ge => self.invert(OpCode::LessThan, a, b),
+                    BinOp::And | BinOp::Or => unreachable!("handled above"),
+                }
+            }
+            Expr::Call(name, args, line, column) => self.call(name, args, *line, *column)?,
+        })
+    }
+
+    /// Generates `&&` or `||`, which only evaluate the right hand side when
+    /// the left hand side does not decide the result
+    fn short_circuit(
+        &mut self,
+        is_or: bool,
+        lhs: &'a Expr,
+        rhs: &'a Expr,
+    ) -> Result<Operand, CompileError> {
+        let end = self.label();
+        let t = Operand::Rel(self.alloc());
+        self.code.copy(Operand::Imm(is_or as Word), t);
+        let a = self.expr(lhs)?;
+        let opcode = if is_or {
+            OpCode::JumpNonZero
+        } else {
+            OpCode::JumpZero
+        };
+        self.code.emit(opcode, &[a, Operand::Addr(end)]);
+        let b = self.expr(rhs)?;
+        self.code.emit(OpCode::Equal, &[b, Operand::Imm(0), t]);
+        self.code.emit(OpCode::Equal, &[t, Operand::Imm(0), t]);
+        self.place(end);
+        Ok(t)
+    }
+
+    /// Calls a function
+    ///
+    /// Arguments and the return address are placed in the frame past the
+    /// current one, and the relative base is moved onto it for the
+    /// duration of the call. The result comes back through a global word.
+    fn call(
+        &mut self,
+        name: &str,
+        args: &'a [Expr],
+        line: usize,
+        column: usize,
+    ) -> Result<Operand, CompileError> {
+        let (label, arity, external) = match self.functions.get(name) {
+            Some(s) => (s.label, s.arity, s.external),
+            None => {
+                return Err(CompileError::new(
+                    line,
+                    column,
+                    format!("unknown function `{}`", name),
+                ))
+            }
+        };
+        if args.len() != arity {
+            return Err(CompileError::new(
+                line,
+                column,
+                format!(
+                    "`{}` takes {} argument(s) but {} were given",
+                    name,
+                    arity,
+                    args.len()
+                ),
+            ));
+        }
+
+        let mut values = Vec::with_capacity(args.len());
+        for arg in args {
+            values.push(self.expr(arg)?);
+        }
+        if external {
+            return Ok(self.call_external(label, values));
+        }
+        for (i, value) in values.into_iter().enumerate() {
+            self.copy_to_callee(value, i as Word + 1);
+        }
+        let back = self.label();
+        self.copy_to_callee(Operand::Addr(back), 0);
+        self.adjust_base_by_frame(false);
+        self.code.jump(label);
+        self.place(back);
+        self.adjust_base_by_frame(true);
+
+        let t = Operand::Rel(self.alloc());
+        self.code.copy(Operand::At(self.ret), t);
+        Ok(t)
+    }
+
+    /// Calls a function defined by another module, which takes its arguments
+    /// in global cells, returns to the address in `rt_ret` and leaves its
+    /// result in `rt_result`
+    ///
+    /// The callee does not touch the relative base, so the frame is left as
+    /// it is.
+    fn call_external(&mut self, label: Label, values: Vec<Operand>) -> Operand {
+        for (value, cell) in values.into_iter().zip(EXTERNAL_ARGS.iter().copied()) {
+            let cell = self.external(cell);
+            self.code.copy(value, Operand::At(cell));
+        }
+        let back = self.label();
+        let ret = self.external("rt_ret");
+        self.code.copy(Operand::Addr(back), Operand::At(ret));
+        self.code.jump(label);
+        self.place(back);
+
+        let t = Operand::Rel(self.alloc());
+        let result = self.external("rt_result");
+        self.code.copy(Operand::At(result), t);
+        t
+    }
+
+    /// Returns `value` to the caller, whose return address is the first
+    /// slot of the frame
+    fn ret(&mut self, value: Operand) {
+        self.code.copy(value, Operand::At(self.ret));
+        self.code.jump_through(Operand::Rel(0));
+    }
+
+    /// Copies `value` to a slot in the frame of a function about to be
+    /// called, which starts just past the current frame
+    fn copy_to_callee(&mut self, value: Operand, slot: Word) {
+        let at = self
+            .code
+            .emit(OpCode::Add, &[value, Operand::Imm(0), Operand::Rel(0)]);
+        self.frame_fixups.push((at + 3, false, slot));
+    }
+
+    /// Moves the relative base past the current frame, or back again
+    fn adjust_base_by_frame(&mut self, negate: bool) {
+        let at = self.code.emit(OpCode::AddRel, &[Operand::Imm(0)]);
+        self.frame_fixups.push((at + 1, negate, 0));
+    }
+
+    fn lookup(&self, name: &str, line: usize, column: usize) -> Result<Word, CompileError> {
+        self.scopes
+            .iter()
+            .rev()
+            .find_map(|scope| scope.get(name).copied())
+            .ok_or_else(|| CompileError::new(line, column, format!("unknown variable `{}`", name)))
+    }
+
+    fn alloc(&mut self) -> Word {
+        let slot = self.next_slot;
+        self.next_slot += 1;
+        self.frame_size = self.frame_size.max(self.next_slot);
+        slot
+    }
+
+    fn binary(&mut self, opcode: OpCode, a: Operand, b: Operand) -> Operand {
+        if let (Operand::Imm(x), Operand::Imm(y)) = (a, b) {
+            return Operand::Imm(match opcode {
+                OpCode::Add => x.wrapping_add(y),
+                OpCode::Mul => x.wrapping_mul(y),
+                OpCode::LessThan => (x < y) as Word,
+                OpCode::Equal => (x == y) as Word,
+                _ => unreachable!("not a binary opcode"),
+            });
+        }
+        let t = Operand::Rel(self.alloc());
+        self.code.emit(opcode, &[a, b, t]);
+        t
+    }
+
+    /// Computes the logical negation of a comparison
+    fn invert(&mut self, opcode: OpCode, a: Operand, b: Operand) -> Operand {
+        let value = self.binary(opcode, a, b);
+        self.binary(OpCode::Equal, value, Operand::Imm(0))
+    }
+
+    fn negate(&mut self, value: Operand) -> Operand {
+        match value {
+            Operand::Imm(n) => Operand::Imm(n.wrapping_neg()),
+            _ => self.binary(OpCode::Mul, value, Operand::Imm(-1)),
+        }
+    }
+
+    fn label(&mut self) -> Label {
+        self.next_label += 1;
+        self.next_label - 1
+    }
+
+    /// The label standing for a symbol defined by another module
+    fn external(&mut self, name: &'a str) -> Label {
+        match self.externals.get(name) {
+            Some(&label) => label,
+            None => {
+                let label = self.label();
+                self.externals.insert(name, label);
+                label
+            }
+        }
+    }
+
+    fn place(&mut self, label: Label) {
+        self.code.place(label);
+    }
+
+    fn place_named(&mut self, label: Label, name: &str) {
+        self.place(label);
+        let address = Address::new(self.code.here());
+        self.debug = std::mem::take(&mut self.debug).with_label(address, name);
+    }
+
+    fn line(&mut self, line: usize) {
+        let address = Address::new(self.code.here());
+        self.debug = std::mem::take(&mut self.debug).with_line(address, line);
+    }
+}
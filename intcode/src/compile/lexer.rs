@@ -0,0 +1,219 @@
+use super::CompileError;
+use crate::Word;
+
+/// A lexical token
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub(super) enum Token {
+    Number(Word),
+    Str(String),
+    Ident(String),
+    Fn,
+    Extern,
+    Let,
+    If,
+    Else,
+    While,
+    Break,
+    Continue,
+    Return,
+    LParen,
+    RParen,
+    LBrace,
+    RBrace,
+    Comma,
+    Semi,
+    Assign,
+    Plus,
+    Minus,
+    Star,
+    Bang,
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+    And,
+    Or,
+    Eof,
+}
+
+/// A token along with where it starts in the source
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub(super) struct Spanned {
+    pub token: Token,
+    pub line: usize,
+    pub column: usize,
+}
+
+pub(super) fn tokenize(source: &str) -> Result<Vec<Spanned>, CompileError> {
+    let mut tokens = Vec::new();
+    let mut chars = source.chars().peekable();
+    let (mut line, mut column) = (1, 1);
+
+    while let Some(&c) = chars.peek() {
+        let (start_line, start_column) = (line, column);
+        let mut bump = |chars: &mut std::iter::Peekable<std::str::Chars>| {
+            let c = chars.next();
+            if c == Some('\n') {
+                line += 1;
+                column = 1;
+            } else {
+                column += 1;
+            }
+            c
+        };
+
+        let token = match c {
+            _ if c.is_whitespace() => {
+                bump(&mut chars);
+                continue;
+            }
+            '/' => {
+                bump(&mut chars);
+                if chars.peek() != Some(&'/') {
+                    return Err(CompileError::new(
+                        start_line,
+                        start_column,
+                        "unexpected `/`",
+                    ));
+                }
+                while chars.peek().is_some_and(|&c| c != '\n') {
+                    bump(&mut chars);
+                }
+                continue;
+            }
+            '0'..='9' => {
+                let mut digits = String::new();
+                while let Some(&d) = chars.peek().filter(|d| d.is_ascii_digit()) {
+                    digits.push(d);
+                    bump(&mut chars);
+                }
+                let value = digits.parse().map_err(|_| {
+                    CompileError::new(start_line, start_column, "number is too large")
+                })?;
+                Token::Number(value)
+            }
+            'a'..='z' | 'A'..='Z' | '_' => {
+                let mut ident = String::new();
+                while let Some(&d) = chars.peek().filter(|d| d.is_alphanumeric() || **d == '_') {
+                    ident.push(d);
+                    bump(&mut chars);
+                }
+                match ident.as_str() {
+                    "fn" => Token::Fn,
+                    "extern" => Token::Extern,
+                    "let" => Token::Let,
+                    "if" => Token::If,
+                    "else" => Token::Else,
+                    "while" => Token::While,
+                    "break" => Token::Break,
+                    "continue" => Token::Continue,
+                    "return" => Token::Return,
+                    _ => Token::Ident(ident),
+                }
+            }
+            '"' => {
+                bump(&mut chars);
+                let mut text = String::new();
+                loop {
+                    match bump(&mut chars) {
+                        Some('"') => break,
+                        Some('\\') => match bump(&mut chars) {
+                            Some('n') => text.push('\n'),
+                            Some('t') => text.push('\t'),
+                            Some('\\') => text.push('\\'),
+                            Some('"') => text.push('"'),
+                            _ => {
+                                return Err(CompileError::new(
+                                    start_line,
+                                    start_column,
+                                    "unknown escape in string",
+                                ))
+                            }
+                        },
+                        Some(c) => text.push(c),
+                        None => {
+                            return Err(CompileError::new(
+                                start_line,
+                                start_column,
+                                "unterminated string",
+                            ))
+                        }
+                    }
+                }
+                Token::Str(text)
+            }
+            '\'' => {
+                bump(&mut chars);
+                let c = match bump(&mut chars) {
+                    Some('\\') => match bump(&mut chars) {
+                        Some('n') => '\n',
+                        Some('t') => '\t',
+                        Some(c) => c,
+                        None => '\\',
+                    },
+                    Some(c) => c,
+                    None => '\'',
+                };
+                if bump(&mut chars) != Some('\'') {
+                    return Err(CompileError::new(
+                        start_line,
+                        start_column,
+                        "unterminated character",
+                    ));
+                }
+                Token::Number(c as Word)
+            }
+            _ => {
+                bump(&mut chars);
+                let next = chars.peek().copied();
+                let mut pair = |token| {
+                    bump(&mut chars);
+                    token
+                };
+                match (c, next) {
+                    ('=', Some('=')) => pair(Token::Eq),
+                    ('!', Some('=')) => pair(Token::Ne),
+                    ('<', Some('=')) => pair(Token::Le),
+                    ('>', Some('=')) => pair(Token::Ge),
+                    ('&', Some('&')) => pair(Token::And),
+                    ('|', Some('|')) => pair(Token::Or),
+                    ('=', _) => Token::Assign,
+                    ('!', _) => Token::Bang,
+                    ('<', _) => Token::Lt,
+                    ('>', _) => Token::Gt,
+                    ('+', _) => Token::Plus,
+                    ('-', _) => Token::Minus,
+                    ('*', _) => Token::Star,
+                    ('(', _) => Token::LParen,
+                    (')', _) => Token::RParen,
+                    ('{', _) => Token::LBrace,
+                    ('}', _) => Token::RBrace,
+                    (',', _) => Token::Comma,
+                    (';', _) => Token::Semi,
+                    _ => {
+                        return Err(CompileError::new(
+                            start_line,
+                            start_column,
+                            format!("unexpected character `{}`", c),
+                        ))
+                    }
+                }
+            }
+        };
+
+        tokens.push(Spanned {
+            token,
+            line: start_line,
+            column: start_column,
+        });
+    }
+
+    tokens.push(Spanned {
+        token: Token::Eof,
+        line,
+        column,
+    });
+    Ok(tokens)
+}
@@ -0,0 +1,344 @@
+use super::{
+    lexer::{Spanned, Token},
+    CompileError,
+};
+use crate::Word;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(super) enum BinOp {
+    Add,
+    Sub,
+    Mul,
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+    And,
+    Or,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub(super) enum Expr {
+    Number(Word),
+    Var(String, usize, usize),
+    Call(String, Vec<Expr>, usize, usize),
+    Input,
+    Neg(Box<Expr>),
+    Not(Box<Expr>),
+    Binary(BinOp, Box<Expr>, Box<Expr>),
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub(super) enum StmtKind {
+    Let(String, Expr),
+    Assign(String, Expr),
+    If(Expr, Vec<Stmt>, Vec<Stmt>),
+    While(Expr, Vec<Stmt>),
+    Break,
+    Continue,
+    Return(Option<Expr>),
+    Output(Expr),
+    Print(String),
+    Expr(Expr),
+}
+
+/// A statement, along with where it starts in the source
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub(super) struct Stmt {
+    pub kind: StmtKind,
+    pub line: usize,
+    pub column: usize,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub(super) struct Function {
+    pub name: String,
+    pub params: Vec<String>,
+    /// Whether the function is declared with `extern` and defined by
+    /// another module, in which case it has no body
+    pub external: bool,
+    pub body: Vec<Stmt>,
+    pub line: usize,
+    pub column: usize,
+}
+
+pub(super) fn parse(tokens: &[Spanned]) -> Result<Vec<Function>, CompileError> {
+    let mut parser = Parser { tokens, pos: 0 };
+    let mut functions = Vec::new();
+    while parser.peek() != &Token::Eof {
+        functions.push(parser.function()?);
+    }
+    Ok(functions)
+}
+
+struct Parser<'a> {
+    tokens: &'a [Spanned],
+    pos: usize,
+}
+
+impl Parser<'_> {
+    fn peek(&self) -> &Token {
+        &self.tokens[self.pos].token
+    }
+
+    fn here(&self) -> (usize, usize) {
+        let t = &self.tokens[self.pos];
+        (t.line, t.column)
+    }
+
+    fn next(&mut self) -> Token {
+        let token = self.tokens[self.pos].token.clone();
+        if token != Token::Eof {
+            self.pos += 1;
+        }
+        token
+    }
+
+    fn error(&self, message: impl Into<String>) -> CompileError {
+        let (line, column) = self.here();
+        CompileError::new(line, column, message)
+    }
+
+    fn eat(&mut self, token: &Token) -> bool {
+        if self.peek() == token {
+            self.next();
+            true
+        } else {
+            false
+        }
+    }
+
+    fn expect(&mut self, token: Token, what: &str) -> Result<(), CompileError> {
+        if self.eat(&token) {
+            Ok(())
+        } else {
+            Err(self.error(format!("expected {}", what)))
+        }
+    }
+
+    fn ident(&mut self) -> Result<String, CompileError> {
+        match self.peek().clone() {
+            Token::Ident(name) => {
+                self.next();
+                Ok(name)
+            }
+            _ => Err(self.error("expected a name")),
+        }
+    }
+
+    fn function(&mut self) -> Result<Function, CompileError> {
+        let (line, column) = self.here();
+        let external = self.eat(&Token::Extern);
+        self.expect(Token::Fn, "`fn`")?;
+        let name = self.ident()?;
+        self.expect(Token::LParen, "`(`")?;
+        let mut params = Vec::new();
+        if !self.eat(&Token::RParen) {
+            loop {
+                params.push(self.ident()?);
+                if self.eat(&Token::RParen) {
+                    break;
+                }
+                self.expect(Token::Comma, "`,` or `)`")?;
+            }
+        }
+        let body = if external {
+            self.expect(Token::Semi, "`;`")?;
+            Vec::new()
+        } else {
+            self.block()?
+        };
+        Ok(Function {
+            name,
+            params,
+            external,
+            body,
+            line,
+            column,
+        })
+    }
+
+    fn block(&mut self) -> Result<Vec<Stmt>, CompileError> {
+        self.expect(Token::LBrace, "`{`")?;
+        let mut stmts = Vec::new();
+        while !self.eat(&Token::RBrace) {
+            if self.peek() == &Token::Eof {
+                return Err(self.error("expected `}`"));
+            }
+            stmts.push(self.statement()?);
+        }
+        Ok(stmts)
+    }
+
+    fn statement(&mut self) -> Result<Stmt, CompileError> {
+        let (line, column) = self.here();
+        let kind = match self.peek().clone() {
+            Token::Let => {
+                self.next();
+                let name = self.ident()?;
+                self.expect(Token::Assign, "`=`")?;
+                let value = self.expr()?;
+                self.expect(Token::Semi, "`;`")?;
+                StmtKind::Let(name, value)
+            }
+            Token::If => {
+                self.next();
+                let cond = self.expr()?;
+                let then = self.block()?;
+                let otherwise = if self.eat(&Token::Else) {
+                    if self.peek() == &Token::If {
+                        vec![self.statement()?]
+                    } else {
+                        self.block()?
+                    }
+                } else {
+                    Vec::new()
+                };
+                StmtKind::If(cond, then, otherwise)
+            }
+            Token::While => {
+                self.next();
+                let cond = self.expr()?;
+                StmtKind::While(cond, self.block()?)
+            }
+            Token::Break => {
+                self.next();
+                self.expect(Token::Semi, "`;`")?;
+                StmtKind::Break
+            }
+            Token::Continue => {
+                self.next();
+                self.expect(Token::Semi, "`;`")?;
+                StmtKind::Continue
+            }
+            Token::Return => {
+                self.next();
+                let value = if self.eat(&Token::Semi) {
+                    None
+                } else {
+                    let value = self.expr()?;
+                    self.expect(Token::Semi, "`;`")?;
+                    Some(value)
+                };
+                StmtKind::Return(value)
+            }
+            Token::Ident(name) if name == "output" => {
+                self.next();
+                self.expect(Token::LParen, "`(`")?;
+                let value = self.expr()?;
+                self.expect(Token::RParen, "`)`")?;
+                self.expect(Token::Semi, "`;`")?;
+                StmtKind::Output(value)
+            }
+            Token::Ident(name) if name == "print" => {
+                self.next();
+                self.expect(Token::LParen, "`(`")?;
+                let text = match self.next() {
+                    Token::Str(text) => text,
+                    _ => return Err(CompileError::new(line, column, "expected a string")),
+                };
+                self.expect(Token::RParen, "`)`")?;
+                self.expect(Token::Semi, "`;`")?;
+                StmtKind::Print(text)
+            }
+            Token::Ident(name) if self.tokens[self.pos + 1].token == Token::Assign => {
+                self.next();
+                self.next();
+                let value = self.expr()?;
+                self.expect(Token::Semi, "`;`")?;
+                StmtKind::Assign(name, value)
+            }
+            _ => {
+                let value = self.expr()?;
+                self.expect(Token::Semi, "`;`")?;
+                StmtKind::Expr(value)
+            }
+        };
+        Ok(Stmt { kind, line, column })
+    }
+
+    fn expr(&mut self) -> Result<Expr, CompileError> {
+        self.binary(0)
+    }
+
+    /// Parses binary operators by precedence climbing, from `||` at level 0
+    /// up to `*` at level 5
+    fn binary(&mut self, level: usize) -> Result<Expr, CompileError> {
+        if level > 5 {
+            return self.unary();
+        }
+        let mut lhs = self.binary(level + 1)?;
+        loop {
+            let op = match (level, self.peek()) {
+                (0, Token::Or) => BinOp::Or,
+                (1, Token::And) => BinOp::And,
+                (2, Token::Eq) => BinOp::Eq,
+                (2, Token::Ne) => BinOp::Ne,
+                (3, Token::Lt) => BinOp::Lt,
+                (3, Token::Le) => BinOp::Le,
+                (3, Token::Gt) => BinOp::Gt,
+                (3, Token::Ge) => BinOp::Ge,
+                (4, Token::Plus) => BinOp::Add,
+                (4, Token::Minus) => BinOp::Sub,
+                (5, Token::Star) => BinOp::Mul,
+                _ => return Ok(lhs),
+            };
+            self.next();
+            let rhs = self.binary(level + 1)?;
+            lhs = Expr::Binary(op, Box::new(lhs), Box::new(rhs));
+        }
+    }
+
+    fn unary(&mut self) -> Result<Expr, CompileError> {
+        if self.eat(&Token::Minus) {
+            return Ok(Expr::Neg(Box::new(self.unary()?)));
+        }
+        if self.eat(&Token::Bang) {
+            return Ok(Expr::Not(Box::new(self.unary()?)));
+        }
+        self.primary()
+    }
+
+    fn primary(&mut self) -> Result<Expr, CompileError> {
+        let (line, column) = self.here();
+        match self.next() {
+            Token::Number(n) => Ok(Expr::Number(n)),
+            Token::LParen => {
+                let value = self.expr()?;
+                self.expect(Token::RParen, "`)`")?;
+                Ok(value)
+            }
+            Token::Ident(name) if self.peek() == &Token::LParen => {
+                self.next();
+                let mut args = Vec::new();
+                if !self.eat(&Token::RParen) {
+                    loop {
+                        args.push(self.expr()?);
+                        if self.eat(&Token::RParen) {
+                            break;
+                        }
+                        self.expect(Token::Comma, "`,` or `)`")?;
+                    }
+                }
+                match name.as_str() {
+                    "input" if args.is_empty() => Ok(Expr::Input),
+                    "input" => Err(CompileError::new(
+                        line,
+                        column,
+                        "`input` takes no arguments",
+                    )),
+                    "output" | "print" => Err(CompileError::new(
+                        line,
+                        column,
+                        format!("`{}` does not produce a value", name),
+                    )),
+                    _ => Ok(Expr::Call(name, args, line, column)),
+                }
+            }
+            Token::Ident(name) => Ok(Expr::Var(name, line, column)),
+            _ => Err(CompileError::new(line, column, "expected an expression")),
+        }
+    }
+}
@@ -0,0 +1,491 @@
+use super::{
+    decode::decode, ops::Instruction, Address, DebugInfo, Decoded, Memory, Observer, Parameter,
+    ProgramCounter, Word,
+};
+use std::{collections::BTreeMap, convert::TryFrom, fmt, io};
+
+/// The number of times each side of a conditional jump was followed
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Branch {
+    /// Executions in which the jump was taken
+    pub taken: usize,
+    /// Executions in which execution fell through to the next instruction
+    pub not_taken: usize,
+}
+
+impl Branch {
+    /// Whether both sides of the jump have been followed
+    pub fn is_covered(&self) -> bool {
+        self.taken > 0 && self.not_taken > 0
+    }
+}
+
+#[derive(Clone, Debug)]
+struct Executed {
+    hits: usize,
+    size: usize,
+    text: String,
+    branch: Option<Branch>,
+}
+
+/// Totals of the instructions and branches covered in a program
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Summary {
+    /// Instructions found in the program, whether executed or not
+    pub instructions: usize,
+    /// Instructions executed at least once
+    pub instructions_hit: usize,
+    /// Sides of conditional jumps found in the program, two per jump
+    pub branches: usize,
+    /// Sides of conditional jumps followed at least once
+    pub branches_hit: usize,
+}
+
+impl Summary {
+    /// Whether every instruction and branch found was exercised
+    pub fn is_complete(&self) -> bool {
+        self.instructions == self.instructions_hit && self.branches == self.branches_hit
+    }
+}
+
+impl fmt::Display for Summary {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "instructions: {}/{}, branches: {}/{}",
+            self.instructions_hit, self.instructions, self.branches_hit, self.branches
+        )
+    }
+}
+
+/// A coverage map of an Intcode program
+///
+/// `Coverage` is an `Observer` which records the addresses executed as
+/// instructions, the addresses read or written as data, and the sides of
+/// each conditional jump that were followed. Attach a `&mut Coverage` to
+/// several executables to accumulate coverage over a set of inputs.
+///
+/// Instructions which were never executed are located by disassembling the
+/// remainder of the program, so self-modifying programs and data which
+/// happens to decode as an instruction may be reported inexactly.
+///
+/// ## Example
+///
+/// ```
+/// use intcode::{Coverage, Executable, Memory};
+///
+/// let memory: Memory = "3,9,8,9,10,9,4,9,99,-1,8".parse().expect("valid data");
+/// let mut coverage = Coverage::default();
+///
+/// let mut exe = Executable::from(memory.clone()).with_observer(&mut coverage);
+/// exe.single_input(8);
+/// let _drain = exe.drain();
+/// exe.run().expect("successful execution");
+/// drop(exe);
+///
+/// assert!(coverage.summary(&memory).is_complete());
+/// println!("{}", coverage.annotate(&memory));
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct Coverage {
+    executed: BTreeMap<Address, Executed>,
+    reads: BTreeMap<Address, usize>,
+    writes: BTreeMap<Address, usize>,
+}
+
+enum Line {
+    Instruction {
+        address: Address,
+        hits: usize,
+        text: String,
+        branch: Option<Branch>,
+    },
+    Data {
+        address: Address,
+        value: Word,
+        reads: usize,
+        writes: usize,
+    },
+}
+
+impl Coverage {
+    /// The number of times the instruction at `address` was executed
+    pub fn hits(&self, address: Address) -> usize {
+        self.executed.get(&address).map_or(0, |e| e.hits)
+    }
+
+    /// The number of times `address` was read as an instruction operand
+    pub fn reads(&self, address: Address) -> usize {
+        self.reads.get(&address).copied().unwrap_or(0)
+    }
+
+    /// The number of times `address` was written
+    pub fn writes(&self, address: Address) -> usize {
+        self.writes.get(&address).copied().unwrap_or(0)
+    }
+
+    /// The conditional jumps executed, along with the sides followed
+    pub fn branches(&self) -> impl Iterator<Item = (Address, Branch)> + '_ {
+        self.executed
+            .iter()
+            .filter_map(|(&address, e)| e.branch.map(|b| (address, b)))
+    }
+
+    /// Totals the instructions and branches covered in `program`
+    pub fn summary(&self, program: &Memory) -> Summary {
+        let mut summary = Summary::default();
+        for line in self.listing(program, None) {
+            if let Line::Instruction { hits, branch, .. } = line {
+                summary.instructions += 1;
+                if hits > 0 {
+                    summary.instructions_hit += 1;
+                }
+                if let Some(branch) = branch {
+                    summary.branches += 2;
+                    summary.branches_hit +=
+                        (branch.taken > 0) as usize + (branch.not_taken > 0) as usize;
+                }
+            }
+        }
+
+        summary
+    }
+
+    /// Renders `program` as a disassembly annotated with coverage counts
+    ///
+    /// Each line shows the execution count of an instruction, or `#####` if
+    /// it was never executed. Data words are marked with `-` and note how
+    /// often they were read and written.
+    pub fn annotate<'a>(&'a self, program: &'a Memory) -> Annotated<'a> {
+        Annotated {
+            coverage: self,
+            program,
+            debug: None,
+        }
+    }
+
+    /// Writes an lcov tracefile for `program`, naming the source `name`
+    ///
+    /// Each address is reported as a line, numbered from 1.
+    pub fn to_lcov(
+        &self,
+        name: &str,
+        program: &Memory,
+        mut output: impl io::Write,
+    ) -> io::Result<()> {
+        writeln!(output, "TN:")?;
+        writeln!(output, "SF:{}", name)?;
+
+        let mut summary = Summary::default();
+        for line in self.listing(program, None) {
+            if let Line::Instruction {
+                address,
+                hits,
+                branch,
+                ..
+            } = line
+            {
+                let line = address.value() + 1;
+                writeln!(output, "DA:{},{}", line, hits)?;
+                summary.instructions += 1;
+                if hits > 0 {
+                    summary.instructions_hit += 1;
+                }
+
+                if let Some(branch) = branch {
+                    for (side, count) in [branch.not_taken, branch.taken].iter().enumerate() {
+                        if hits > 0 {
+                            writeln!(output, "BRDA:{},0,{},{}", line, side, count)?;
+                        } else {
+                            writeln!(output, "BRDA:{},0,{},-", line, side)?;
+                        }
+                        summary.branches += 1;
+                        if *count > 0 {
+                            summary.branches_hit += 1;
+                        }
+                    }
+                }
+            }
+        }
+
+        writeln!(output, "BRF:{}", summary.branches)?;
+        writeln!(output, "BRH:{}", summary.branches_hit)?;
+        writeln!(output, "LF:{}", summary.instructions)?;
+        writeln!(output, "LH:{}", summary.instructions_hit)?;
+        writeln!(output, "end_of_record")
+    }
+
+    fn listing<'a>(
+        &'a self,
+        program: &'a Memory,
+        debug: Option<&'a DebugInfo>,
+    ) -> impl Iterator<Item = Line> + 'a {
+        let end = program.size();
+        let mut next = 0;
+        std::iter::from_fn(move || {
+            if next >= end {
+                return None;
+            }
+
+            let address = Address::new(next);
+            if let Some(e) = self.executed.get(&address) {
+                next += e.size;
+                return Some(Line::Instruction {
+                    address,
+                    hits: e.hits,
+                    text: e.text.clone(),
+                    branch: e.branch,
+                });
+            }
+
+            let is_data = debug.is_some_and(|d| d.is_data(address));
+            if !is_data && self.reads(address) == 0 && self.writes(address) == 0 {
+                if let Some(decoded) = self.disassemble(program, address) {
+                    next += decoded.size();
+                    let branch = branch_of(&decoded);
+                    return Some(Line::Instruction {
+                        address,
+                        hits: 0,
+                        text: decoded.to_string(),
+                        branch,
+                    });
+                }
+            }
+
+            next += 1;
+            Some(Line::Data {
+                address,
+                value: program.read_or_default(address),
+                reads: self.reads(address),
+                writes: self.writes(address),
+            })
+        })
+    }
+
+    /// Decodes an unexecuted instruction, provided that it does not overlap
+    /// any executed instruction
+    fn disassemble(&self, program: &Memory, address: Address) -> Option<Decoded> {
+        let i = Instruction::try_from(program.read_or_default(address)).ok()?;
+        let decoded = decode(i, ProgramCounter::at(address), program).ok()?;
+
+        let span =
+            Address::new(address.value() + 1)..Address::new(address.value() + decoded.size());
+        if self.executed.range(span).next().is_some() {
+            None
+        } else {
+            Some(decoded)
+        }
+    }
+}
+
+/// Creates an empty branch record for conditional jumps
+///
+/// Jumps with an immediate condition always go the same way, so they are not
+/// considered to branch.
+fn branch_of(instruction: &Decoded) -> Option<Branch> {
+    match instruction {
+        Decoded::JumpNonZero(ops) | Decoded::JumpZero(ops) => match ops.value {
+            Parameter::Immediate(_) => None,
+            _ => Some(Branch::default()),
+        },
+        _ => None,
+    }
+}
+
+impl Observer for Coverage {
+    fn on_fetch(&mut self, pc: Address, instruction: &Decoded) {
+        let entry = self.executed.entry(pc).or_insert_with(|| Executed {
+            hits: 0,
+            size: instruction.size(),
+            text: instruction.to_string(),
+            branch: branch_of(instruction),
+        });
+        entry.hits += 1;
+    }
+
+    fn on_read(&mut self, _pc: Address, address: Address, _value: Word) {
+        *self.reads.entry(address).or_insert(0) += 1;
+    }
+
+    fn on_write(&mut self, _pc: Address, address: Address, _prior: Word, _value: Word) {
+        *self.writes.entry(address).or_insert(0) += 1;
+    }
+
+    fn on_jump(&mut self, pc: Address, _target: Address, taken: bool) {
+        if let Some(branch) = self.executed.get_mut(&pc).and_then(|e| e.branch.as_mut()) {
+            if taken {
+                branch.taken += 1;
+            } else {
+                branch.not_taken += 1;
+            }
+        }
+    }
+}
+
+/// A disassembly annotated with coverage counts
+///
+/// Created by `Coverage::annotate`.
+pub struct Annotated<'a> {
+    coverage: &'a Coverage,
+    program: &'a Memory,
+    debug: Option<&'a DebugInfo>,
+}
+
+impl<'a> Annotated<'a> {
+    /// Labels the disassembly and notes the source line of each instruction
+    ///
+    /// Words in data regions are never disassembled.
+    pub fn with_debug_info(mut self, debug: &'a DebugInfo) -> Self {
+        self.debug = Some(debug);
+        self
+    }
+}
+
+impl fmt::Display for Annotated<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mut source_line = None;
+        for line in self.coverage.listing(self.program, self.debug) {
+            let address = match &line {
+                Line::Instruction { address, .. } | Line::Data { address, .. } => *address,
+            };
+            if let Some(label) = self.debug.and_then(|d| d.label(address)) {
+                writeln!(f, "{}:", label)?;
+            }
+
+            match line {
+                Line::Instruction {
+                    address,
+                    hits,
+                    text,
+                    branch,
+                } => {
+                    if hits > 0 {
+                        write!(f, "{:>8} {:>6}: {}", hits, address, text)?;
+                    } else {
+                        write!(f, "{:>8} {:>6}: {}", "#####", address, text)?;
+                    }
+                    if let Some(b) = branch {
+                        write!(f, "  [taken: {}, not taken: {}]", b.taken, b.not_taken)?;
+                    }
+                }
+                Line::Data {
+                    address,
+                    value,
+                    reads,
+                    writes,
+                } => {
+                    write!(f, "{:>8} {:>6}: {}", "-", address, value)?;
+                    if reads > 0 || writes > 0 {
+                        write!(f, "  [reads: {}, writes: {}]", reads, writes)?;
+                    }
+                }
+            }
+
+            // Note each source line against the first word it produced
+            match self.debug.and_then(|d| d.line(address).map(|l| (d, l))) {
+                Some((debug, line)) if Some(line) != source_line => {
+                    match debug.source_line(line) {
+                        Some(text) => write!(f, "  ; line {}: {}", line, text)?,
+                        None => write!(f, "  ; line {}", line)?,
+                    }
+                    source_line = Some(line);
+                }
+                _ => {}
+            }
+            writeln!(f)?;
+        }
+
+        writeln!(f, "{}", self.coverage.summary(self.program))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Branch, Coverage};
+    use crate::{Address, Executable, Memory, Word};
+    use anyhow::Result;
+    use pretty_assertions::assert_eq;
+
+    const PUZ_5_PART_2_EXAMPLE: &str = "
+        3,21,1008,21,8,20,1005,20,22,107,8,21,20,1006,20,31,
+        1106,0,36,98,0,0,1002,21,125,20,4,20,1105,1,46,104,
+        999,1105,1,46,1101,1000,1,20,4,20,1105,1,46,98,99";
+
+    fn cover(coverage: &mut Coverage, program: &Memory, input: Word) -> Result<()> {
+        let mut exe = Executable::from(program.clone()).with_observer(coverage);
+        exe.single_input(input);
+        let _drain = exe.drain();
+        exe.run()?;
+        Ok(())
+    }
+
+    #[test]
+    fn single_input_leaves_paths_uncovered() -> Result<()> {
+        crate::init_logging();
+        let program: Memory = PUZ_5_PART_2_EXAMPLE.parse()?;
+        let mut coverage = Coverage::default();
+        cover(&mut coverage, &program, 8)?;
+
+        let summary = coverage.summary(&program);
+        assert!(!summary.is_complete());
+        assert_eq!(1, coverage.hits(Address::ZERO));
+        assert_eq!(0, coverage.hits(Address::new(31)));
+        assert_eq!(1, coverage.writes(Address::new(21)));
+        assert_eq!(
+            Some(Branch {
+                taken: 1,
+                not_taken: 0
+            }),
+            coverage
+                .branches()
+                .find(|&(a, _)| a == Address::new(6))
+                .map(|(_, b)| b)
+        );
+
+        let annotated = coverage.annotate(&program).to_string();
+        assert!(
+            annotated.contains("#####     31: write $999 =>"),
+            "{}",
+            annotated
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn inputs_around_eight_cover_every_path() -> Result<()> {
+        crate::init_logging();
+        let program: Memory = PUZ_5_PART_2_EXAMPLE.parse()?;
+        let mut coverage = Coverage::default();
+        for input in 7..=9 {
+            cover(&mut coverage, &program, input)?;
+        }
+
+        let summary = coverage.summary(&program);
+        assert!(summary.is_complete(), "{}", coverage.annotate(&program));
+        assert_eq!(4, summary.branches);
+        assert_eq!(2, coverage.branches().count());
+
+        Ok(())
+    }
+
+    #[test]
+    fn writes_lcov_records() -> Result<()> {
+        crate::init_logging();
+        let program: Memory = "3,9,8,9,10,9,4,9,99,-1,8".parse()?;
+        let mut coverage = Coverage::default();
+        cover(&mut coverage, &program, 8)?;
+
+        let mut buf = Vec::new();
+        coverage.to_lcov("equal-to-8.int", &program, &mut buf)?;
+        let lcov = String::from_utf8(buf)?;
+
+        assert_eq!(
+            "TN:\nSF:equal-to-8.int\nDA:1,1\nDA:3,1\nDA:7,1\nDA:9,1\n\
+             BRF:0\nBRH:0\nLF:4\nLH:4\nend_of_record\n",
+            lcov
+        );
+
+        Ok(())
+    }
+}
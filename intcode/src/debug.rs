@@ -0,0 +1,292 @@
+use super::{Address, Image};
+use std::{collections::BTreeMap, fmt, ops::Range};
+
+/// Debug information relating a program to the source it was built from
+///
+/// Labels name addresses in the program, so that any address can be
+/// described relative to the nearest label before it, as in `main+12`. Data
+/// regions mark words which hold data rather than instructions, and the line
+/// table maps each instruction back to the line of source it came from. A
+/// line covers every address up to the next mapped address.
+///
+/// ## Example
+///
+/// ```
+/// use intcode::{Address, DebugInfo};
+///
+/// let info = DebugInfo::default()
+///     .with_source("in x\nout x\nhalt\n")
+///     .with_label(Address::new(0), "main")
+///     .with_line(Address::new(0), 1)
+///     .with_line(Address::new(2), 2)
+///     .with_line(Address::new(4), 3);
+///
+/// assert_eq!("main+2 (line 2: out x)", info.locate(Address::new(2)).to_string());
+/// ```
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct DebugInfo {
+    labels: BTreeMap<Address, String>,
+    data: BTreeMap<Address, Address>,
+    lines: BTreeMap<Address, usize>,
+    source: Vec<String>,
+}
+
+impl DebugInfo {
+    /// Names an address, replacing any existing label
+    pub fn with_label(mut self, address: Address, name: impl Into<String>) -> Self {
+        self.labels.insert(address, name.into());
+        self
+    }
+
+    /// Marks a range of addresses as holding data
+    pub fn with_data(mut self, region: Range<Address>) -> Self {
+        if region.start < region.end {
+            self.data.insert(region.start, region.end);
+        }
+        self
+    }
+
+    /// Maps the instruction at `address` to a line of source, counting
+    /// from 1
+    pub fn with_line(mut self, address: Address, line: usize) -> Self {
+        self.lines.insert(address, line);
+        self
+    }
+
+    /// Attaches the source text, so that locations can quote it
+    pub fn with_source(mut self, source: &str) -> Self {
+        self.source = source.lines().map(str::to_string).collect();
+        self
+    }
+
+    /// The label at exactly `address`, if any
+    pub fn label(&self, address: Address) -> Option<&str> {
+        self.labels.get(&address).map(String::as_str)
+    }
+
+    /// Iterates over labelled addresses, in address order
+    pub fn labels(&self) -> impl Iterator<Item = (Address, &str)> + '_ {
+        self.labels.iter().map(|(a, n)| (*a, n.as_str()))
+    }
+
+    /// Whether `address` lies within a data region
+    pub fn is_data(&self, address: Address) -> bool {
+        self.data
+            .range(..=address)
+            .next_back()
+            .is_some_and(|(_, end)| address < *end)
+    }
+
+    /// The line of source which produced the word at `address`
+    pub fn line(&self, address: Address) -> Option<usize> {
+        self.lines.range(..=address).next_back().map(|(_, l)| *l)
+    }
+
+    /// The text of a line of source, counting from 1
+    pub fn source_line(&self, line: usize) -> Option<&str> {
+        line.checked_sub(1)
+            .and_then(|idx| self.source.get(idx))
+            .map(|l| l.trim())
+    }
+
+    /// Describes `address` relative to the nearest preceding label
+    ///
+    /// Addresses before the first label are described numerically.
+    pub fn symbolize(&self, address: Address) -> Symbol<'_> {
+        match self.labels.range(..=address).next_back() {
+            Some((base, name)) => Symbol {
+                address,
+                label: Some((name.as_str(), address.value() - base.value())),
+            },
+            None => Symbol {
+                address,
+                label: None,
+            },
+        }
+    }
+
+    /// Describes `address` symbolically, along with its line of source
+    pub fn locate(&self, address: Address) -> Location<'_> {
+        Location {
+            info: self,
+            address,
+        }
+    }
+}
+
+impl From<&Image> for DebugInfo {
+    /// Takes labels from the symbol table of an image
+    fn from(image: &Image) -> Self {
+        image
+            .symbols()
+            .fold(Self::default(), |info, (address, name)| {
+                info.with_label(address, name)
+            })
+    }
+}
+
+/// An address described relative to a label
+///
+/// Created by `DebugInfo::symbolize`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Symbol<'a> {
+    address: Address,
+    label: Option<(&'a str, usize)>,
+}
+
+impl Symbol<'_> {
+    /// The nearest preceding label and the offset from it
+    pub fn label(&self) -> Option<(&str, usize)> {
+        self.label
+    }
+}
+
+impl fmt::Display for Symbol<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.label {
+            Some((name, 0)) => f.write_str(name),
+            Some((name, offset)) => write!(f, "{}+{}", name, offset),
+            None => self.address.fmt(f),
+        }
+    }
+}
+
+/// An address described symbolically, along with its line of source
+///
+/// Created by `DebugInfo::locate`.
+#[derive(Clone, Copy, Debug)]
+pub struct Location<'a> {
+    info: &'a DebugInfo,
+    address: Address,
+}
+
+impl fmt::Display for Location<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.info.symbolize(self.address).fmt(f)?;
+        if let Some(line) = self.info.line(self.address) {
+            match self.info.source_line(line) {
+                Some(text) => write!(f, " (line {}: {})", line, text)?,
+                None => write!(f, " (line {})", line)?,
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::DebugInfo;
+    use crate::{Address, Coverage, Executable, Image, Memory, Trace};
+    use anyhow::Result;
+    use pretty_assertions::assert_eq;
+    use std::sync::Arc;
+
+    /// Reads a value, then jumps into the data which follows the program
+    const BAD_JUMP: &str = "\
+        main: in x
+              jmp 1, data
+        halt: halt
+        x:    .word 0
+        data: .word 12345";
+
+    fn debug_info() -> DebugInfo {
+        DebugInfo::default()
+            .with_source(BAD_JUMP)
+            .with_label(Address::new(0), "main")
+            .with_label(Address::new(5), "halt")
+            .with_label(Address::new(6), "x")
+            .with_label(Address::new(7), "data")
+            .with_line(Address::new(0), 1)
+            .with_line(Address::new(2), 2)
+            .with_line(Address::new(5), 3)
+            .with_line(Address::new(6), 4)
+            .with_line(Address::new(7), 5)
+            .with_data(Address::new(6)..Address::new(8))
+    }
+
+    #[test]
+    fn describes_addresses() {
+        crate::init_logging();
+        let info = debug_info();
+
+        assert_eq!("main", info.symbolize(Address::new(0)).to_string());
+        assert_eq!("main+3", info.symbolize(Address::new(3)).to_string());
+        assert_eq!(
+            "main+2 (line 2: jmp 1, data)",
+            info.locate(Address::new(2)).to_string()
+        );
+        assert!(!info.is_data(Address::new(5)));
+        assert!(info.is_data(Address::new(7)));
+        assert!(!info.is_data(Address::new(8)));
+
+        let bare = DebugInfo::default().with_label(Address::new(4), "later");
+        assert_eq!("2", bare.locate(Address::new(2)).to_string());
+    }
+
+    #[test]
+    fn errors_show_location() -> Result<()> {
+        crate::init_logging();
+        let memory: Memory = "3,6,1105,1,7,99,0,12345".parse()?;
+
+        let mut exe = Executable::from(memory).with_debug_info(Arc::new(debug_info()));
+        exe.single_input(1);
+        let error = exe.run().unwrap_err();
+
+        assert_eq!(
+            "invalid instruction; pc = data (line 5: data: .word 12345)",
+            error.to_string()
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn traces_and_listings_show_location() -> Result<()> {
+        crate::init_logging();
+        let memory: Memory = "3,6,1105,1,5,99,0,12345".parse()?;
+        let info = debug_info();
+
+        let mut exe = Executable::from(memory.clone()).with_observer(Trace::default());
+        exe.single_input(1);
+        exe.run()?;
+        let trace = exe.into_observer();
+        let symbolized = trace.symbolize(&info).to_string();
+        assert_eq!(
+            Some("     0 main (line 1: main: in x): read => (6)"),
+            symbolized.lines().next()
+        );
+        assert!(
+            symbolized.contains("     2 halt (line 3: halt: halt)"),
+            "{}",
+            symbolized
+        );
+
+        let mut coverage = Coverage::default();
+        let mut exe = Executable::from(memory.clone()).with_observer(&mut coverage);
+        exe.single_input(1);
+        exe.run()?;
+        let listing = coverage
+            .annotate(&memory)
+            .with_debug_info(&info)
+            .to_string();
+        assert!(listing.starts_with("main:\n"), "{}", listing);
+        assert!(listing.contains("data:\n"), "{}", listing);
+        assert!(
+            listing.contains("; line 5: data: .word 12345"),
+            "{}",
+            listing
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn labels_come_from_images() {
+        crate::init_logging();
+        let image = Image::new(Memory::from_vec(vec![99])).with_symbol(Address::ZERO, "main");
+
+        let info = DebugInfo::from(&image);
+
+        assert_eq!(Some("main"), info.label(Address::ZERO));
+    }
+}
@@ -21,6 +21,7 @@ macro_rules! decode_impl {
         let addr_ctx = execute::DecodeError { pc: $pc };
 
         let mut inputs: ArrayVec<[Parameter; Self::READ_PARAMS as usize]> = ArrayVec::new();
+        #[allow(clippy::reversed_empty_ranges)]
         for i in 0..Self::READ_PARAMS {
             let param_addr = $pc.param(i);
             let value = $memory
@@ -31,7 +32,7 @@ macro_rules! decode_impl {
         }
 
         let mut outputs: ArrayVec<[Output; Self::WRITE_PARAMS as usize]> = ArrayVec::new();
-        #[allow(clippy::range_plus_one)]
+        #[allow(clippy::range_plus_one, clippy::reversed_empty_ranges)]
         for i in Self::READ_PARAMS..(Self::READ_PARAMS + Self::WRITE_PARAMS) {
             let param_addr = $pc.param(i);
             let value = $memory
@@ -189,6 +190,7 @@ impl fmt::Display for OutputOperands {
 }
 
 /// Operands for an instruction that has one input and one output
+#[allow(dead_code)]
 #[derive(Debug, PartialEq, Eq)]
 pub struct UnaryOperands {
     pub value: Parameter,
@@ -250,6 +252,39 @@ impl fmt::Display for Decoded {
     }
 }
 
+impl Decoded {
+    /// Decodes the instruction at `address`, if it holds a valid one
+    ///
+    /// ## Example
+    ///
+    /// ```
+    /// use intcode::{Address, Decoded, Memory};
+    ///
+    /// let memory: Memory = "1001,5,1,5,99,0".parse().expect("valid data");
+    ///
+    /// let decoded = Decoded::at(&memory, Address::ZERO).expect("valid instruction");
+    /// assert_eq!("add (5), $1 => (5)", decoded.to_string());
+    /// assert!(Decoded::at(&memory, Address::new(5)).is_none());
+    /// ```
+    pub fn at(memory: &Memory, address: Address) -> Option<Decoded> {
+        memory
+            .try_read(address)
+            .ok()
+            .and_then(|op| Instruction::try_from(op).ok())
+            .and_then(|i| decode(i, ProgramCounter::at(address), memory).ok())
+    }
+
+    /// The number of words the instruction occupies, including parameters
+    pub fn size(&self) -> usize {
+        match self {
+            Decoded::Halt => 1,
+            Decoded::Input(_) | Decoded::Output(_) | Decoded::AddRel(_) => 2,
+            Decoded::JumpNonZero(_) | Decoded::JumpZero(_) => 3,
+            Decoded::Add(_) | Decoded::Mul(_) | Decoded::LessThan(_) | Decoded::Equal(_) => 4,
+        }
+    }
+}
+
 pub(crate) fn decode(
     i: Instruction,
     pc: ProgramCounter,
@@ -292,6 +327,17 @@ impl Parameter {
         }
     }
 
+    /// Resolves the address that the parameter refers to
+    ///
+    /// Returns `None` for immediate parameters, which do not refer to memory.
+    pub fn resolve(self, relative_base: Address) -> Result<Option<Address>, error::InvalidAddress> {
+        match self {
+            Parameter::Position(addr) => Ok(Some(addr)),
+            Parameter::Immediate(_) => Ok(None),
+            Parameter::Relative(offset) => Ok(Some((relative_base + offset)?)),
+        }
+    }
+
     /// Loads value from memory
     pub fn load(
         self,
@@ -334,6 +380,14 @@ impl Output {
         }
     }
 
+    /// Resolves the address that the output refers to
+    pub fn resolve(self, relative_base: Address) -> Result<Address, error::InvalidAddress> {
+        match self {
+            Output::Position(addr) => Ok(addr),
+            Output::Relative(offset) => relative_base + offset,
+        }
+    }
+
     /// Stores a value to memory
     pub fn store(self, relative_base: Address, memory: &mut Memory, value: Word) -> Word {
         match self {
@@ -0,0 +1,391 @@
+use super::{Address, Image, Memory, Word};
+use serde::{Deserialize, Serialize};
+use std::{fmt, io, ops::Range};
+use thiserror::Error;
+
+/// A run of consecutive words which differ between two memories
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Change {
+    /// The address of the first word in the run
+    pub start: usize,
+    /// The words as they were
+    pub before: Vec<Word>,
+    /// The words as they are now
+    pub after: Vec<Word>,
+}
+
+impl Change {
+    /// The addresses covered by the run
+    pub fn range(&self) -> Range<Address> {
+        Address::new(self.start)..Address::new(self.start + self.after.len())
+    }
+
+    fn addresses(&self) -> impl Iterator<Item = Address> {
+        (self.start..).map(Address::new)
+    }
+}
+
+impl fmt::Display for Change {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let join = |words: &[Word]| {
+            words
+                .iter()
+                .map(Word::to_string)
+                .collect::<Vec<_>>()
+                .join(", ")
+        };
+        let range = self.range();
+        if self.after.len() == 1 {
+            write!(f, "{}: ", range.start)?;
+        } else {
+            write!(f, "{}..{}: ", range.start, range.end)?;
+        }
+        write!(f, "{} => {}", join(&self.before), join(&self.after))
+    }
+}
+
+/// A reason a `Diff` could not be applied to a memory
+#[derive(Error, Clone, Debug, PartialEq, Eq)]
+pub enum PatchError {
+    /// The memory is not the size the diff was taken from
+    #[error("patch expects {expected} words of memory but found {found}")]
+    Size { expected: usize, found: usize },
+    /// A word does not hold the value the diff was taken from
+    #[error("patch expects {expected} at address {address} but found {found}")]
+    Conflict {
+        address: Address,
+        expected: Word,
+        found: Word,
+    },
+}
+
+/// The words which differ between two memories
+///
+/// Memories of different sizes are compared as if the shorter were padded
+/// with zeroes, which is how an executing program sees memory beyond its end.
+/// The sizes are kept so that applying the diff also grows or shrinks memory.
+///
+/// A diff is displayed as a report with one line for each run of changed
+/// words. Written with `to_writer`, it is JSON which serves both as a
+/// machine-readable report and as a patch which `from_reader` loads for
+/// `apply`ing to another copy of the original memory.
+///
+/// ## Example
+///
+/// ```
+/// use intcode::{Address, Executable, Memory};
+///
+/// let program: Memory = "1,0,0,0,99".parse().expect("valid data");
+/// let mut patched = program.clone();
+/// patched.try_write(Address::new(1), 4).expect("in bounds");
+/// patched.try_write(Address::new(2), 4).expect("in bounds");
+///
+/// let result = Executable::from(patched.clone()).execute().expect("halts");
+/// let diff = patched.diff(&result);
+/// assert_eq!("0: 1 => 198", diff.to_string());
+///
+/// let setup = program.diff(&patched);
+/// assert_eq!(
+///     vec![Address::new(1)..Address::new(3)],
+///     setup.ranges().collect::<Vec<_>>()
+/// );
+///
+/// let mut copy = program.clone();
+/// setup.apply(&mut copy).expect("patch applies to the original");
+/// assert_eq!(patched, copy);
+/// ```
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Diff {
+    before_size: usize,
+    after_size: usize,
+    changes: Vec<Change>,
+}
+
+impl Diff {
+    pub(crate) fn between(before: &Memory, after: &Memory) -> Self {
+        let end = before.size().max(after.size());
+        let mut changes: Vec<Change> = Vec::new();
+        let mut idx = 0;
+        while idx < end {
+            if let Some(next) = before.shared_page_end(after, idx) {
+                idx = next;
+                continue;
+            }
+
+            let address = Address::new(idx);
+            let (old, new) = (
+                before.read_or_default(address),
+                after.read_or_default(address),
+            );
+            if old != new {
+                match changes.last_mut() {
+                    Some(run) if run.start + run.after.len() == idx => {
+                        run.before.push(old);
+                        run.after.push(new);
+                    }
+                    _ => changes.push(Change {
+                        start: idx,
+                        before: vec![old],
+                        after: vec![new],
+                    }),
+                }
+            }
+            idx += 1;
+        }
+
+        Self {
+            before_size: before.size(),
+            after_size: after.size(),
+            changes,
+        }
+    }
+
+    /// Whether the memories were identical
+    pub fn is_empty(&self) -> bool {
+        self.changes.is_empty() && self.before_size == self.after_size
+    }
+
+    /// The size of the original memory
+    pub fn before_size(&self) -> usize {
+        self.before_size
+    }
+
+    /// The size of the changed memory
+    pub fn after_size(&self) -> usize {
+        self.after_size
+    }
+
+    /// The runs of changed words, in address order
+    pub fn changes(&self) -> &[Change] {
+        &self.changes
+    }
+
+    /// The addresses covered by each run of changed words
+    pub fn ranges(&self) -> impl Iterator<Item = Range<Address>> + '_ {
+        self.changes.iter().map(Change::range)
+    }
+
+    /// The total number of changed words
+    pub fn changed_words(&self) -> usize {
+        self.changes.iter().map(|c| c.after.len()).sum()
+    }
+
+    /// A diff which undoes this one
+    pub fn reverse(&self) -> Self {
+        Self {
+            before_size: self.after_size,
+            after_size: self.before_size,
+            changes: self
+                .changes
+                .iter()
+                .map(|c| Change {
+                    start: c.start,
+                    before: c.after.clone(),
+                    after: c.before.clone(),
+                })
+                .collect(),
+        }
+    }
+
+    /// Changes `memory` from the original contents to the changed contents
+    ///
+    /// Memory is left untouched unless it matches the original in size and in
+    /// every word which the diff changes.
+    pub fn apply(&self, memory: &mut Memory) -> Result<(), PatchError> {
+        if memory.size() != self.before_size {
+            return Err(PatchError::Size {
+                expected: self.before_size,
+                found: memory.size(),
+            });
+        }
+
+        for change in &self.changes {
+            for (address, &expected) in change.addresses().zip(&change.before) {
+                let found = memory.read_or_default(address);
+                if found != expected {
+                    return Err(PatchError::Conflict {
+                        address,
+                        expected,
+                        found,
+                    });
+                }
+            }
+        }
+
+        memory.set_memory_limit(self.after_size);
+        for change in &self.changes {
+            for (address, &value) in change.addresses().zip(&change.after) {
+                if address.value() < self.after_size {
+                    memory
+                        .try_write(address, value)
+                        .expect("memory was sized to fit the change");
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Applies the diff to the program in an image, keeping its metadata
+    pub fn apply_to_image(&self, image: &mut Image) -> Result<(), PatchError> {
+        self.apply(image.memory_mut())
+    }
+
+    /// Writes the diff as JSON
+    pub fn to_writer(&self, output: impl io::Write) -> io::Result<()> {
+        serde_json::to_writer(output, self)?;
+        Ok(())
+    }
+
+    /// Reads a diff written by `to_writer`
+    pub fn from_reader(input: impl io::Read) -> io::Result<Self> {
+        let diff: Self = serde_json::from_reader(input)?;
+        if let Some(change) = diff
+            .changes
+            .iter()
+            .find(|c| c.before.len() != c.after.len())
+        {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("change at {} has mismatched lengths", change.start),
+            ));
+        }
+        Ok(diff)
+    }
+}
+
+impl fmt::Display for Diff {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mut lines = Vec::new();
+        if self.before_size != self.after_size {
+            lines.push(format!("size: {} => {}", self.before_size, self.after_size));
+        }
+        lines.extend(self.changes.iter().map(Change::to_string));
+
+        if lines.is_empty() {
+            f.write_str("no changes")
+        } else {
+            f.write_str(&lines.join("\n"))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Change, Diff, PatchError};
+    use crate::{Address, Executable, Image, Memory};
+    use anyhow::Result;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn finds_runs_of_changed_words() -> Result<()> {
+        crate::init_logging();
+        let mut program: Memory = include_str!("../../inputs/input-02").parse()?;
+        let image = program.clone();
+        program.try_write(Address::new(1), 12)?;
+        program.try_write(Address::new(2), 2)?;
+
+        let setup = image.diff(&program);
+        assert_eq!(
+            vec![Change {
+                start: 1,
+                before: vec![0, 0],
+                after: vec![12, 2],
+            }],
+            setup.changes()
+        );
+        assert_eq!("1..3: 0, 0 => 12, 2", setup.to_string());
+
+        let result = Executable::from(program.clone()).execute()?;
+        let run = program.diff(&result);
+        assert_eq!(Some(0), run.changes().first().map(|c| c.start));
+        assert_eq!(
+            result.read_or_default(Address::ZERO),
+            run.changes()[0].after[0]
+        );
+        assert!(run.ranges().all(|r| r.end <= Address::new(program.size())));
+
+        assert!(program.diff(&program).is_empty());
+        assert_eq!("no changes", program.diff(&program).to_string());
+
+        Ok(())
+    }
+
+    #[test]
+    fn skips_shared_pages() {
+        crate::init_logging();
+        let image = Memory::from_vec((0..2000).collect());
+        let mut memory = image.clone();
+        memory.try_write(Address::new(1500), -1).unwrap();
+        memory.try_write(Address::new(1501), -2).unwrap();
+        memory.try_write(Address::new(1999), 0).unwrap();
+
+        assert_eq!(
+            "1500..1502: 1500, 1501 => -1, -2\n1999: 1999 => 0",
+            image.diff(&memory).to_string()
+        );
+    }
+
+    #[test]
+    fn sizes_change_with_zero_padding() -> Result<()> {
+        crate::init_logging();
+        let before = Memory::from_vec(vec![1, 2, 3]);
+        let mut after = before.clone();
+        after.write_arbitrary(Address::new(5), 9);
+        after.try_write(Address::new(0), 0)?;
+
+        let diff = before.diff(&after);
+        assert_eq!("size: 3 => 6\n0: 1 => 0\n5: 0 => 9", diff.to_string());
+        assert_eq!(2, diff.changed_words());
+
+        let mut patched = before.clone();
+        diff.apply(&mut patched)?;
+        assert_eq!(after, patched);
+
+        diff.reverse().apply(&mut patched)?;
+        assert_eq!(before, patched);
+
+        Ok(())
+    }
+
+    #[test]
+    fn patches_round_trip_and_apply_to_images() -> Result<()> {
+        crate::init_logging();
+        let original: Memory = "1,9,10,3,2,3,11,0,99,30,40,50".parse()?;
+        let result = Executable::from(original.clone()).execute()?;
+        let diff = original.diff(&result);
+
+        let mut json = Vec::new();
+        diff.to_writer(&mut json)?;
+        assert_eq!(
+            r#"{"before_size":12,"after_size":12,"changes":[{"start":0,"before":[1],"after":[3500]},{"start":3,"before":[3],"after":[70]}]}"#,
+            String::from_utf8(json.clone())?
+        );
+        let patch = Diff::from_reader(&json[..])?;
+        assert_eq!(diff, patch);
+
+        let mut image = Image::new(original).with_symbol(Address::new(8), "halt");
+        patch.apply_to_image(&mut image)?;
+        assert_eq!(&result, image.memory());
+        assert_eq!(Some("halt"), image.symbol(Address::new(8)));
+
+        // The patch no longer applies once it has been applied
+        assert_eq!(
+            Err(PatchError::Conflict {
+                address: Address::ZERO,
+                expected: 1,
+                found: 3500,
+            }),
+            patch.apply_to_image(&mut image)
+        );
+        assert_eq!(
+            Err(PatchError::Size {
+                expected: 12,
+                found: 5,
+            }),
+            patch.apply(&mut Memory::from_vec(vec![0; 5]))
+        );
+
+        Ok(())
+    }
+}
@@ -0,0 +1,511 @@
+use super::{Address, AsyncExecutable, ErrorKind, Executable, ExecutionError, Memory, Word};
+use futures::{channel::mpsc as async_mpsc, executor, StreamExt};
+use std::{fmt, sync::mpsc};
+
+/// How an execution under differential test came to an end
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Ending {
+    /// The program halted with the given memory
+    Halted(Memory),
+    /// The program failed at the given instruction, leaving the given memory
+    Failed(ErrorKind, Address, Memory),
+    /// The program was still running once the step limit was reached
+    StepLimit(Memory),
+}
+
+/// The observable result of running a program on a backend
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Outcome {
+    /// Values output by the program, in order
+    pub outputs: Vec<Word>,
+    /// How execution ended
+    pub ending: Ending,
+}
+
+impl Outcome {
+    fn new(outputs: Vec<Word>, result: Result<bool, ExecutionError>, memory: &Memory) -> Self {
+        let ending = match result {
+            Ok(false) => Ending::Halted(memory.clone()),
+            Ok(true) => Ending::StepLimit(memory.clone()),
+            Err(e) => Ending::Failed(e.kind(), e.pc(), memory.clone()),
+        };
+
+        Self { outputs, ending }
+    }
+}
+
+/// A means of executing Intcode programs which can be checked against others
+pub trait Backend {
+    /// A name identifying the backend in reports
+    fn name(&self) -> &str;
+
+    /// Runs `program` to completion, or for at most `max_steps` instructions,
+    /// providing `inputs` and then closing the input pipe
+    fn evaluate(&self, program: &Memory, inputs: &[Word], max_steps: usize) -> Outcome;
+}
+
+/// Runs programs on `Executable`
+///
+/// A reused backend executes each program once, resets the executable, and
+/// reports the outcome of executing it again.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct SyncBackend {
+    reused: bool,
+}
+
+impl SyncBackend {
+    /// Runs each program on a newly constructed executable
+    pub fn fresh() -> Self {
+        Self { reused: false }
+    }
+
+    /// Runs each program on an executable which has been reset after use
+    pub fn reused() -> Self {
+        Self { reused: true }
+    }
+
+    fn run_once(exe: &mut Executable, inputs: &[Word], max_steps: usize) -> Outcome {
+        let (tx, rx) = mpsc::channel();
+        for &value in inputs {
+            tx.send(value).expect("receiver is held by the executable");
+        }
+        drop(tx);
+        exe.pipe_inputs_from(rx);
+        let drain = exe.drain();
+
+        let result = step_sync(exe, max_steps);
+
+        // Release the sender so that the drain terminates
+        drop(exe.take_output());
+        Outcome::new(drain.to_vec(), result, exe.memory_mut())
+    }
+}
+
+fn step_sync(exe: &mut Executable, max_steps: usize) -> Result<bool, ExecutionError> {
+    for _ in 0..max_steps {
+        if !exe.step()? {
+            return Ok(false);
+        }
+    }
+
+    Ok(true)
+}
+
+impl Backend for SyncBackend {
+    fn name(&self) -> &str {
+        if self.reused {
+            "sync (reused)"
+        } else {
+            "sync"
+        }
+    }
+
+    fn evaluate(&self, program: &Memory, inputs: &[Word], max_steps: usize) -> Outcome {
+        let mut exe = Executable::from(program.clone());
+        if self.reused {
+            Self::run_once(&mut exe, inputs, max_steps);
+            exe.reset();
+        }
+
+        Self::run_once(&mut exe, inputs, max_steps)
+    }
+}
+
+/// Runs programs on `AsyncExecutable` within a single-threaded runtime
+///
+/// A reused backend executes each program once, resets the executable, and
+/// reports the outcome of executing it again.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct AsyncBackend {
+    reused: bool,
+}
+
+impl AsyncBackend {
+    /// Runs each program on a newly constructed executable
+    pub fn fresh() -> Self {
+        Self { reused: false }
+    }
+
+    /// Runs each program on an executable which has been reset after use
+    pub fn reused() -> Self {
+        Self { reused: true }
+    }
+
+    async fn run_once(exe: &mut AsyncExecutable, inputs: &[Word], max_steps: usize) -> Outcome {
+        let (mut tx, rx) = async_mpsc::channel(inputs.len().max(1));
+        for &value in inputs {
+            tx.try_send(value)
+                .expect("channel has capacity for every input");
+        }
+        drop(tx);
+        exe.pipe_inputs_from(rx);
+
+        let (otx, mut orx) = async_mpsc::channel(1);
+        exe.pipe_outputs_to(otx);
+
+        let run = async {
+            let mut result = Ok(true);
+            for _ in 0..max_steps {
+                match exe.step().await {
+                    Ok(true) => {}
+                    done => {
+                        result = done;
+                        break;
+                    }
+                }
+            }
+
+            // Release the sender so that the collector terminates
+            drop(exe.take_output());
+            result
+        };
+
+        let collect = async {
+            let mut outputs = Vec::new();
+            while let Some(value) = orx.next().await {
+                outputs.push(value);
+            }
+            outputs
+        };
+
+        let (result, outputs) = futures::join!(run, collect);
+        Outcome::new(outputs, result, exe.memory_mut())
+    }
+}
+
+impl Backend for AsyncBackend {
+    fn name(&self) -> &str {
+        if self.reused {
+            "async (reused)"
+        } else {
+            "async"
+        }
+    }
+
+    fn evaluate(&self, program: &Memory, inputs: &[Word], max_steps: usize) -> Outcome {
+        executor::block_on(async {
+            let mut exe = AsyncExecutable::from(program.clone());
+            if self.reused {
+                Self::run_once(&mut exe, inputs, max_steps).await;
+                exe.reset();
+            }
+
+            Self::run_once(&mut exe, inputs, max_steps).await
+        })
+    }
+}
+
+/// Two backends which disagreed about the outcome of a program
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Mismatch {
+    /// The program under test
+    pub program: Memory,
+    /// The inputs provided to the program
+    pub inputs: Vec<Word>,
+    /// The name and outcome of the first backend
+    pub expected: (String, Outcome),
+    /// The name and outcome of the disagreeing backend
+    pub actual: (String, Outcome),
+}
+
+impl fmt::Display for Mismatch {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(
+            f,
+            "backends {} and {} disagree",
+            self.expected.0, self.actual.0
+        )?;
+        writeln!(f, "program: {:?}", self.program)?;
+        writeln!(f, "inputs:  {:?}", self.inputs)?;
+        writeln!(f, "{}: {:?}", self.expected.0, self.expected.1)?;
+        write!(f, "{}: {:?}", self.actual.0, self.actual.1)
+    }
+}
+
+/// Runs programs on several backends and checks that they agree
+///
+/// Backends agree when they produce the same outputs and end in the same way:
+/// halting with identical memory, failing with the same kind of error at the
+/// same instruction and with identical memory, or reaching the step limit
+/// with identical memory.
+///
+/// ## Example
+///
+/// ```
+/// use intcode::{Differential, Memory};
+///
+/// let memory: Memory = "3,9,8,9,10,9,4,9,99,-1,8".parse().expect("valid data");
+///
+/// let outcome = Differential::default()
+///     .check(&memory, &[8])
+///     .unwrap_or_else(|mismatch| panic!("{}", mismatch));
+///
+/// assert_eq!(vec![1], outcome.outputs);
+/// ```
+pub struct Differential {
+    backends: Vec<Box<dyn Backend>>,
+    max_steps: usize,
+}
+
+impl Default for Differential {
+    /// Compares fresh and reused synchronous and asynchronous executables
+    fn default() -> Self {
+        Self::new()
+            .backend(SyncBackend::fresh())
+            .backend(SyncBackend::reused())
+            .backend(AsyncBackend::fresh())
+            .backend(AsyncBackend::reused())
+    }
+}
+
+impl Differential {
+    /// Creates a harness with no backends and a limit of 100,000 steps
+    pub fn new() -> Self {
+        Self {
+            backends: Vec::new(),
+            max_steps: 100_000,
+        }
+    }
+
+    /// Adds a backend to compare
+    ///
+    /// The first backend added is the reference against which the others are
+    /// compared.
+    pub fn backend(mut self, backend: impl Backend + 'static) -> Self {
+        self.backends.push(Box::new(backend));
+        self
+    }
+
+    /// Sets the number of instructions after which execution is abandoned
+    pub fn max_steps(mut self, max_steps: usize) -> Self {
+        self.max_steps = max_steps;
+        self
+    }
+
+    /// Runs `program` with `inputs` on every backend
+    ///
+    /// Returns the agreed outcome, or the first backend to disagree with the
+    /// reference.
+    ///
+    /// ## Panics
+    ///
+    /// Panics if no backends have been added.
+    pub fn check(&self, program: &Memory, inputs: &[Word]) -> Result<Outcome, Box<Mismatch>> {
+        let (reference, others) = self
+            .backends
+            .split_first()
+            .expect("at least one backend to check");
+        let expected = reference.evaluate(program, inputs, self.max_steps);
+
+        for backend in others {
+            let actual = backend.evaluate(program, inputs, self.max_steps);
+            if actual != expected {
+                return Err(Box::new(Mismatch {
+                    program: program.clone(),
+                    inputs: inputs.to_vec(),
+                    expected: (reference.name().to_string(), expected),
+                    actual: (backend.name().to_string(), actual),
+                }));
+            }
+        }
+
+        Ok(expected)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Backend, Differential, Ending, Outcome, SyncBackend};
+    use crate::{Address, ErrorKind, Memory, Word};
+    use anyhow::Result;
+    use pretty_assertions::assert_eq;
+    use proptest::prelude::*;
+
+    /// Opcodes paired with the number of parameters that they take
+    const OPCODES: &[(Word, usize)] = &[
+        (1, 3),
+        (2, 3),
+        (3, 1),
+        (4, 1),
+        (5, 2),
+        (6, 2),
+        (7, 3),
+        (8, 3),
+        (9, 1),
+    ];
+    const DATA_WORDS: usize = 8;
+
+    /// Raw choices for a single instruction: an opcode index, and a mode
+    /// selector and value for each parameter
+    type Choice = (usize, [u8; 3], [i16; 3]);
+
+    /// Assembles a valid program from raw choices
+    ///
+    /// Jumps always target the start of an instruction and writes are
+    /// directed at a data region following the code, though relative
+    /// parameters may stray once the relative base has moved.
+    fn assemble(choices: &[Choice]) -> Vec<Word> {
+        let mut starts = Vec::with_capacity(choices.len() + 1);
+        let mut next = 0;
+        for &(op, _, _) in choices {
+            starts.push(next as Word);
+            next += OPCODES[op % OPCODES.len()].1 + 1;
+        }
+        starts.push(next as Word);
+        let data = next as Word + 1;
+        let size = data + DATA_WORDS as Word;
+
+        let mut program = Vec::with_capacity(size as usize);
+        for &(op, modes, values) in choices {
+            let (opcode, params) = OPCODES[op % OPCODES.len()];
+            let writes = matches!(opcode, 1 | 2 | 3 | 7 | 8);
+            let jumps = matches!(opcode, 5 | 6);
+
+            let mut instruction = opcode;
+            let mut operands = Vec::with_capacity(params);
+            for i in 0..params {
+                let raw = Word::from(values[i]);
+                let (mode, value) = if writes && i == params - 1 {
+                    match modes[i] % 2 {
+                        0 => (0, data + raw.rem_euclid(DATA_WORDS as Word)),
+                        _ => (2, raw.rem_euclid(DATA_WORDS as Word)),
+                    }
+                } else if jumps && i == 1 {
+                    (1, starts[raw.rem_euclid(starts.len() as Word) as usize])
+                } else {
+                    match modes[i] % 3 {
+                        0 => (0, raw.rem_euclid(size)),
+                        1 => (1, raw),
+                        _ => (2, raw.rem_euclid(DATA_WORDS as Word)),
+                    }
+                };
+                instruction += mode * 10i64.pow(i as u32 + 2);
+                operands.push(value);
+            }
+
+            program.push(instruction);
+            program.extend(operands);
+        }
+
+        program.push(99);
+        program.resize(size as usize, 0);
+        program
+    }
+
+    fn choice() -> impl Strategy<Value = Choice> {
+        (any::<usize>(), any::<[u8; 3]>(), any::<[i16; 3]>())
+    }
+
+    proptest! {
+        #[test]
+        fn backends_agree_on_random_programs(
+            choices in prop::collection::vec(choice(), 1..24),
+            inputs in prop::collection::vec(-100i64..100, 0..6),
+        ) {
+            let program = Memory::from_vec(assemble(&choices));
+            let harness = Differential::default().max_steps(2_000);
+
+            if let Err(mismatch) = harness.check(&program, &inputs) {
+                panic!("{}", mismatch);
+            }
+        }
+    }
+
+    #[test]
+    fn agrees_on_quine() -> Result<()> {
+        crate::init_logging();
+        let program: Memory =
+            "109,1,204,-1,1001,100,1,100,1008,100,16,101,1006,101,0,99".parse()?;
+
+        let outcome = Differential::default()
+            .check(&program, &[])
+            .map_err(|m| anyhow::anyhow!("{}", m))?;
+
+        assert_eq!(program.to_vec(), outcome.outputs);
+
+        Ok(())
+    }
+
+    #[test]
+    fn agrees_on_errors() -> Result<()> {
+        crate::init_logging();
+        let program: Memory = "3,0,3,0,99".parse()?;
+
+        let outcome = Differential::default()
+            .check(&program, &[7])
+            .map_err(|m| anyhow::anyhow!("{}", m))?;
+
+        assert_eq!(
+            Ending::Failed(
+                ErrorKind::UnexpectedEndOfInput,
+                Address::new(2),
+                "7,0,3,0,99".parse()?
+            ),
+            outcome.ending
+        );
+
+        Ok(())
+    }
+
+    /// Runs programs on `SyncBackend`, but scribbles over memory on failure
+    struct Scribbling;
+
+    impl Backend for Scribbling {
+        fn name(&self) -> &str {
+            "scribbling"
+        }
+
+        fn evaluate(&self, program: &Memory, inputs: &[Word], max_steps: usize) -> Outcome {
+            let mut outcome = SyncBackend::fresh().evaluate(program, inputs, max_steps);
+            if let Ending::Failed(_, _, memory) = &mut outcome.ending {
+                memory.write_arbitrary(Address::new(0), -1);
+            }
+            outcome
+        }
+    }
+
+    #[test]
+    fn detects_differing_memory_on_errors() -> Result<()> {
+        crate::init_logging();
+        let program: Memory = "3,0,3,0,99".parse()?;
+
+        let mismatch = Differential::new()
+            .backend(SyncBackend::fresh())
+            .backend(Scribbling)
+            .check(&program, &[7])
+            .expect_err("backends should disagree about memory");
+
+        assert_eq!("scribbling", mismatch.actual.0);
+
+        Ok(())
+    }
+
+    #[test]
+    fn arithmetic_overflow_wraps() -> Result<()> {
+        crate::init_logging();
+        let program: Memory = "1101,9223372036854775807,1,7,4,7,99,0".parse()?;
+
+        let outcome = Differential::default()
+            .check(&program, &[])
+            .map_err(|m| anyhow::anyhow!("{}", m))?;
+
+        assert_eq!(vec![Word::MIN], outcome.outputs);
+
+        Ok(())
+    }
+
+    #[test]
+    fn stops_at_step_limit() -> Result<()> {
+        crate::init_logging();
+        let program: Memory = "1105,1,0".parse()?;
+
+        let outcome = Differential::default()
+            .max_steps(10)
+            .check(&program, &[])
+            .map_err(|m| anyhow::anyhow!("{}", m))?;
+
+        assert_eq!(Ending::StepLimit(program), outcome.ending);
+
+        Ok(())
+    }
+}
@@ -0,0 +1,167 @@
+use super::{
+    ops::{Instruction, OpCode, ParameterMode},
+    Word,
+};
+use std::{collections::BTreeMap, fmt};
+
+/// Where an instruction finds or puts a value
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum Operand<L> {
+    /// A constant
+    Imm(Word),
+    /// The address of a label, as a constant
+    Addr(L),
+    /// The word at a label
+    At(L),
+    /// The word at an offset within the code
+    Offset(usize),
+    /// The word at an offset from the relative base
+    Rel(Word),
+}
+
+impl<L> Operand<L> {
+    fn mode(&self) -> ParameterMode {
+        match self {
+            Self::At(_) | Self::Offset(_) => ParameterMode::Position,
+            Self::Imm(_) | Self::Addr(_) => ParameterMode::Immediate,
+            Self::Rel(_) => ParameterMode::Relative,
+        }
+    }
+}
+
+/// Lays out instructions and data as though they start at address `0`,
+/// keeping track of the words which hold addresses
+///
+/// Labels may be referred to before they are placed. Their addresses are
+/// filled in by `finish`, and any which were never placed are left for the
+/// caller to resolve.
+pub(crate) struct Emitter<L> {
+    words: Vec<Word>,
+    labels: BTreeMap<L, usize>,
+    /// Words holding the address of a label
+    fixups: Vec<(usize, L)>,
+    /// Words holding an offset within the code
+    relocations: Vec<usize>,
+}
+
+impl<L> Default for Emitter<L> {
+    fn default() -> Self {
+        Self {
+            words: Vec::new(),
+            labels: BTreeMap::new(),
+            fixups: Vec::new(),
+            relocations: Vec::new(),
+        }
+    }
+}
+
+impl<L: Copy + Ord + fmt::Debug> Emitter<L> {
+    /// The offset at which the next word will be placed
+    pub(crate) fn here(&self) -> usize {
+        self.words.len()
+    }
+
+    /// Places `label` at the next word
+    pub(crate) fn place(&mut self, label: L) {
+        let previous = self.labels.insert(label, self.words.len());
+        assert!(previous.is_none(), "label `{:?}` is placed twice", label);
+    }
+
+    /// Places `label` at the start of `values`
+    pub(crate) fn data(&mut self, label: L, values: &[Word]) {
+        self.place(label);
+        self.words.extend_from_slice(values);
+    }
+
+    /// Places `label` at a word holding the address of `target`
+    pub(crate) fn pointer(&mut self, label: L, target: L) {
+        self.place(label);
+        self.fixups.push((self.words.len(), target));
+        self.words.push(0);
+    }
+
+    /// Overwrites a word which has already been emitted
+    pub(crate) fn patch(&mut self, at: usize, value: Word) {
+        self.words[at] = value;
+    }
+
+    /// Emits an instruction, returning the offset it was placed at
+    pub(crate) fn emit(&mut self, opcode: OpCode, operands: &[Operand<L>]) -> usize {
+        let at = self.words.len();
+        let modes: Vec<ParameterMode> = operands.iter().map(Operand::mode).collect();
+        self.words.push(Instruction::new(opcode, &modes).encode());
+        for &operand in operands {
+            let word = match operand {
+                Operand::Imm(value) | Operand::Rel(value) => value,
+                Operand::Addr(label) | Operand::At(label) => {
+                    self.fixups.push((self.words.len(), label));
+                    0
+                }
+                Operand::Offset(offset) => {
+                    self.relocations.push(self.words.len());
+                    offset as Word
+                }
+            };
+            self.words.push(word);
+        }
+        at
+    }
+
+    pub(crate) fn copy(&mut self, from: Operand<L>, to: Operand<L>) {
+        if from != to {
+            self.emit(OpCode::Add, &[from, Operand::Imm(0), to]);
+        }
+    }
+
+    pub(crate) fn jump(&mut self, label: L) {
+        self.jump_if(Operand::Imm(1), label);
+    }
+
+    pub(crate) fn jump_if(&mut self, cond: Operand<L>, label: L) {
+        self.emit(OpCode::JumpNonZero, &[cond, Operand::Addr(label)]);
+    }
+
+    pub(crate) fn jump_unless(&mut self, cond: Operand<L>, label: L) {
+        self.emit(OpCode::JumpZero, &[cond, Operand::Addr(label)]);
+    }
+
+    /// Jumps to the address held at `target`
+    pub(crate) fn jump_through(&mut self, target: Operand<L>) {
+        self.emit(OpCode::JumpZero, &[Operand::Imm(0), target]);
+    }
+
+    /// Fills in the address of every placed label
+    pub(crate) fn finish(mut self) -> Emitted<L> {
+        let mut unresolved = Vec::new();
+        for &(at, label) in &self.fixups {
+            match self.labels.get(&label) {
+                Some(&offset) => {
+                    self.words[at] = offset as Word;
+                    self.relocations.push(at);
+                }
+                None => unresolved.push((at, label)),
+            }
+        }
+        self.relocations.sort_unstable();
+
+        Emitted {
+            words: self.words,
+            labels: self.labels,
+            relocations: self.relocations,
+            unresolved,
+        }
+    }
+}
+
+/// The words laid out by an `Emitter`
+pub(crate) struct Emitted<L> {
+    pub(crate) words: Vec<Word>,
+    /// The offset of every placed label
+    pub(crate) labels: BTreeMap<L, usize>,
+    /// The words which hold an offset within the code, and so must be
+    /// rebased if it is placed anywhere but address `0`
+    pub(crate) relocations: Vec<usize>,
+    /// Words which should hold the address of a label which was never
+    /// placed
+    pub(crate) unresolved: Vec<(usize, L)>,
+}
@@ -1,21 +1,24 @@
 use super::{
     decode::{
-        self, decode, BinaryOperands, Decoded, InputOperands, JumpIfOperands, OutputOperands,
+        self, decode, BinaryOperands, Decoded, InputOperands, JumpIfOperands, Output,
+        OutputOperands, Parameter,
     },
     error,
     ops::Instruction,
-    Address, Buffer, Memory, Relative, Word,
+    state, Address, Buffer, DebugInfo, History, Image, MachineState, Memory, Observer, Relative,
+    Undone, Word,
 };
 use snafu::{ResultExt, Snafu};
 use std::{
     convert::TryFrom,
-    fmt, ops,
+    error::Error,
+    fmt,
     sync::{
         atomic::{AtomicUsize, Ordering},
         mpsc::{channel, Receiver, RecvError, SendError, Sender},
+        Arc,
     },
 };
-use thiserror::Error;
 
 /// A counter which keeps track of the currently executing instruction
 /// in the Intcode executor
@@ -38,6 +41,11 @@ impl ProgramCounter {
     /// A program counter initialized to start from the first address in memory
     pub const START: Self = Self(0);
 
+    /// A program counter initialized to start from `address`
+    pub const fn at(address: Address) -> Self {
+        Self(address.value())
+    }
+
     #[inline]
     /// Obtains the address of parameter `idx` for the current instruction
     pub const fn param(self, idx: u8) -> Address {
@@ -70,56 +78,200 @@ static NEXT_EXECUTABLE_ID: AtomicUsize = AtomicUsize::new(0);
 /// Executes programs, keeps track of current position, and relays input and
 /// output during execution.
 #[derive(Debug)]
-pub struct Executable {
+pub struct Executable<O = ()> {
     id: usize,
+    image: Memory,
     memory: Memory,
     pc: ProgramCounter,
+    /// Where execution begins, and resumes after a reset
+    entry: ProgramCounter,
     rel: Address,
     input: Receiver<Word>,
-    output: Sender<Word>,
+    /// Where outputs are sent, or `None` if writing output should fail
+    output: Option<Sender<Word>>,
     steps: usize,
+    debug: Option<Arc<DebugInfo>>,
+    observer: O,
 }
 
 impl From<Memory> for Executable {
     fn from(memory: Memory) -> Self {
+        Self::from(Image::new(memory))
+    }
+}
+
+impl From<Image> for Executable {
+    /// Starts execution at the image's entry point, describing errors in
+    /// terms of its symbols
+    fn from(image: Image) -> Self {
+        let entry = ProgramCounter::at(image.entry());
+        let debug = image
+            .symbols()
+            .next()
+            .map(|_| Arc::new(DebugInfo::from(&image)));
+        let memory = image.into_memory();
         Self {
             id: NEXT_EXECUTABLE_ID.fetch_add(1, Ordering::AcqRel),
+            image: memory.clone(),
             memory,
-            pc: ProgramCounter::START,
+            pc: entry,
+            entry,
             rel: Address::new(0),
             input: channel().1,
-            output: channel().0,
+            output: None,
             steps: 0,
+            debug,
+            observer: (),
         }
     }
 }
 
+impl<O> fmt::Display for Executable<O> {
+    /// Summarises the machine on one line, ending with the instruction it
+    /// will execute next
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "#{} ", self.id)?;
+        state::write_status(f, self.pc.address(), self.rel, self.steps, &self.memory)
+    }
+}
+
 impl Executable {
+    pub fn buffer_to(&mut self, target: &mut Executable) -> Buffer {
+        Buffer::between(self, target)
+    }
+}
+
+impl<O> Executable<O> {
+    /// Restores memory to the program image the executable was created
+    /// from and rewinds the program counter and relative base
+    ///
+    /// Input and output pipes are left in place, so a single executable can
+    /// be reused to evaluate the same program against many inputs.
+    pub fn reset(&mut self) {
+        self.memory.reset_to(&self.image);
+        self.pc = self.entry;
+        self.rel = Address::ZERO;
+        self.steps = 0;
+    }
+
+    /// Attaches an observer which will be notified of execution events
+    pub fn with_observer<P: Observer>(self, observer: P) -> Executable<P> {
+        Executable {
+            id: self.id,
+            image: self.image,
+            memory: self.memory,
+            pc: self.pc,
+            entry: self.entry,
+            rel: self.rel,
+            input: self.input,
+            output: self.output,
+            steps: self.steps,
+            debug: self.debug,
+            observer,
+        }
+    }
+
+    /// Attaches debug information, which errors use to describe where
+    /// execution failed
+    pub fn with_debug_info(mut self, debug: Arc<DebugInfo>) -> Self {
+        self.debug = Some(debug);
+        self
+    }
+
+    /// Provides access to the attached observer
+    pub fn observer(&self) -> &O {
+        &self.observer
+    }
+
+    /// Provides mutable access to the attached observer
+    pub fn observer_mut(&mut self) -> &mut O {
+        &mut self.observer
+    }
+
+    /// Detaches the observer, discarding the executable
+    pub fn into_observer(self) -> O {
+        self.observer
+    }
+
+    /// A number which identifies the executable among all those created
+    pub fn id(&self) -> usize {
+        self.id
+    }
+
+    /// The address of the next instruction to execute
+    pub fn pc(&self) -> Address {
+        self.pc.address()
+    }
+
+    /// The base address for relative mode parameters
+    pub fn relative_base(&self) -> Address {
+        self.rel
+    }
+
+    /// The number of instructions executed so far
+    pub fn steps(&self) -> usize {
+        self.steps
+    }
+
+    /// The current contents of memory
+    pub fn memory(&self) -> &Memory {
+        &self.memory
+    }
+
+    /// Takes a copy of the registers and memory
+    pub fn state(&self) -> MachineState {
+        MachineState {
+            pc: self.pc.address(),
+            relative_base: self.rel,
+            steps: self.steps,
+            memory: self.memory.clone(),
+        }
+    }
+
+    /// Replaces the registers and memory with those of `state`
+    ///
+    /// The attached observer is not notified, so any history it keeps of
+    /// earlier execution no longer applies.
+    pub fn restore_state(&mut self, state: MachineState) {
+        self.pc = ProgramCounter::at(state.pc);
+        self.rel = state.relative_base;
+        self.steps = state.steps;
+        self.memory = state.memory;
+    }
+
     pub fn single_input(&mut self, value: Word) {
         let (tx, rx) = channel();
         self.input = rx;
         tx.send(value).unwrap();
     }
 
-    pub fn pipe_to(&mut self, target: &mut Executable) -> Sender<Word> {
+    pub fn pipe_to<P>(&mut self, target: &mut Executable<P>) -> Sender<Word> {
         let (tx, rx) = channel();
-        self.output = tx.clone();
+        self.output = Some(tx.clone());
         target.input = rx;
         tx
     }
 
-    pub fn buffer_to(&mut self, target: &mut Executable) -> Buffer {
-        Buffer::between(self, target)
+    pub(super) fn pipe_outputs_to(&mut self, target: Sender<Word>) {
+        self.output = Some(target);
     }
 
-    pub(super) fn pipe_outputs_to(&mut self, target: Sender<Word>) {
-        self.output = target;
+    /// Disconnects the output pipe, returning it
+    ///
+    /// A drain of the pipe finishes once the returned sender is dropped, and
+    /// any further output fails as though the pipe were closed.
+    pub(crate) fn take_output(&mut self) -> Option<Sender<Word>> {
+        self.output.take()
     }
 
     pub(super) fn pipe_inputs_from(&mut self, source: Receiver<Word>) {
         self.input = source;
     }
 
+    pub(crate) fn memory_mut(&mut self) -> &mut Memory {
+        &mut self.memory
+    }
+
     pub fn drain(&mut self) -> OutputDrain {
         let (tx, rx) = channel();
         self.pipe_outputs_to(tx);
@@ -136,29 +288,81 @@ impl Executable {
 
         decode(i, self.pc, &self.memory)
     }
+}
+
+impl Executable<History> {
+    /// Reverts the effects of the most recently executed instruction
+    ///
+    /// Returns `None` once there is no further history to undo.
+    pub fn step_back(&mut self) -> Option<Undone> {
+        self.observer.undo(
+            &mut self.memory,
+            &mut self.pc,
+            &mut self.rel,
+            &mut self.steps,
+        )
+    }
 
+    /// Rewinds execution to just before the `count`th most recent
+    /// instruction that consumed input
+    ///
+    /// Returns the undone inputs in the order they were originally received;
+    /// these must be provided again for execution to follow the same path.
+    pub fn rewind_inputs(&mut self, count: usize) -> Vec<Word> {
+        self.observer.rewind_inputs(
+            count,
+            &mut self.memory,
+            &mut self.pc,
+            &mut self.rel,
+            &mut self.steps,
+        )
+    }
+}
+
+impl<O: Observer + Send + 'static> Executable<O> {
     pub fn execute_in_thread(self) -> std::thread::JoinHandle<Result<Memory, ExecutionError>> {
         std::thread::spawn(move || self.execute())
     }
+}
 
+impl<O: Observer> Executable<O> {
     /// Executes the Intcode program in memory until a halt instruction is
     /// encountered or an invalid operation causes termination due to an
     /// `ExecutionError`
     pub fn execute(mut self) -> Result<Memory, ExecutionError> {
-        while self.step()? {}
+        self.run()?;
 
         Ok(self.memory)
     }
 
+    /// Executes the Intcode program in memory until a halt instruction is
+    /// encountered, without consuming the executable
+    ///
+    /// Combined with `reset`, this allows the same executable to be run
+    /// repeatedly.
+    pub fn run(&mut self) -> Result<&Memory, ExecutionError> {
+        while self.step()? {}
+
+        Ok(&self.memory)
+    }
+
     pub fn step(&mut self) -> Result<bool, ExecutionError> {
-        Ok(self.execute_op(self.read_instruction()?)?)
+        self.try_step()
+            .map_err(|e| ExecutionError::new(e, self.debug.clone()))
+    }
+
+    fn try_step(&mut self) -> Result<bool, ExecutionErrorInner> {
+        let op = self.read_instruction()?;
+        self.observer.on_fetch(self.pc.address(), &op);
+        self.execute_op(op)
     }
 
     fn execute_op(&mut self, op: Decoded) -> Result<bool, ExecutionErrorInner> {
         self.steps += 1;
         match op {
-            Decoded::Add(params) => self.execute_binary_op(params, ops::Add::add),
-            Decoded::Mul(params) => self.execute_binary_op(params, ops::Mul::mul),
+            // Overflow wraps regardless of build profile
+            Decoded::Add(params) => self.execute_binary_op(params, Word::wrapping_add),
+            Decoded::Mul(params) => self.execute_binary_op(params, Word::wrapping_mul),
             Decoded::Input(params) => self.execute_input(params),
             Decoded::Output(params) => self.execute_output(params),
             Decoded::JumpNonZero(params) => self.execute_jump_if(params, true),
@@ -179,24 +383,46 @@ impl Executable {
 
         Ok(true)
     }
+
+    fn load(&mut self, param: Parameter) -> Result<Word, ExecutionErrorInner> {
+        let address = match param {
+            Parameter::Immediate(value) => return Ok(value),
+            _ => param
+                .resolve(self.rel)
+                .context(InvalidAddress { pc: self.pc })?
+                .expect("non-immediate parameters refer to memory"),
+        };
+
+        let value = self.memory.read_or_default(address);
+        self.observer.on_read(self.pc.address(), address, value);
+
+        Ok(value)
+    }
+
+    fn store(&mut self, target: Output, value: Word) -> Result<(), ExecutionErrorInner> {
+        let address = target
+            .resolve(self.rel)
+            .context(InvalidAddress { pc: self.pc })?;
+
+        let prior = self.memory.write_arbitrary(address, value);
+        self.observer
+            .on_write(self.pc.address(), address, prior, value);
+
+        Ok(())
+    }
+
     fn execute_binary_op(
         &mut self,
         operands: BinaryOperands,
         f: fn(Word, Word) -> Word,
     ) -> Result<(), ExecutionErrorInner> {
-        let left = operands
-            .left
-            .load(self.rel, &self.memory)
-            .context(InvalidAddress { pc: self.pc })?;
-        let right = operands
-            .right
-            .load(self.rel, &self.memory)
-            .context(InvalidAddress { pc: self.pc })?;
+        let left = self.load(operands.left)?;
+        let right = self.load(operands.right)?;
         let result = f(left, right);
 
         log::trace!("{}@{}: {} {} = {}", self.id, self.pc, left, right, result);
 
-        operands.target.store(self.rel, &mut self.memory, result);
+        self.store(operands.target, result)?;
 
         self.pc.advance(4);
         Ok(())
@@ -210,32 +436,30 @@ impl Executable {
 
         log::trace!("{}@{}: {} =>", self.id, self.pc, value);
 
-        operands.target.store(self.rel, &mut self.memory, value);
+        self.observer.on_input(self.pc.address(), value);
+        self.store(operands.target, value)?;
 
         self.pc.advance(2);
         Ok(())
     }
 
     fn execute_output(&mut self, operands: OutputOperands) -> Result<(), ExecutionErrorInner> {
-        let value = operands
-            .source
-            .load(self.rel, &self.memory)
-            .context(InvalidAddress { pc: self.pc })?;
+        let value = self.load(operands.source)?;
 
         log::trace!("{}@{}: => {}", self.id, self.pc, value);
 
-        self.output
-            .send(value)
-            .context(OutputPipeClosed { pc: self.pc })?;
+        let sent = match &self.output {
+            Some(output) => output.send(value),
+            None => Err(SendError(value)),
+        };
+        sent.context(OutputPipeClosed { pc: self.pc })?;
+        self.observer.on_output(self.pc.address(), value);
         self.pc.advance(2);
         Ok(())
     }
 
     fn execute_add_rel(&mut self, operands: OutputOperands) -> Result<(), ExecutionErrorInner> {
-        let value = operands
-            .source
-            .load(self.rel, &self.memory)
-            .context(InvalidAddress { pc: self.pc })?;
+        let value = self.load(operands.source)?;
 
         let next = (self.rel + Relative::from(value)).context(InvalidAddress { pc: self.pc })?;
 
@@ -248,6 +472,8 @@ impl Executable {
             next
         );
 
+        self.observer
+            .on_relative_base(self.pc.address(), self.rel, next);
         self.rel = next;
         self.pc.advance(2);
         Ok(())
@@ -258,17 +484,14 @@ impl Executable {
         operands: JumpIfOperands,
         non_zero: bool,
     ) -> Result<(), ExecutionErrorInner> {
-        let value = operands
-            .value
-            .load(self.rel, &self.memory)
-            .context(InvalidAddress { pc: self.pc })?;
-        let target_raw = operands
-            .jump_target
-            .load(self.rel, &self.memory)
-            .context(InvalidAddress { pc: self.pc })?;
+        let value = self.load(operands.value)?;
+        let target_raw = self.load(operands.jump_target)?;
         let target = Address::try_from(target_raw).context(InvalidAddress { pc: self.pc })?;
 
-        if (value != 0) == non_zero {
+        let taken = (value != 0) == non_zero;
+        self.observer.on_jump(self.pc.address(), target, taken);
+
+        if taken {
             log::trace!("{}@{}: {} ~> {}", self.id, self.pc, value, target);
 
             self.pc.jump(target);
@@ -286,20 +509,14 @@ impl Executable {
         operands: BinaryOperands,
         f: fn(&Word, &Word) -> bool,
     ) -> Result<(), ExecutionErrorInner> {
-        let left = operands
-            .left
-            .load(self.rel, &self.memory)
-            .context(InvalidAddress { pc: self.pc })?;
-        let right = operands
-            .right
-            .load(self.rel, &self.memory)
-            .context(InvalidAddress { pc: self.pc })?;
+        let left = self.load(operands.left)?;
+        let right = self.load(operands.right)?;
 
         let result = if f(&left, &right) { 1 } else { 0 };
 
         log::trace!("{}@{}: {} {} = {}", self.id, self.pc, left, right, result);
 
-        operands.target.store(self.rel, &mut self.memory, result);
+        self.store(operands.target, result)?;
 
         self.pc.advance(4);
         Ok(())
@@ -329,9 +546,116 @@ impl OutputDrain {
 /// * Execution of an invalid instruction
 /// * Access to an address beyond the memory limit
 /// * Attempt to interpret a negative value as an address
-#[derive(Error, Debug)]
-#[error("{0}")]
-pub struct ExecutionError(#[from] ExecutionErrorInner);
+///
+/// If the executable had debug information attached, the error describes
+/// where execution failed in terms of labels and source lines.
+#[derive(Debug)]
+pub struct ExecutionError {
+    inner: ExecutionErrorInner,
+    debug: Option<Arc<DebugInfo>>,
+}
+
+impl fmt::Display for ExecutionError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match &self.debug {
+            Some(debug) => write!(f, "{}; pc = {}", self.kind(), debug.locate(self.pc())),
+            None => self.inner.fmt(f),
+        }
+    }
+}
+
+impl Error for ExecutionError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        Some(&self.inner)
+    }
+}
+
+impl From<ExecutionErrorInner> for ExecutionError {
+    fn from(inner: ExecutionErrorInner) -> Self {
+        Self::new(inner, None)
+    }
+}
+
+impl ExecutionError {
+    pub(crate) fn new(inner: ExecutionErrorInner, debug: Option<Arc<DebugInfo>>) -> Self {
+        Self { inner, debug }
+    }
+
+    /// Attaches debug information used to describe where execution failed
+    pub fn with_debug_info(mut self, debug: Arc<DebugInfo>) -> Self {
+        self.debug = Some(debug);
+        self
+    }
+
+    /// Whether execution was stopped by request rather than by a failure
+    pub fn is_cancelled(&self) -> bool {
+        matches!(self.inner, ExecutionErrorInner::Cancelled { .. })
+    }
+
+    /// The category of the error
+    pub fn kind(&self) -> ErrorKind {
+        match self.inner {
+            ExecutionErrorInner::InvalidInstruction { .. } => ErrorKind::InvalidInstruction,
+            ExecutionErrorInner::OutOfBoundsAccess { .. } => ErrorKind::OutOfBoundsAccess,
+            ExecutionErrorInner::UnexpectedEndOfProgram { .. } => ErrorKind::UnexpectedEndOfProgram,
+            ExecutionErrorInner::InvalidAddress { .. } => ErrorKind::InvalidAddress,
+            ExecutionErrorInner::DecodeError { .. } => ErrorKind::DecodeError,
+            ExecutionErrorInner::UnexpectedEndOfInput { .. } => ErrorKind::UnexpectedEndOfInput,
+            ExecutionErrorInner::OutputPipeClosed { .. } => ErrorKind::OutputPipeClosed,
+            ExecutionErrorInner::Cancelled { .. } => ErrorKind::Cancelled,
+        }
+    }
+
+    /// The address of the instruction which was executing
+    pub fn pc(&self) -> Address {
+        match self.inner {
+            ExecutionErrorInner::InvalidInstruction { pc, .. }
+            | ExecutionErrorInner::OutOfBoundsAccess { pc, .. }
+            | ExecutionErrorInner::UnexpectedEndOfProgram { pc, .. }
+            | ExecutionErrorInner::InvalidAddress { pc, .. }
+            | ExecutionErrorInner::DecodeError { pc, .. }
+            | ExecutionErrorInner::UnexpectedEndOfInput { pc, .. }
+            | ExecutionErrorInner::OutputPipeClosed { pc, .. }
+            | ExecutionErrorInner::Cancelled { pc } => pc.address(),
+        }
+    }
+}
+
+/// The category of an `ExecutionError`
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum ErrorKind {
+    /// An instruction had an unknown opcode or parameter mode
+    InvalidInstruction,
+    /// Memory was accessed beyond the memory limit
+    OutOfBoundsAccess,
+    /// The program counter moved beyond the end of memory
+    UnexpectedEndOfProgram,
+    /// A negative value was used as an address
+    InvalidAddress,
+    /// An instruction's parameters could not be decoded
+    DecodeError,
+    /// Input was requested after the input pipe closed
+    UnexpectedEndOfInput,
+    /// Output was sent after the output pipe closed
+    OutputPipeClosed,
+    /// Execution was stopped through a `CancelHandle`
+    Cancelled,
+}
+
+impl fmt::Display for ErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            ErrorKind::InvalidInstruction => "invalid instruction",
+            ErrorKind::OutOfBoundsAccess => "attempted out of bounds access",
+            ErrorKind::UnexpectedEndOfProgram => "unexpected end of program",
+            ErrorKind::InvalidAddress => "invalid address",
+            ErrorKind::DecodeError => "unable to decode instruction",
+            ErrorKind::UnexpectedEndOfInput => "unexpected end of input",
+            ErrorKind::OutputPipeClosed => "attempted to write on a closed output pipe",
+            ErrorKind::Cancelled => "execution cancelled",
+        })
+    }
+}
 
 #[derive(Snafu, Debug)]
 #[snafu(visibility(pub(crate)))]
@@ -371,4 +695,6 @@ pub(crate) enum ExecutionErrorInner {
         source: SendError<Word>,
         pc: ProgramCounter,
     },
+    #[snafu(display("execution cancelled; pc = {}", pc))]
+    Cancelled { pc: ProgramCounter },
 }
@@ -0,0 +1,333 @@
+use super::{recording::ascii_char, AsyncExecutable, ExecutionError, Image, Memory, Word};
+use futures::{
+    channel::mpsc::{channel, Receiver, Sender},
+    stream::Fuse,
+    SinkExt, StreamExt,
+};
+use regex::Regex;
+use thiserror::Error;
+use tokio::task::JoinHandle;
+
+/// An error while scripting an ASCII program
+#[derive(Error, Debug)]
+pub enum ExpectError {
+    /// The program stopped producing output before any pattern matched
+    #[error("program stopped while expecting {patterns}; unmatched output:\n{output}")]
+    Halted { patterns: String, output: String },
+    /// The program stopped reading input
+    #[error("program is no longer accepting input")]
+    InputClosed,
+    /// The program failed
+    #[error(transparent)]
+    Execution(#[from] ExecutionError),
+    /// The program failed, and its error was returned by an earlier call
+    #[error("program failed earlier in the session")]
+    Failed,
+}
+
+/// Output which matched an expected pattern
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Match {
+    /// Output received before the match
+    pub before: String,
+    groups: Vec<Option<String>>,
+}
+
+impl Match {
+    /// The text of the whole match
+    pub fn text(&self) -> &str {
+        self.group(0).unwrap_or_default()
+    }
+
+    /// The text of a capture group, or `None` if it did not participate in
+    /// the match
+    pub fn group(&self, idx: usize) -> Option<&str> {
+        self.groups.get(idx).and_then(|g| g.as_deref())
+    }
+}
+
+/// The remainder of a scripted session once the program has halted
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Finished {
+    /// ASCII output which was not consumed by an expectation
+    pub output: String,
+    /// Output values which were not ASCII characters, in order
+    pub values: Vec<Word>,
+    /// The final state of memory
+    pub memory: Memory,
+}
+
+/// Drives an ASCII program from a script, in the manner of `expect`
+///
+/// Output is buffered until it matches an expected pattern, at which point
+/// the output up to and including the match is consumed. Patterns are tried
+/// as each line of output is completed, and once more when the program stops
+/// producing output, so that a pattern never matches a partial line. Output
+/// values which are not ASCII characters are set aside and returned by
+/// `finish`.
+///
+/// ## Example
+///
+/// ```
+/// use intcode::{AsciiSession, Memory};
+/// use regex::Regex;
+///
+/// // Prints "?", then echoes a single character
+/// const ECHO_ONE: &str = "104,63,104,10,3,11,4,11,99,0,0,0";
+///
+/// let mut runtime = tokio::runtime::Runtime::new().unwrap();
+/// runtime.block_on(async {
+///     let memory: Memory = ECHO_ONE.parse().expect("valid data");
+///     let mut session = AsciiSession::start(memory);
+///
+///     session.expect(&Regex::new(r"\?\n").unwrap()).await?;
+///     session.send_line("x").await?;
+///     let matched = session.expect(&Regex::new(r"(.)").unwrap()).await?;
+///     assert_eq!(Some("x"), matched.group(1));
+///
+///     session.finish().await?;
+///     Ok::<_, intcode::ExpectError>(())
+/// }).unwrap();
+/// ```
+#[derive(Debug)]
+pub struct AsciiSession {
+    input: Sender<Word>,
+    output: Fuse<Receiver<Word>>,
+    execution: Option<JoinHandle<Result<Memory, ExecutionError>>>,
+    /// The final state of memory, once the program has halted
+    memory: Option<Memory>,
+    buffer: String,
+    values: Vec<Word>,
+    transcript: String,
+}
+
+impl AsciiSession {
+    /// Starts executing a program in the background
+    ///
+    /// ## Panics
+    ///
+    /// Panics if not called from within a Tokio runtime.
+    pub fn start(program: impl Into<Image>) -> Self {
+        let mut exe = AsyncExecutable::from(program.into());
+        let (input, input_rx) = channel(20);
+        let (output_tx, output) = channel(20);
+        exe.pipe_inputs_from(input_rx);
+        exe.pipe_outputs_to(output_tx);
+
+        Self {
+            input,
+            output: output.fuse(),
+            execution: Some(tokio::spawn(exe.execute())),
+            memory: None,
+            buffer: String::new(),
+            values: Vec::new(),
+            transcript: String::new(),
+        }
+    }
+
+    /// Waits for output matching `pattern`
+    pub async fn expect(&mut self, pattern: &Regex) -> Result<Match, ExpectError> {
+        self.expect_any(&[pattern]).await.map(|(_, m)| m)
+    }
+
+    /// Waits for output matching any of `patterns`
+    ///
+    /// Returns the index of the pattern which matched earliest in the
+    /// output, preferring the first pattern given if several match at the
+    /// same position. If the program fails before any pattern matches, its
+    /// error is returned.
+    pub async fn expect_any(&mut self, patterns: &[&Regex]) -> Result<(usize, Match), ExpectError> {
+        if let Some(found) = self.find(patterns) {
+            return Ok(found);
+        }
+
+        loop {
+            match self.output.next().await {
+                Some(value) => {
+                    if self.receive(value) {
+                        if let Some(found) = self.find(patterns) {
+                            return Ok(found);
+                        }
+                    }
+                }
+                None => {
+                    if let Some(found) = self.find(patterns) {
+                        return Ok(found);
+                    }
+                    self.wait().await?;
+
+                    let patterns = patterns
+                        .iter()
+                        .map(|p| format!("`{}`", p))
+                        .collect::<Vec<_>>()
+                        .join(" or ");
+                    return Err(ExpectError::Halted {
+                        patterns,
+                        output: self.buffer.clone(),
+                    });
+                }
+            }
+        }
+    }
+
+    /// Sends a line of text, followed by a newline
+    pub async fn send_line(&mut self, line: &str) -> Result<(), ExpectError> {
+        for b in line.bytes().chain(Some(b'\n')) {
+            self.input
+                .send(Word::from(b))
+                .await
+                .map_err(|_| ExpectError::InputClosed)?;
+        }
+
+        self.transcript.push_str(line);
+        self.transcript.push('\n');
+        Ok(())
+    }
+
+    /// Everything output by the program and sent to it so far
+    pub fn transcript(&self) -> &str {
+        &self.transcript
+    }
+
+    /// Closes input and waits for the program to halt
+    pub async fn finish(mut self) -> Result<Finished, ExpectError> {
+        self.input.close_channel();
+        while let Some(value) = self.output.next().await {
+            self.buffer.push_str(&render(value, &mut self.values));
+        }
+
+        self.wait().await?;
+        Ok(Finished {
+            output: self.buffer,
+            values: self.values,
+            memory: self.memory.expect("a halted program leaves its memory"),
+        })
+    }
+
+    /// Waits for the program to halt, keeping its final memory
+    async fn wait(&mut self) -> Result<(), ExpectError> {
+        match self.execution.take() {
+            Some(execution) => {
+                let memory = execution.await.expect("execution task panicked")?;
+                self.memory = Some(memory);
+                Ok(())
+            }
+            None if self.memory.is_some() => Ok(()),
+            None => Err(ExpectError::Failed),
+        }
+    }
+
+    /// Buffers an output value, returning whether it completed a line
+    fn receive(&mut self, value: Word) -> bool {
+        let text = render(value, &mut self.values);
+        self.buffer.push_str(&text);
+        self.transcript.push_str(&text);
+        text == "\n"
+    }
+
+    fn find(&mut self, patterns: &[&Regex]) -> Option<(usize, Match)> {
+        let (idx, captures) = patterns
+            .iter()
+            .enumerate()
+            .filter_map(|(idx, p)| p.captures(&self.buffer).map(|c| (idx, c)))
+            .min_by_key(|(idx, c)| (c.get(0).map_or(0, |m| m.start()), *idx))?;
+
+        let whole = captures.get(0).expect("a match has a whole group");
+        let found = Match {
+            before: self.buffer[..whole.start()].to_string(),
+            groups: captures
+                .iter()
+                .map(|g| g.map(|g| g.as_str().to_string()))
+                .collect(),
+        };
+        let end = whole.end();
+
+        self.buffer.drain(..end);
+        Some((idx, found))
+    }
+}
+
+fn render(value: Word, values: &mut Vec<Word>) -> String {
+    match ascii_char(value) {
+        Some(ch) => ch.to_string(),
+        None => {
+            values.push(value);
+            String::new()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{AsciiSession, ExpectError};
+    use crate::{Address, Memory};
+    use anyhow::Result;
+    use pretty_assertions::assert_eq;
+    use regex::Regex;
+
+    /// Prints "Name?", reads a line, then prints "Hi" followed by one
+    /// hundred times the number of characters read
+    const GREETER: &str = "
+        104,78,104,97,104,109,104,101,104,63,104,10,
+        3,35,1008,35,10,36,1005,36,28,1001,37,100,37,1105,1,12,
+        104,72,104,105,4,37,99,0,0,0";
+
+    #[tokio::test]
+    async fn expects_and_captures() -> Result<()> {
+        crate::init_logging();
+        let memory: Memory = GREETER.parse()?;
+        let mut session = AsciiSession::start(memory);
+
+        let prompt = Regex::new(r"(\w+)\?\n")?;
+        let matched = session.expect(&prompt).await?;
+        assert_eq!(Some("Name"), matched.group(1));
+        assert_eq!("", matched.before);
+
+        session.send_line("abc").await?;
+        let (idx, matched) = session
+            .expect_any(&[&Regex::new("Bye")?, &Regex::new("Hi")?])
+            .await?;
+        assert_eq!(1, idx);
+        assert_eq!("Hi", matched.text());
+
+        let finished = session.finish().await?;
+        assert_eq!(vec![300], finished.values);
+        assert_eq!("", finished.output);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn reports_unmatched_output_on_halt() -> Result<()> {
+        crate::init_logging();
+        let memory: Memory = GREETER.parse()?;
+        let mut session = AsciiSession::start(memory);
+
+        session.send_line("abc").await?;
+        match session.expect(&Regex::new("Command")?).await {
+            Err(ExpectError::Halted { output, .. }) => assert_eq!("Name?\nHi", output),
+            other => panic!("unexpected result: {:?}", other),
+        }
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn reports_failure_instead_of_halt() -> Result<()> {
+        crate::init_logging();
+        // Prints "a", then executes an invalid opcode
+        let memory: Memory = "104,97,98".parse()?;
+        let mut session = AsciiSession::start(memory);
+
+        match session.expect(&Regex::new("Command")?).await {
+            Err(ExpectError::Execution(err)) => assert_eq!(Address::new(2), err.pc()),
+            other => panic!("unexpected result: {:?}", other),
+        }
+        match session.finish().await {
+            Err(ExpectError::Failed) => {}
+            other => panic!("unexpected result: {:?}", other),
+        }
+
+        Ok(())
+    }
+}
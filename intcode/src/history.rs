@@ -0,0 +1,244 @@
+use super::{Address, Decoded, Memory, Observer, ProgramCounter, Word};
+
+#[derive(Clone, Copy, Debug)]
+struct Entry {
+    pc: Address,
+    rel: Option<Address>,
+    writes: usize,
+    input: Option<Word>,
+    output: Option<Word>,
+}
+
+/// An undo log of executed instructions, allowing execution to be reversed
+///
+/// A `History` is an `Observer` which records the prior value of every memory
+/// write along with changes to the program counter and relative base.
+/// Attaching one to an executable enables `step_back` and `rewind_inputs`.
+///
+/// Memory which was grown to accommodate a write is not shrunk when that write
+/// is undone; the grown region is zeroed instead.
+#[derive(Clone, Debug, Default)]
+pub struct History {
+    entries: Vec<Entry>,
+    writes: Vec<(Address, Word)>,
+}
+
+/// An instruction which has been undone
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Undone {
+    /// The address of the undone instruction
+    pub pc: Address,
+    /// The value that the instruction consumed as input
+    ///
+    /// Inputs are not returned to the input pipe when undone, so this value
+    /// must be provided again to re-execute the instruction.
+    pub input: Option<Word>,
+    /// The value that the instruction sent as output
+    pub output: Option<Word>,
+}
+
+impl History {
+    /// The number of instructions which can be undone
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Whether there are any instructions to undo
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Forgets all recorded instructions
+    pub fn clear(&mut self) {
+        self.entries.clear();
+        self.writes.clear();
+    }
+
+    /// The number of recorded instructions which consumed input
+    pub fn input_events(&self) -> usize {
+        self.entries.iter().filter(|e| e.input.is_some()).count()
+    }
+
+    /// Reverts the effects of the most recently executed instruction
+    pub(crate) fn undo(
+        &mut self,
+        memory: &mut Memory,
+        pc: &mut ProgramCounter,
+        rel: &mut Address,
+        steps: &mut usize,
+    ) -> Option<Undone> {
+        let entry = self.entries.pop()?;
+
+        for (address, prior) in self.writes.drain(entry.writes..).rev() {
+            memory.write_arbitrary(address, prior);
+        }
+
+        if let Some(prior) = entry.rel {
+            *rel = prior;
+        }
+
+        pc.jump(entry.pc);
+        // A restored state may have counted fewer steps than are recorded
+        *steps = steps.saturating_sub(1);
+
+        Some(Undone {
+            pc: entry.pc,
+            input: entry.input,
+            output: entry.output,
+        })
+    }
+
+    /// Reverts executed instructions until `count` instructions which
+    /// consumed input have been undone
+    ///
+    /// Returns the undone inputs in the order they were originally received.
+    pub(crate) fn rewind_inputs(
+        &mut self,
+        count: usize,
+        memory: &mut Memory,
+        pc: &mut ProgramCounter,
+        rel: &mut Address,
+        steps: &mut usize,
+    ) -> Vec<Word> {
+        let mut inputs = Vec::with_capacity(count);
+        while inputs.len() < count {
+            match self.undo(memory, pc, rel, steps) {
+                Some(undone) => inputs.extend(undone.input),
+                None => break,
+            }
+        }
+
+        inputs.reverse();
+        inputs
+    }
+
+    fn current(&mut self) -> &mut Entry {
+        self.entries
+            .last_mut()
+            .expect("events are preceded by an instruction fetch")
+    }
+}
+
+impl Observer for History {
+    fn on_fetch(&mut self, pc: Address, _instruction: &Decoded) {
+        self.entries.push(Entry {
+            pc,
+            rel: None,
+            writes: self.writes.len(),
+            input: None,
+            output: None,
+        });
+    }
+
+    fn on_write(&mut self, _pc: Address, address: Address, prior: Word, _value: Word) {
+        self.writes.push((address, prior));
+    }
+
+    fn on_input(&mut self, _pc: Address, value: Word) {
+        self.current().input = Some(value);
+    }
+
+    fn on_output(&mut self, _pc: Address, value: Word) {
+        self.current().output = Some(value);
+    }
+
+    fn on_relative_base(&mut self, _pc: Address, prior: Address, _base: Address) {
+        self.current().rel = Some(prior);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::History;
+    use crate::{Address, Executable, Memory, Word};
+    use anyhow::Result;
+    use pretty_assertions::assert_eq;
+    use std::sync::mpsc::channel;
+
+    /// Outputs a running total of its inputs until it receives a zero
+    const RUNNING_TOTAL: &str = "3,20,1006,20,14,1,20,21,21,4,21,1105,1,0,99";
+
+    fn feed(exe: &mut Executable<History>, inputs: &[Word]) {
+        let (tx, rx) = channel();
+        for &i in inputs {
+            tx.send(i).unwrap();
+        }
+        exe.pipe_inputs_from(rx);
+    }
+
+    #[test]
+    fn rewind_to_previous_input() -> Result<()> {
+        crate::init_logging();
+        let memory: Memory = RUNNING_TOTAL.parse()?;
+        let mut exe = Executable::from(memory).with_observer(History::default());
+        let drain = exe.drain();
+
+        feed(&mut exe, &[5, 7]);
+        assert!(exe.run().is_err(), "inputs should run out");
+        assert_eq!(2, exe.observer().input_events());
+
+        assert_eq!(vec![7], exe.rewind_inputs(1));
+        assert_eq!(1, exe.observer().input_events());
+
+        feed(&mut exe, &[10, 0]);
+        let memory = exe.run()?;
+        assert_eq!(15, memory.read_or_default(Address::new(21)));
+
+        drop(exe);
+        assert_eq!(vec![5, 12, 15], drain.to_vec());
+
+        Ok(())
+    }
+
+    #[test]
+    fn step_back_to_start_restores_memory() -> Result<()> {
+        crate::init_logging();
+        let memory: Memory = RUNNING_TOTAL.parse()?;
+        let mut exe = Executable::from(memory.clone()).with_observer(History::default());
+        let _drain = exe.drain();
+
+        feed(&mut exe, &[3, 4, 0]);
+        exe.run()?;
+        assert_eq!(7, exe.memory_mut().read_or_default(Address::new(21)));
+
+        let mut undone = 0;
+        while exe.step_back().is_some() {
+            undone += 1;
+        }
+        assert!(undone > 0);
+        assert!(exe.observer().is_empty());
+
+        let restored = exe.memory_mut().clone();
+        assert!(restored.iter().skip(memory.size()).all(|w| w == 0));
+        assert_eq!(
+            memory.to_vec(),
+            restored.iter().take(memory.size()).collect::<Vec<_>>()
+        );
+
+        // Replaying from the start gives the same result as the first run
+        feed(&mut exe, &[3, 4, 0]);
+        exe.run()?;
+        assert_eq!(7, exe.memory_mut().read_or_default(Address::new(21)));
+
+        Ok(())
+    }
+
+    #[test]
+    fn step_back_after_restoring_an_earlier_state() -> Result<()> {
+        crate::init_logging();
+        let memory: Memory = RUNNING_TOTAL.parse()?;
+        let mut exe = Executable::from(memory).with_observer(History::default());
+        let _drain = exe.drain();
+        let start = exe.state();
+
+        feed(&mut exe, &[3, 4, 0]);
+        exe.run()?;
+        exe.restore_state(start);
+
+        // The history outlives the restored step count, which stays at zero
+        assert!(exe.step_back().is_some());
+        assert_eq!(0, exe.steps());
+
+        Ok(())
+    }
+}
@@ -0,0 +1,466 @@
+use super::{Address, Memory, Word};
+use std::{collections::BTreeMap, convert::TryFrom, io};
+
+/// Identifies a binary program image
+///
+/// Text programs only contain digits, signs, commas and whitespace, so the
+/// magic can never be mistaken for the start of one.
+pub(crate) const MAGIC: &[u8; 4] = b"ICIM";
+
+/// The version of the image format written by this crate
+pub const IMAGE_VERSION: u8 = 1;
+
+const HAS_SOURCE_HASH: u8 = 0b01;
+const HAS_SYMBOLS: u8 = 0b10;
+
+/// A program stored in the compact binary image format
+///
+/// An image is laid out as follows, with every integer after the header
+/// bytes stored as a LEB128 varint. Words are zig-zag encoded first, so that
+/// small negative values stay small.
+///
+/// ```text
+/// magic         "ICIM"
+/// version       u8
+/// flags         u8
+/// entry         varint
+/// source hash   u64, little endian, if flagged
+/// symbols       varint count, then (address varint, length varint, UTF-8)
+///               for each, if flagged
+/// length        varint
+/// words         zig-zag varint for each
+/// ```
+///
+/// ## Example
+///
+/// ```
+/// use intcode::{Address, Image, Memory};
+///
+/// let memory: Memory = "1,1,1,4,99,5,6,0,99".parse().expect("valid data");
+/// let image = Image::new(memory.clone()).with_symbol(Address::new(4), "halt");
+///
+/// let mut buf = Vec::new();
+/// image.write_to(&mut buf).expect("write to a vector");
+///
+/// let loaded = Image::read_from(&mut &buf[..]).expect("valid image");
+/// assert_eq!(Some("halt"), loaded.symbol(Address::new(4)));
+/// assert_eq!(memory, Memory::from_reader(&mut &buf[..]).expect("valid image"));
+/// ```
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Image {
+    memory: Memory,
+    entry: Address,
+    source_hash: Option<u64>,
+    symbols: BTreeMap<Address, String>,
+}
+
+impl From<Memory> for Image {
+    fn from(memory: Memory) -> Self {
+        Self::new(memory)
+    }
+}
+
+impl Image {
+    /// Wraps a program with no metadata, entered at address `0`
+    pub fn new(memory: Memory) -> Self {
+        Self {
+            memory,
+            entry: Address::ZERO,
+            source_hash: None,
+            symbols: BTreeMap::new(),
+        }
+    }
+
+    /// Sets the address at which execution should begin
+    ///
+    /// Executables created from the image start here, and return here when
+    /// reset.
+    pub fn with_entry(mut self, entry: Address) -> Self {
+        self.entry = entry;
+        self
+    }
+
+    /// Records a hash of the source the program was built from
+    ///
+    /// See `hash_source` for a suitable hash of source text.
+    pub fn with_source_hash(mut self, hash: u64) -> Self {
+        self.source_hash = Some(hash);
+        self
+    }
+
+    /// Names an address, replacing any existing name
+    pub fn with_symbol(mut self, address: Address, name: impl Into<String>) -> Self {
+        self.symbols.insert(address, name.into());
+        self
+    }
+
+    /// The program
+    pub fn memory(&self) -> &Memory {
+        &self.memory
+    }
+
+    /// The program, for patching before it runs
+    pub fn memory_mut(&mut self) -> &mut Memory {
+        &mut self.memory
+    }
+
+    /// Extracts the program, discarding metadata
+    pub fn into_memory(self) -> Memory {
+        self.memory
+    }
+
+    /// The address at which execution should begin
+    pub fn entry(&self) -> Address {
+        self.entry
+    }
+
+    /// The hash of the source the program was built from, if recorded
+    pub fn source_hash(&self) -> Option<u64> {
+        self.source_hash
+    }
+
+    /// The name of an address, if it has one
+    pub fn symbol(&self, address: Address) -> Option<&str> {
+        self.symbols.get(&address).map(String::as_str)
+    }
+
+    /// Iterates over named addresses, in address order
+    pub fn symbols(&self) -> impl Iterator<Item = (Address, &str)> + '_ {
+        self.symbols.iter().map(|(a, n)| (*a, n.as_str()))
+    }
+
+    /// Hashes source text with 64-bit FNV-1a
+    ///
+    /// The hash is stable across platforms and releases, so it may be
+    /// compared against a hash recorded in an image to detect stale builds.
+    pub fn hash_source(source: &str) -> u64 {
+        source.bytes().fold(0xcbf2_9ce4_8422_2325, |hash, b| {
+            (hash ^ u64::from(b)).wrapping_mul(0x0100_0000_01b3)
+        })
+    }
+
+    /// Writes the image in the binary format
+    pub fn write_to(&self, output: &mut dyn io::Write) -> io::Result<()> {
+        let mut flags = 0;
+        if self.source_hash.is_some() {
+            flags |= HAS_SOURCE_HASH;
+        }
+        if !self.symbols.is_empty() {
+            flags |= HAS_SYMBOLS;
+        }
+
+        let mut buf = Vec::with_capacity(16 + self.memory.size() * 2);
+        buf.extend_from_slice(MAGIC);
+        buf.push(IMAGE_VERSION);
+        buf.push(flags);
+        write_varint(&mut buf, self.entry.value() as u64);
+        if let Some(hash) = self.source_hash {
+            buf.extend_from_slice(&hash.to_le_bytes());
+        }
+        if !self.symbols.is_empty() {
+            write_varint(&mut buf, self.symbols.len() as u64);
+            for (address, name) in &self.symbols {
+                write_varint(&mut buf, address.value() as u64);
+                write_varint(&mut buf, name.len() as u64);
+                buf.extend_from_slice(name.as_bytes());
+            }
+        }
+
+        write_varint(&mut buf, self.memory.size() as u64);
+        for w in self.memory.iter() {
+            write_varint(&mut buf, zigzag(w));
+        }
+
+        output.write_all(&buf)
+    }
+
+    /// Loads a program in either the binary or the text format
+    ///
+    /// A text program has no metadata, so it is entered at address `0`.
+    pub fn load(input: &mut dyn io::BufRead) -> io::Result<Self> {
+        // A reader may return fewer bytes than asked for, so keep reading
+        // until the magic is complete or the input ends
+        let mut magic = [0; 4];
+        let mut read = 0;
+        while read < magic.len() {
+            match input.read(&mut magic[read..]) {
+                Ok(0) => break,
+                Ok(n) => read += n,
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+                Err(e) => return Err(e),
+            }
+        }
+
+        let mut input = io::Read::chain(&magic[..read], input);
+        if &magic[..read] == MAGIC {
+            Self::read_from(&mut input)
+        } else {
+            Memory::from_text(&mut input).map(Self::new)
+        }
+    }
+
+    /// Reads an image in the binary format
+    ///
+    /// Images written by a newer version of the format are rejected.
+    pub fn read_from(input: &mut dyn io::Read) -> io::Result<Self> {
+        let mut header = [0; 6];
+        input.read_exact(&mut header)?;
+        if &header[..4] != MAGIC {
+            return Err(invalid("not a program image"));
+        }
+        let version = header[4];
+        if version != IMAGE_VERSION {
+            return Err(invalid(format!("unsupported image version {}", version)));
+        }
+        let flags = header[5];
+        if flags & !(HAS_SOURCE_HASH | HAS_SYMBOLS) != 0 {
+            return Err(invalid(format!("unknown image flags {:#04x}", flags)));
+        }
+
+        let mut data = Vec::new();
+        input.read_to_end(&mut data)?;
+        let mut reader = Reader { data: &data };
+
+        let entry = reader.address()?;
+        let source_hash = if flags & HAS_SOURCE_HASH != 0 {
+            let mut bytes = [0; 8];
+            bytes.copy_from_slice(reader.bytes(8)?);
+            Some(u64::from_le_bytes(bytes))
+        } else {
+            None
+        };
+
+        let mut symbols = BTreeMap::new();
+        if flags & HAS_SYMBOLS != 0 {
+            for _ in 0..reader.varint()? {
+                let address = reader.address()?;
+                let len = reader.len()?;
+                let name = std::str::from_utf8(reader.bytes(len)?)
+                    .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+                symbols.insert(address, name.to_string());
+            }
+        }
+
+        let len = reader.len()?;
+        // Every word takes at least one byte, which bounds the allocation
+        // made for a corrupt length
+        let mut words = Vec::with_capacity(len.min(reader.data.len()));
+        for _ in 0..len {
+            words.push(unzigzag(reader.varint()?));
+        }
+        if !reader.data.is_empty() {
+            return Err(invalid("trailing data after program"));
+        }
+
+        Ok(Self {
+            memory: Memory::from_vec(words),
+            entry,
+            source_hash,
+            symbols,
+        })
+    }
+}
+
+fn invalid(msg: impl Into<String>) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg.into())
+}
+
+fn zigzag(w: Word) -> u64 {
+    ((w << 1) ^ (w >> 63)) as u64
+}
+
+fn unzigzag(v: u64) -> Word {
+    ((v >> 1) as Word) ^ -((v & 1) as Word)
+}
+
+fn write_varint(buf: &mut Vec<u8>, mut v: u64) {
+    while v >= 0x80 {
+        buf.push(v as u8 | 0x80);
+        v >>= 7;
+    }
+    buf.push(v as u8);
+}
+
+struct Reader<'a> {
+    data: &'a [u8],
+}
+
+impl<'a> Reader<'a> {
+    fn bytes(&mut self, len: usize) -> io::Result<&'a [u8]> {
+        if self.data.len() < len {
+            return Err(io::Error::new(
+                io::ErrorKind::UnexpectedEof,
+                "truncated program image",
+            ));
+        }
+        let (bytes, rest) = self.data.split_at(len);
+        self.data = rest;
+        Ok(bytes)
+    }
+
+    fn varint(&mut self) -> io::Result<u64> {
+        let mut v = 0;
+        for shift in (0..64).step_by(7) {
+            let b = self.bytes(1)?[0];
+            // Only the lowest bit of the tenth byte fits in 64 bits
+            if shift == 63 && b > 1 {
+                return Err(invalid("varint overflows 64 bits"));
+            }
+            v |= u64::from(b & 0x7f) << shift;
+            if b & 0x80 == 0 {
+                return Ok(v);
+            }
+        }
+        Err(invalid("varint is too long"))
+    }
+
+    fn len(&mut self) -> io::Result<usize> {
+        usize::try_from(self.varint()?).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+    }
+
+    fn address(&mut self) -> io::Result<Address> {
+        self.len().map(Address::new)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{unzigzag, zigzag, Image};
+    use crate::{Address, Executable, Memory, Word};
+    use anyhow::Result;
+    use pretty_assertions::assert_eq;
+    use std::io;
+
+    #[test]
+    fn zigzag_round_trips() {
+        for &w in &[0, 1, -1, 63, -64, 1 << 40, Word::MAX, Word::MIN] {
+            assert_eq!(w, unzigzag(zigzag(w)), "{}", w);
+        }
+        assert_eq!(1, zigzag(-1));
+        assert_eq!(2, zigzag(1));
+    }
+
+    #[test]
+    fn round_trips_with_metadata() -> Result<()> {
+        crate::init_logging();
+        let source = "1,9,10,3,2,3,11,0,99,30,40,-50";
+        let memory: Memory = source.parse()?;
+        let image = Image::new(memory)
+            .with_entry(Address::new(4))
+            .with_source_hash(Image::hash_source(source))
+            .with_symbol(Address::ZERO, "main")
+            .with_symbol(Address::new(9), "data");
+
+        let mut buf = Vec::new();
+        image.write_to(&mut buf)?;
+
+        let loaded = Image::read_from(&mut &buf[..])?;
+        assert_eq!(image, loaded);
+        assert_eq!(
+            vec![(Address::ZERO, "main"), (Address::new(9), "data")],
+            loaded.symbols().collect::<Vec<_>>()
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn rejects_corrupt_images() -> Result<()> {
+        crate::init_logging();
+        let mut buf = Vec::new();
+        Image::new(Memory::from_vec(vec![1000, -1000])).write_to(&mut buf)?;
+
+        let truncated = Image::read_from(&mut &buf[..buf.len() - 1]).unwrap_err();
+        assert_eq!(io::ErrorKind::UnexpectedEof, truncated.kind());
+
+        let mut newer = buf.clone();
+        newer[4] += 1;
+        let newer = Image::read_from(&mut &newer[..]).unwrap_err();
+        assert_eq!(io::ErrorKind::InvalidData, newer.kind());
+
+        buf.push(0);
+        let trailing = Image::read_from(&mut &buf[..]).unwrap_err();
+        assert_eq!(io::ErrorKind::InvalidData, trailing.kind());
+
+        // The most negative word takes all ten bytes of a varint
+        let mut widest = Vec::new();
+        Image::new(Memory::from_vec(vec![Word::MIN])).write_to(&mut widest)?;
+        let last = widest.len() - 1;
+        assert_eq!(1, widest[last]);
+        for &corrupt in &[0x02, 0x81] {
+            widest[last] = corrupt;
+            let overflow = Image::read_from(&mut &widest[..]).unwrap_err();
+            assert_eq!(io::ErrorKind::InvalidData, overflow.kind());
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn loaders_detect_format() -> Result<()> {
+        crate::init_logging();
+        let memory = Memory::from_vec(vec![104, -7, 99]);
+        let mut buf = Vec::new();
+        memory.to_writer(&mut buf)?;
+
+        assert_eq!(memory, Memory::from_reader(&mut &buf[..])?);
+        assert_eq!(memory, Memory::from_buf_reader(&mut &buf[..])?);
+        assert_eq!(memory, Memory::from_image(&mut &buf[..])?);
+        assert_eq!(memory, Memory::from_reader(&mut "104,-7,99".as_bytes())?);
+
+        Ok(())
+    }
+
+    #[test]
+    fn loaders_detect_format_from_short_reads() -> Result<()> {
+        crate::init_logging();
+        let memory = Memory::from_vec(vec![104, -7, 99]);
+        let mut buf = Vec::new();
+        memory.to_writer(&mut buf)?;
+
+        // Each read returns a single byte
+        let mut image = io::BufReader::with_capacity(1, &buf[..]);
+        assert_eq!(memory, Memory::from_buf_reader(&mut image)?);
+        let mut text = io::BufReader::with_capacity(1, "104,-7,99".as_bytes());
+        assert_eq!(memory, Memory::from_buf_reader(&mut text)?);
+
+        Ok(())
+    }
+
+    #[test]
+    fn executables_start_at_entry() -> Result<()> {
+        crate::init_logging();
+        // Outputs 1 and 2 from the start, or just 2 from the entry point
+        let memory = Memory::from_vec(vec![104, 1, 104, 2, 99]);
+        let mut buf = Vec::new();
+        Image::new(memory)
+            .with_entry(Address::new(2))
+            .write_to(&mut buf)?;
+
+        let image = Image::load(&mut &buf[..])?;
+        let mut exe = Executable::from(image);
+        let drain = exe.drain();
+        exe.run()?;
+        exe.reset();
+        exe.run()?;
+        drop(exe);
+
+        assert_eq!(vec![2, 2], drain.to_vec());
+
+        Ok(())
+    }
+
+    #[test]
+    fn images_are_smaller_than_text() -> Result<()> {
+        crate::init_logging();
+        let source = include_str!("../../inputs/input-09");
+        let memory: Memory = source.parse()?;
+        let mut buf = Vec::new();
+        memory.to_writer(&mut buf)?;
+
+        assert!(buf.len() * 2 < source.len(), "{} bytes", buf.len());
+        assert_eq!(memory, Memory::from_image(&mut &buf[..])?);
+
+        Ok(())
+    }
+}
@@ -6,33 +6,88 @@
 //! use intcode::{Address, Executable, Memory};
 //!
 //! const PROGRAM_DATA: &str = "1,1,1,4,99,5,6,0,99";
-//! let memory = Memory::from_str(PROGRAM_DATA).expect("valid data");
+//! let memory: Memory = PROGRAM_DATA.parse().expect("valid data");
 //!
-//! let mut exe = Executable::from(memory);
+//! let exe = Executable::from(memory);
 //!
 //! let result = exe.execute().expect("successful execution");
 //!
 //! assert_eq!(30, result.read_or_default(Address::new(0)));
 //! ```
+//!
+//! ## Features
+//!
+//! `AsyncExecutable` and its pipes are built on `futures` alone, so they run
+//! on any executor. The default `tokio-runtime` feature adds conveniences which need
+//! a Tokio runtime: `AsyncOutputDrain::into_vec`, watched inputs, the
+//! `AsciiTerminal` and `AsciiSession`. The default `cli` feature builds the
+//! `intcode` binary along with the dependencies only it needs.
 
 mod address;
 mod async_execute;
+mod batch;
 mod buffer;
+mod cancel;
+mod compile;
+mod coverage;
+mod debug;
 mod decode;
+mod diff;
+mod differential;
+mod emit;
 mod error;
 mod execute;
+#[cfg(feature = "tokio-runtime")]
+mod expect;
+mod history;
+mod image;
+mod link;
 mod memory;
+mod modification;
+mod observer;
 mod ops;
+mod optimize;
+mod recording;
+mod runtime;
+mod state;
+mod symbolic;
+#[cfg(feature = "tokio-runtime")]
 mod terminal;
+mod trace;
 
-pub use address::Address;
-use address::Relative;
-pub use async_execute::AsyncExecutable;
+pub use address::{Address, Relative};
+pub use async_execute::{AsyncExecutable, AsyncOutputDrain, Termination};
+pub use batch::{Batch, Evaluation};
 pub use buffer::Buffer;
+pub use cancel::CancelHandle;
+pub use compile::{compile, CompileError, Compiled};
+pub use coverage::{Annotated, Branch, Coverage, Summary};
+pub use debug::{DebugInfo, Location, Symbol};
+pub use decode::{
+    BinaryOperands, Decoded, InputOperands, JumpIfOperands, Output, OutputOperands, Parameter,
+};
+pub use diff::{Change, Diff, PatchError};
+pub use differential::{
+    AsyncBackend, Backend, Differential, Ending, Mismatch, Outcome, SyncBackend,
+};
 use execute::ProgramCounter;
-pub use execute::{Executable, ExecutionError};
+pub use execute::{ErrorKind, Executable, ExecutionError};
+#[cfg(feature = "tokio-runtime")]
+pub use expect::{AsciiSession, ExpectError, Finished, Match};
+pub use history::{History, Undone};
+pub use image::{Image, IMAGE_VERSION};
+pub use link::{LinkError, Linked, Linker, Module};
 pub use memory::Memory;
-pub use terminal::{AsciiTerminal, TerminalOut};
+pub use modification::{Finding, Modification, SelfModification};
+pub use observer::Observer;
+pub use optimize::{optimize, NotStatic, Optimized, OptimizedBackend, Report, StepSavings};
+pub use recording::{EventKind, RecordedEvent, Recording, TranscriptDiff};
+pub use runtime::{heap_start, runtime_library, STACK_SIZE};
+pub use state::MachineState;
+pub use symbolic::{Constraint, End, Exploration, Linear, Path, Symbolic, Value, Var};
+#[cfg(feature = "tokio-runtime")]
+pub use terminal::{AsciiTerminal, NonAsciiPolicy, TerminalExit, TerminalOut};
+pub use trace::{replay, Divergence, SymbolizedTrace, Trace, TraceRecord};
 
 /// The quantum of data in Intcode memory
 pub type Word = i64;
@@ -301,6 +356,95 @@ mod tests {
         run_program_test(QUINE, 0, EXPECTED)
     }
 
+    #[test]
+    fn reset_allows_reuse() -> Result<()> {
+        crate::init_logging();
+        let memory: Memory = PUZ_5_PART_2_EXAMPLE.parse()?;
+
+        let mut exe = Executable::from(memory);
+
+        for &(input, expected) in &[(-2, 999), (8, 1000), (99, 1001)] {
+            exe.reset();
+            exe.single_input(input);
+            let drain = exe.drain();
+
+            exe.run()?;
+
+            drop(exe.drain());
+            assert_eq!(vec![expected], drain.to_vec());
+        }
+
+        Ok(())
+    }
+
+    #[derive(Default)]
+    struct Recorder {
+        fetched: Vec<super::Address>,
+        writes: Vec<(super::Address, Word, Word)>,
+        inputs: Vec<Word>,
+        outputs: Vec<Word>,
+        jumps: Vec<(super::Address, super::Address, bool)>,
+    }
+
+    impl super::Observer for Recorder {
+        fn on_fetch(&mut self, pc: super::Address, _: &super::Decoded) {
+            self.fetched.push(pc);
+        }
+
+        fn on_write(
+            &mut self,
+            _: super::Address,
+            address: super::Address,
+            prior: Word,
+            value: Word,
+        ) {
+            self.writes.push((address, prior, value));
+        }
+
+        fn on_input(&mut self, _: super::Address, value: Word) {
+            self.inputs.push(value);
+        }
+
+        fn on_output(&mut self, _: super::Address, value: Word) {
+            self.outputs.push(value);
+        }
+
+        fn on_jump(&mut self, pc: super::Address, target: super::Address, taken: bool) {
+            self.jumps.push((pc, target, taken));
+        }
+    }
+
+    #[test]
+    fn observer_sees_execution_events() -> Result<()> {
+        use super::Address;
+        crate::init_logging();
+        let memory: Memory = IMM_JUMP_INPUT_WAS_ZERO.parse()?;
+
+        let mut exe = Executable::from(memory).with_observer(Recorder::default());
+        exe.single_input(0);
+        let drain = exe.drain();
+
+        exe.run()?;
+        let recorder = exe.into_observer();
+
+        let addrs = |a: &[usize]| a.iter().copied().map(Address::new).collect::<Vec<_>>();
+        assert_eq!(addrs(&[0, 2, 5, 9, 11]), recorder.fetched);
+        assert_eq!(
+            vec![(Address::new(3), -1, 0), (Address::new(12), 1, 0)],
+            recorder.writes
+        );
+        assert_eq!(vec![0], recorder.inputs);
+        assert_eq!(vec![0], recorder.outputs);
+        assert_eq!(
+            vec![(Address::new(2), Address::new(9), false)],
+            recorder.jumps
+        );
+        assert_eq!(vec![0], drain.to_vec());
+
+        Ok(())
+    }
+
+    #[cfg(feature = "tokio-runtime")]
     async fn run_program_test_async(
         program_data: &str,
         input: Word,
@@ -321,6 +465,7 @@ mod tests {
         Ok(())
     }
 
+    #[cfg(feature = "tokio-runtime")]
     #[tokio::test]
     async fn quine_async() -> Result<()> {
         const QUINE: &str = "109,1,204,-1,1001,100,1,100,1008,100,16,101,1006,101,0,99";
@@ -329,4 +474,177 @@ mod tests {
         ];
         run_program_test_async(QUINE, 0, EXPECTED).await
     }
+
+    #[test]
+    fn async_runs_without_a_tokio_runtime() -> Result<()> {
+        use futures::{executor::block_on, SinkExt, StreamExt};
+        crate::init_logging();
+        const ECHO: &str = "3,5,4,5,99,0";
+        const ADD_TWO: &str = "3,20,3,21,1,20,21,22,4,22,99";
+
+        let mut first = super::AsyncExecutable::from(ECHO.parse::<Memory>()?);
+        let mut second = super::AsyncExecutable::from(ADD_TWO.parse::<Memory>()?);
+        first.single_input(3);
+        let mut injector = first.pipe_to(&mut second);
+        let drain = second.drain();
+
+        let (a, b, injected, outputs) = block_on(async {
+            futures::join!(
+                first.execute(),
+                second.execute(),
+                injector.send(5),
+                drain.collect::<Vec<_>>()
+            )
+        });
+        a?;
+        b?;
+        injected?;
+        assert_eq!(vec![8], outputs);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn stream_runs_only_as_far_as_polled() -> Result<()> {
+        use futures::stream::{StreamExt, TryStreamExt};
+        crate::init_logging();
+        const COUNT_UP_FOREVER: &str = "101,1,9,9,4,9,1105,1,0,0";
+        let memory: Memory = COUNT_UP_FOREVER.parse()?;
+        let mut coverage = super::Coverage::default();
+
+        let exe = super::AsyncExecutable::from(memory).with_observer(&mut coverage);
+        let outputs: Vec<Word> = exe.into_stream().take(3).try_collect().await?;
+
+        assert_eq!(vec![1, 2, 3], outputs);
+        // Execution stops at the third output, before jumping back again
+        assert_eq!(3, coverage.hits(super::Address::new(4)));
+        assert_eq!(2, coverage.hits(super::Address::new(6)));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn stream_ends_with_the_error() -> Result<()> {
+        use futures::{sink::SinkExt, stream::StreamExt};
+        crate::init_logging();
+        let memory: Memory = "3,5,4,5,98,0".parse()?;
+
+        let mut exe = super::AsyncExecutable::from(memory);
+        let (mut tx, rx) = futures::channel::mpsc::channel(1);
+        exe.pipe_inputs_from(rx);
+        tx.send(7).await?;
+        let results: Vec<_> = exe.into_stream().collect().await;
+
+        assert_eq!(2, results.len());
+        assert_eq!(&7, results[0].as_ref().expect("first output"));
+        let error = results[1].as_ref().expect_err("invalid instruction");
+        assert_eq!(super::ErrorKind::InvalidInstruction, error.kind());
+
+        Ok(())
+    }
+
+    const ECHO_FOREVER: &str = "3,7,4,7,1105,1,0,0";
+
+    #[tokio::test]
+    async fn cancel_while_awaiting_input() -> Result<()> {
+        use futures::{SinkExt, StreamExt};
+        crate::init_logging();
+        let memory: Memory = ECHO_FOREVER.parse()?;
+
+        let mut exe = super::AsyncExecutable::from(memory);
+        let (mut tx, rx) = futures::channel::mpsc::channel(1);
+        let (otx, mut orx) = futures::channel::mpsc::channel(1);
+        exe.pipe_inputs_from(rx);
+        exe.pipe_outputs_to(otx);
+        let cancel = exe.cancel_handle();
+
+        let join = tokio::spawn(exe.execute_cancellable());
+
+        tx.send(5).await?;
+        assert_eq!(Some(5), orx.next().await);
+        cancel.cancel();
+
+        let mut exe = match join.await?? {
+            super::Termination::Cancelled(exe) => exe,
+            super::Termination::Halted(_) => panic!("echo program should not halt"),
+        };
+
+        // The pending input instruction is retried on resumption
+        let cancel = exe.cancel_handle();
+        let join = tokio::spawn(exe.execute_cancellable());
+        tx.send(7).await?;
+        assert_eq!(Some(7), orx.next().await);
+        cancel.cancel();
+
+        assert!(matches!(join.await??, super::Termination::Cancelled(_)));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn cancel_while_awaiting_output() -> Result<()> {
+        use futures::StreamExt;
+        crate::init_logging();
+        // Counts upwards from 1 forever
+        let memory: Memory = "101,1,9,9,4,9,1105,1,0,0".parse()?;
+
+        let mut exe = super::AsyncExecutable::from(memory);
+        let (otx, mut orx) = futures::channel::mpsc::channel(1);
+        exe.pipe_outputs_to(otx);
+        let cancel = exe.cancel_handle();
+
+        // Nothing is read, so the executable is blocked on output by the time
+        // it is cancelled
+        let (result, ()) = futures::join!(exe.execute_cancellable(), async { cancel.cancel() });
+        let mut exe = match result? {
+            super::Termination::Cancelled(exe) => exe,
+            super::Termination::Halted(_) => panic!("counter program should not halt"),
+        };
+
+        // The pending output instruction is retried on resumption, without
+        // having sent its value already
+        let cancel = exe.cancel_handle();
+        let join = tokio::spawn(exe.execute_cancellable());
+        let outputs: Vec<Word> = orx.by_ref().take(4).collect().await;
+        assert_eq!(vec![1, 2, 3, 4], outputs);
+        cancel.cancel();
+
+        assert!(matches!(join.await??, super::Termination::Cancelled(_)));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn cancel_compute_bound_loop() -> Result<()> {
+        crate::init_logging();
+        let memory: Memory = "1105,1,0".parse()?;
+
+        let mut exe = super::AsyncExecutable::from(memory);
+        let cancel = exe.cancel_handle();
+
+        // The loop never awaits I/O, so it must yield for the canceller to run
+        let join = tokio::spawn(exe.execute_cancellable());
+        tokio::spawn(async move { cancel.cancel() });
+
+        assert!(matches!(join.await??, super::Termination::Cancelled(_)));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn cancelled_execute_is_distinguishable() -> Result<()> {
+        crate::init_logging();
+        let memory: Memory = "1105,1,0".parse()?;
+
+        let mut exe = super::AsyncExecutable::from(memory);
+        exe.cancel_handle().cancel();
+
+        let err = exe
+            .execute()
+            .await
+            .expect_err("execution should be cancelled");
+        assert!(err.is_cancelled());
+
+        Ok(())
+    }
 }
@@ -0,0 +1,421 @@
+use super::{
+    ops::{Instruction, OpCode, ParameterMode},
+    Address, DebugInfo, Image, Memory, Word,
+};
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    convert::TryFrom,
+    ops::Range,
+};
+use thiserror::Error;
+
+/// A reason modules could not be linked
+#[derive(Error, Clone, Debug, PartialEq, Eq)]
+pub enum LinkError {
+    #[error("module `{name}` is linked twice")]
+    DuplicateModule { name: String },
+    #[error("symbol `{name}` is exported by both `{first}` and `{second}`")]
+    DuplicateExport {
+        name: String,
+        first: String,
+        second: String,
+    },
+    #[error("module `{module}` imports undefined symbol `{name}`")]
+    Undefined { module: String, name: String },
+    #[error("module `{module}` refers to offset {offset}, beyond its end")]
+    OutOfRange { module: String, offset: usize },
+    #[error("module `{module}` has no valid instruction at offset {offset}")]
+    Undecodable { module: String, offset: usize },
+}
+
+/// A piece of a program which may be placed anywhere in memory
+///
+/// A module is written as though it starts at address `0`. Relocation
+/// records mark the words holding addresses within the module, which are
+/// rebased when the module is placed. Imports mark words which hold the
+/// address of a symbol exported by another module, plus the word's own
+/// value as an offset from it. An import replaces any relocation of the same
+/// word.
+///
+/// ## Example
+///
+/// ```
+/// use intcode::{Linker, Memory, Module};
+///
+/// // Outputs the word at `value`, which lives in another module
+/// let main = Module::new("main", "4,0,99".parse().expect("valid data")).with_import(1, "value");
+/// let data = Module::new("data", "42".parse().expect("valid data")).with_export("value", 0);
+///
+/// let linked = Linker::default()
+///     .with_module(main)
+///     .with_module(data)
+///     .link()
+///     .expect("all symbols are defined");
+/// assert_eq!("4,3,99,42".parse::<Memory>().expect("valid data"), *linked.memory());
+/// ```
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Module {
+    name: String,
+    words: Vec<Word>,
+    relocations: BTreeSet<usize>,
+    exports: BTreeMap<String, usize>,
+    imports: BTreeMap<usize, String>,
+}
+
+impl Module {
+    /// Wraps a program with no relocations, imports or exports
+    pub fn new(name: impl Into<String>, memory: Memory) -> Self {
+        Self {
+            name: name.into(),
+            words: memory.to_vec(),
+            relocations: BTreeSet::new(),
+            exports: BTreeMap::new(),
+            imports: BTreeMap::new(),
+        }
+    }
+
+    /// Marks the word at `offset` as holding an address within the module
+    pub fn with_relocation(mut self, offset: usize) -> Self {
+        self.relocations.insert(offset);
+        self
+    }
+
+    /// Marks the operands of every instruction in `code` which need
+    /// relocating
+    ///
+    /// The range must hold only instructions, laid out back to back.
+    /// Position mode operands are relocated, as are immediate jump targets.
+    /// Other immediate operands and relative mode operands are left alone,
+    /// so any which hold addresses must be marked with `with_relocation`.
+    pub fn with_code(mut self, code: Range<usize>) -> Result<Self, LinkError> {
+        let name = &self.name;
+        let mut offset = code.start;
+        while offset < code.end {
+            let undecodable = || LinkError::Undecodable {
+                module: name.clone(),
+                offset,
+            };
+            let op = *self.words.get(offset).ok_or_else(undecodable)?;
+            let instruction = Instruction::try_from(op).map_err(|_| undecodable())?;
+            let opcode = instruction.opcode();
+            let modes = instruction.param_modes();
+            let is_jump = matches!(opcode, OpCode::JumpNonZero | OpCode::JumpZero);
+            for i in 0..opcode.params() {
+                let relocated = match modes.mode(i) {
+                    ParameterMode::Position => true,
+                    ParameterMode::Immediate => is_jump && i == 1,
+                    ParameterMode::Relative => false,
+                };
+                if relocated {
+                    self.relocations.insert(offset + 1 + usize::from(i));
+                }
+            }
+            offset += 1 + usize::from(opcode.params());
+        }
+        Ok(self)
+    }
+
+    /// Exports the address at `offset` under `name`, for other modules to
+    /// import
+    pub fn with_export(mut self, name: impl Into<String>, offset: usize) -> Self {
+        self.exports.insert(name.into(), offset);
+        self
+    }
+
+    /// Fills the word at `offset` with the address of `name`, plus its
+    /// current value
+    pub fn with_import(mut self, offset: usize, name: impl Into<String>) -> Self {
+        self.imports.insert(offset, name.into());
+        self
+    }
+
+    /// The name of the module
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// The number of words in the module
+    pub fn size(&self) -> usize {
+        self.words.len()
+    }
+
+    fn check_offsets(&self) -> Result<(), LinkError> {
+        let len = self.words.len();
+        let beyond = self
+            .relocations
+            .iter()
+            .chain(self.imports.keys())
+            .copied()
+            .find(|&o| o >= len)
+            .or_else(|| self.exports.values().copied().find(|&o| o > len));
+        match beyond {
+            Some(offset) => Err(LinkError::OutOfRange {
+                module: self.name.clone(),
+                offset,
+            }),
+            None => Ok(()),
+        }
+    }
+}
+
+/// Combines modules into a single program
+///
+/// Modules are placed one after another, in the order they were added, so
+/// the first module is the one which starts executing.
+#[derive(Clone, Debug, Default)]
+pub struct Linker {
+    modules: Vec<Module>,
+}
+
+impl Linker {
+    /// Adds a module after those already added
+    pub fn with_module(mut self, module: Module) -> Self {
+        self.modules.push(module);
+        self
+    }
+
+    /// Places every module, rebases relocated words and resolves imports
+    pub fn link(&self) -> Result<Linked, LinkError> {
+        let mut bases = BTreeMap::new();
+        let mut placed = Vec::with_capacity(self.modules.len());
+        let mut base = 0;
+        for module in &self.modules {
+            module.check_offsets()?;
+            if bases.insert(module.name.as_str(), base).is_some() {
+                return Err(LinkError::DuplicateModule {
+                    name: module.name.clone(),
+                });
+            }
+            placed.push((
+                module.name.clone(),
+                Address::new(base)..Address::new(base + module.size()),
+            ));
+            base += module.size();
+        }
+
+        let mut symbols = BTreeMap::new();
+        let mut owners = BTreeMap::new();
+        for module in &self.modules {
+            for (name, offset) in &module.exports {
+                if let Some(first) = owners.insert(name.as_str(), module.name.as_str()) {
+                    return Err(LinkError::DuplicateExport {
+                        name: name.clone(),
+                        first: first.to_string(),
+                        second: module.name.clone(),
+                    });
+                }
+                let address = Address::new(bases[module.name.as_str()] + offset);
+                symbols.insert(name.clone(), address);
+            }
+        }
+
+        let mut words = Vec::with_capacity(base);
+        for module in &self.modules {
+            let base = bases[module.name.as_str()];
+            let start = words.len();
+            words.extend_from_slice(&module.words);
+            for &offset in &module.relocations {
+                if !module.imports.contains_key(&offset) {
+                    words[start + offset] += base as Word;
+                }
+            }
+            for (&offset, name) in &module.imports {
+                let address = symbols.get(name).ok_or_else(|| LinkError::Undefined {
+                    module: module.name.clone(),
+                    name: name.clone(),
+                })?;
+                words[start + offset] += address.value() as Word;
+            }
+        }
+
+        Ok(Linked {
+            memory: Memory::from_vec(words),
+            symbols,
+            modules: placed,
+        })
+    }
+}
+
+/// A program produced by linking modules
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Linked {
+    memory: Memory,
+    symbols: BTreeMap<String, Address>,
+    modules: Vec<(String, Range<Address>)>,
+}
+
+impl Linked {
+    /// The program
+    pub fn memory(&self) -> &Memory {
+        &self.memory
+    }
+
+    /// Extracts the program, discarding symbols
+    pub fn into_memory(self) -> Memory {
+        self.memory
+    }
+
+    /// The address of an exported symbol
+    pub fn symbol(&self, name: &str) -> Option<Address> {
+        self.symbols.get(name).copied()
+    }
+
+    /// The addresses a module was placed at
+    pub fn module(&self, name: &str) -> Option<Range<Address>> {
+        self.modules
+            .iter()
+            .find(|(n, _)| n == name)
+            .map(|(_, range)| range.clone())
+    }
+
+    /// Labels every exported symbol, and the start of every module without
+    /// a symbol of its own there
+    pub fn debug_info(&self) -> DebugInfo {
+        let info = self
+            .modules
+            .iter()
+            .fold(DebugInfo::default(), |info, (name, range)| {
+                info.with_label(range.start, name.as_str())
+            });
+        self.symbols.iter().fold(info, |info, (name, address)| {
+            info.with_label(*address, name.as_str())
+        })
+    }
+
+    /// Packages the program as an image, with the same labels as
+    /// `debug_info` as symbols
+    pub fn image(&self) -> Image {
+        self.debug_info()
+            .labels()
+            .fold(Image::new(self.memory.clone()), |image, (address, name)| {
+                image.with_symbol(address, name)
+            })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{LinkError, Linker, Module};
+    use crate::{Address, Executable, Memory};
+    use anyhow::Result;
+    use pretty_assertions::assert_eq;
+
+    /// Triples `arg` into `result`, then jumps to the address held in `ret`
+    fn library() -> Result<Module> {
+        Ok(Module::new("lib", "1002,7,3,8,106,0,9,0,0,0".parse()?)
+            .with_code(0..7)?
+            .with_export("triple", 0)
+            .with_export("arg", 7)
+            .with_export("result", 8)
+            .with_export("ret", 9))
+    }
+
+    /// Reads a value, calls `triple` with it and outputs the result
+    fn program() -> Result<Module> {
+        Ok(
+            Module::new("main", "3,0,1101,9,0,0,1105,1,0,4,0,99".parse()?)
+                .with_code(0..12)?
+                .with_relocation(3)
+                .with_import(1, "arg")
+                .with_import(5, "ret")
+                .with_import(8, "triple")
+                .with_import(10, "result"),
+        )
+    }
+
+    #[test]
+    fn rebases_and_resolves_modules() -> Result<()> {
+        crate::init_logging();
+        let linked = Linker::default()
+            .with_module(program()?)
+            .with_module(library()?)
+            .link()?;
+
+        let expected: Memory = "3,19,1101,9,0,21,1105,1,12,4,20,99,\
+                                1002,19,3,20,106,0,21,0,0,0"
+            .parse()?;
+        assert_eq!(expected, *linked.memory());
+        assert_eq!(Some(Address::new(12)), linked.symbol("triple"));
+        assert_eq!(
+            Some(Address::new(12)..Address::new(22)),
+            linked.module("lib")
+        );
+
+        let mut exe = Executable::from(linked.memory().clone());
+        exe.single_input(14);
+        let drain = exe.drain();
+        exe.run()?;
+        drop(exe);
+        assert_eq!(vec![42], drain.to_vec());
+
+        Ok(())
+    }
+
+    #[test]
+    fn places_libraries_anywhere() -> Result<()> {
+        crate::init_logging();
+        let padding = Module::new("padding", Memory::from_vec(vec![0; 100]));
+        let linked = Linker::default()
+            .with_module(program()?)
+            .with_module(padding)
+            .with_module(library()?)
+            .link()?;
+
+        assert_eq!(Some(Address::new(112)), linked.symbol("triple"));
+        let mut exe = Executable::from(linked.memory().clone());
+        exe.single_input(-5);
+        let drain = exe.drain();
+        exe.run()?;
+        drop(exe);
+        assert_eq!(vec![-15], drain.to_vec());
+
+        let image = linked.image();
+        assert_eq!(Some("padding"), image.symbol(Address::new(12)));
+        assert_eq!(Some("triple"), image.symbol(Address::new(112)));
+
+        Ok(())
+    }
+
+    #[test]
+    fn reports_link_errors() -> Result<()> {
+        crate::init_logging();
+        let missing = Linker::default().with_module(program()?).link();
+        assert_eq!(
+            Err(LinkError::Undefined {
+                module: "main".to_string(),
+                name: "arg".to_string()
+            }),
+            missing
+        );
+
+        let twice = Linker::default()
+            .with_module(library()?)
+            .with_module(Module::new("other", Memory::from_vec(vec![99])).with_export("arg", 0))
+            .link()
+            .unwrap_err();
+        assert_eq!(
+            "symbol `arg` is exported by both `lib` and `other`",
+            twice.to_string()
+        );
+
+        let beyond = Linker::default()
+            .with_module(Module::new("main", Memory::from_vec(vec![99])).with_relocation(1))
+            .link()
+            .unwrap_err();
+        assert_eq!(
+            "module `main` refers to offset 1, beyond its end",
+            beyond.to_string()
+        );
+
+        let data = Module::new("data", Memory::from_vec(vec![42])).with_code(0..1);
+        assert_eq!(
+            Some(LinkError::Undecodable {
+                module: "data".to_string(),
+                offset: 0
+            }),
+            data.err()
+        );
+
+        Ok(())
+    }
+}
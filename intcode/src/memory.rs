@@ -1,14 +1,45 @@
-use super::{error, Address, Word};
-use std::{io, mem, str};
+use super::{error, image, Address, Diff, Image, Word};
+use std::{borrow::Cow, fmt, io, mem, str, sync::Arc};
+
+const PAGE_BITS: usize = 8;
+const PAGE_SIZE: usize = 1 << PAGE_BITS;
+const PAGE_MASK: usize = PAGE_SIZE - 1;
+
+type Page = [Word; PAGE_SIZE];
 
 /// An Intcode memory
 ///
 /// Intcode programs are a vector of signed integers.
-#[derive(Clone, Debug, PartialEq, Eq)]
+///
+/// Memory is stored as a series of fixed-size pages which are shared between
+/// clones and only copied when first written to. Cloning a `Memory` is cheap,
+/// which makes it practical to keep a pristine program image around and to
+/// restore it with `reset_to` between runs.
+#[derive(Clone)]
 pub struct Memory {
-    data: Vec<Word>,
+    pages: Vec<Arc<Page>>,
+    len: usize,
+}
+
+impl fmt::Debug for Memory {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_list().entries(self.iter()).finish()
+    }
 }
 
+impl PartialEq for Memory {
+    fn eq(&self, other: &Self) -> bool {
+        self.len == other.len
+            && self
+                .pages
+                .iter()
+                .zip(&other.pages)
+                .all(|(l, r)| Arc::ptr_eq(l, r) || l[..] == r[..])
+    }
+}
+
+impl Eq for Memory {}
+
 impl str::FromStr for Memory {
     type Err = io::Error;
 
@@ -47,23 +78,48 @@ impl str::FromStr for Memory {
 impl Memory {
     /// Initializes Intcode memory from a vector of data
     pub fn from_vec(data: Vec<Word>) -> Self {
-        Self { data }
+        let pages = data
+            .chunks(PAGE_SIZE)
+            .map(|chunk| {
+                let mut page = [0; PAGE_SIZE];
+                page[..chunk.len()].copy_from_slice(chunk);
+                Arc::new(page)
+            })
+            .collect();
+
+        Self {
+            pages,
+            len: data.len(),
+        }
     }
 
     /// Initializes Intcode memory from an `io::Read`er
     ///
-    /// This function will read in the entire dataset before parsing.
+    /// This function will read in the entire dataset before parsing. Both
+    /// text and binary images are accepted.
     pub fn from_reader(input: &mut dyn io::Read) -> io::Result<Memory> {
-        let mut raw_data = String::new();
-        input.read_to_string(&mut raw_data)?;
+        let mut raw_data = Vec::new();
+        input.read_to_end(&mut raw_data)?;
+
+        if raw_data.starts_with(image::MAGIC) {
+            return Self::from_image(&mut &raw_data[..]);
+        }
 
-        raw_data.parse()
+        str::from_utf8(&raw_data)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?
+            .parse()
     }
 
     /// Initializes Intcode memory from an `io::BufRead`er
     ///
-    /// This function will read in the entire dataset before parsing.
+    /// Both text and binary images are accepted. Use `Image::load` to keep
+    /// the image's metadata.
     pub fn from_buf_reader(input: &mut dyn io::BufRead) -> io::Result<Memory> {
+        Image::load(input).map(Image::into_memory)
+    }
+
+    /// Parses a text program from an `io::BufRead`er
+    pub(crate) fn from_text(input: &mut dyn io::BufRead) -> io::Result<Memory> {
         let mut data = Vec::new();
         let mut buf = Vec::with_capacity(16);
         loop {
@@ -73,7 +129,9 @@ impl Memory {
                 0 => break,
                 c => {
                     debug_assert!(c == buf.len());
-                    let raw = std::str::from_utf8(&buf[..c - 1])
+                    // The last value need not be followed by a comma
+                    let value = buf.strip_suffix(b",").unwrap_or(&buf);
+                    let raw = std::str::from_utf8(value)
                         .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
                     let trimmed = raw.trim();
                     if trimmed.is_empty() {
@@ -90,36 +148,141 @@ impl Memory {
         Ok(Self::from_vec(data))
     }
 
+    /// Initializes Intcode memory from a binary program image
+    ///
+    /// Use `Image::read_from` to keep the image's metadata.
+    pub fn from_image(input: &mut dyn io::Read) -> io::Result<Memory> {
+        Image::read_from(input).map(Image::into_memory)
+    }
+
+    /// Writes memory as a binary program image with no metadata
+    pub fn to_writer(&self, output: &mut dyn io::Write) -> io::Result<()> {
+        Image::new(self.clone()).write_to(output)
+    }
+
+    /// Iterates over the values in memory, in address order
+    pub fn iter(&self) -> impl Iterator<Item = Word> + '_ {
+        self.pages
+            .iter()
+            .flat_map(|page| page.iter().copied())
+            .take(self.len)
+    }
+
+    /// Copies the contents of memory into a vector
+    pub fn to_vec(&self) -> Vec<Word> {
+        self.iter().collect()
+    }
+
+    /// Borrows the contents of memory as a series of contiguous slices, in
+    /// address order
+    pub fn chunks(&self) -> impl Iterator<Item = &[Word]> + '_ {
+        let mut remaining = self.len;
+        self.pages.iter().map(move |page| {
+            let len = remaining.min(PAGE_SIZE);
+            remaining -= len;
+            &page[..len]
+        })
+    }
+
     /// Provides immutable access to the underlying memory
-    #[inline]
-    pub fn raw(&self) -> &[Word] {
-        &self.data
+    ///
+    /// Memory is no longer stored contiguously, so this only borrows when
+    /// the program fits in a single page and copies otherwise.
+    #[deprecated(note = "use `chunks` to borrow memory, or `iter` or `to_vec`")]
+    pub fn raw(&self) -> Cow<'_, [Word]> {
+        match &self.pages[..] {
+            [] => Cow::Borrowed(&[]),
+            [page] => Cow::Borrowed(&page[..self.len]),
+            _ => Cow::Owned(self.to_vec()),
+        }
     }
 
     /// Returns the size of allocated memory in `Word`s
     #[inline]
     pub fn size(&self) -> usize {
-        self.data.len()
+        self.len
     }
 
     /// Returns the maximum valid address in memory
     #[inline]
     pub fn max_address(&self) -> Address {
-        Address::new(self.data.len() - 1)
+        Address::new(self.len - 1)
     }
 
-    #[inline]
     pub fn set_memory_limit(&mut self, capacity: usize) {
-        self.data.resize_with(capacity, || 0);
+        if capacity < self.len {
+            // Keep the tail of the last page zeroed so that later growth
+            // exposes zeroes rather than stale data
+            let offset = capacity & PAGE_MASK;
+            self.pages.truncate((capacity + PAGE_MASK) >> PAGE_BITS);
+            if offset != 0 {
+                if let Some(last) = self.pages.last_mut() {
+                    for w in &mut Arc::make_mut(last)[offset..] {
+                        *w = 0;
+                    }
+                }
+            }
+        } else {
+            let pages = (capacity + PAGE_MASK) >> PAGE_BITS;
+            self.pages.resize_with(pages, || Arc::new([0; PAGE_SIZE]));
+        }
+
+        self.len = capacity;
+    }
+
+    /// Restores this memory to the contents of `image`
+    ///
+    /// Pages that still share storage with `image` are left untouched. Pages
+    /// that have been copied on write are overwritten in place, so a memory
+    /// that is repeatedly reset to the same image will not allocate once it
+    /// has been warmed up.
+    pub fn reset_to(&mut self, image: &Memory) {
+        self.pages.truncate(image.pages.len());
+        for (page, pristine) in self.pages.iter_mut().zip(&image.pages) {
+            if Arc::ptr_eq(page, pristine) {
+                continue;
+            }
+
+            match Arc::get_mut(page) {
+                Some(owned) => owned.copy_from_slice(&pristine[..]),
+                None => *page = Arc::clone(pristine),
+            }
+        }
+
+        let start = self.pages.len();
+        self.pages
+            .extend(image.pages[start..].iter().map(Arc::clone));
+        self.len = image.len;
+    }
+
+    /// Compares this memory with `other`, finding the words which differ
+    ///
+    /// Pages which the two memories still share are skipped, so comparing a
+    /// memory with the image it was cloned from is cheap.
+    pub fn diff(&self, other: &Memory) -> Diff {
+        Diff::between(self, other)
+    }
+
+    /// If the page holding `idx` is shared with `other` and lies within both
+    /// memories, returns the index just past it
+    pub(crate) fn shared_page_end(&self, other: &Memory, idx: usize) -> Option<usize> {
+        let page = idx >> PAGE_BITS;
+        let end = (page + 1) << PAGE_BITS;
+        let shared = end <= self.len
+            && end <= other.len
+            && Arc::ptr_eq(&self.pages[page], &other.pages[page]);
+        if shared {
+            Some(end)
+        } else {
+            None
+        }
     }
 
     /// Attempts to read a value from a given address
     ///
     /// Returns `None` if the address is outside the bounds of legal addresses.
     pub fn try_read(&self, address: Address) -> Result<Word, error::OutOfBoundsAccess> {
-        self.data
-            .get(address.value())
-            .copied()
+        self.get(address.value())
             .ok_or_else(|| error::OutOfBoundsAccess::new(address))
     }
 
@@ -127,7 +290,16 @@ impl Memory {
     ///
     /// Returns `0` if the address is outside the bounds of legal addresses.
     pub fn read_or_default(&self, address: Address) -> Word {
-        self.data.get(address.value()).copied().unwrap_or(0)
+        self.get(address.value()).unwrap_or(0)
+    }
+
+    #[inline]
+    fn get(&self, idx: usize) -> Option<Word> {
+        if idx < self.len {
+            Some(self.pages[idx >> PAGE_BITS][idx & PAGE_MASK])
+        } else {
+            None
+        }
     }
 
     /// Attempts to write a value to the given address
@@ -139,11 +311,13 @@ impl Memory {
         address: Address,
         value: Word,
     ) -> Result<Word, error::OutOfBoundsAccess> {
-        let sloc = self
-            .data
-            .get_mut(address.value())
-            .ok_or_else(|| error::OutOfBoundsAccess::new(address))?;
-        Ok(mem::replace(sloc, value))
+        let idx = address.value();
+        if idx >= self.len {
+            return Err(error::OutOfBoundsAccess::new(address));
+        }
+
+        let page = Arc::make_mut(&mut self.pages[idx >> PAGE_BITS]);
+        Ok(mem::replace(&mut page[idx & PAGE_MASK], value))
     }
 
     /// Attempts to write a value to the given address, expanding memory if the
@@ -164,7 +338,7 @@ impl Memory {
 
 #[cfg(test)]
 mod tests {
-    use crate::{Memory, Word};
+    use crate::{Address, Memory, Word};
     use anyhow::Result;
     use pretty_assertions::assert_eq;
 
@@ -177,7 +351,10 @@ mod tests {
     fn check_buf_reader() -> Result<()> {
         let mut reader = std::io::Cursor::new(DATA);
 
-        assert_eq!(EXPECTED, Memory::from_buf_reader(&mut reader)?.raw());
+        assert_eq!(
+            EXPECTED,
+            &Memory::from_buf_reader(&mut reader)?.to_vec()[..]
+        );
 
         Ok(())
     }
@@ -186,7 +363,7 @@ mod tests {
     fn check_reader() -> Result<()> {
         let mut reader = std::io::Cursor::new(DATA);
 
-        assert_eq!(EXPECTED, Memory::from_reader(&mut reader)?.raw());
+        assert_eq!(EXPECTED, &Memory::from_reader(&mut reader)?.to_vec()[..]);
 
         Ok(())
     }
@@ -194,8 +371,58 @@ mod tests {
     #[test]
     fn check_str() -> Result<()> {
         let actual: Memory = DATA.parse()?;
-        assert_eq!(EXPECTED, actual.raw());
+        assert_eq!(EXPECTED, &actual.to_vec()[..]);
 
         Ok(())
     }
+
+    #[test]
+    fn clones_share_pages_until_written() {
+        let image = Memory::from_vec((0..1000).collect());
+        let mut copy = image.clone();
+
+        copy.try_write(Address::new(300), -1).unwrap();
+
+        assert_eq!(299, image.read_or_default(Address::new(299)));
+        assert_eq!(300, image.read_or_default(Address::new(300)));
+        assert_eq!(-1, copy.read_or_default(Address::new(300)));
+        assert_ne!(image, copy);
+    }
+
+    #[test]
+    fn reset_restores_image() {
+        let image = Memory::from_vec((0..600).collect());
+        let mut memory = image.clone();
+
+        memory.try_write(Address::new(5), 42).unwrap();
+        memory.write_arbitrary(Address::new(2000), 7);
+        memory.reset_to(&image);
+
+        assert_eq!(image, memory);
+        assert_eq!(600, memory.size());
+        assert_eq!(0, memory.read_or_default(Address::new(2000)));
+    }
+
+    #[test]
+    fn chunks_borrow_pages_in_order() {
+        let memory = Memory::from_vec((0..600).collect());
+
+        let chunks: Vec<&[Word]> = memory.chunks().collect();
+
+        assert_eq!(
+            vec![256, 256, 88],
+            chunks.iter().map(|c| c.len()).collect::<Vec<_>>()
+        );
+        assert_eq!(memory.to_vec(), chunks.concat());
+    }
+
+    #[test]
+    fn shrinking_clears_stale_values() {
+        let mut memory = Memory::from_vec(vec![1, 2, 3, 4]);
+
+        memory.set_memory_limit(2);
+        memory.set_memory_limit(4);
+
+        assert_eq!(&[1, 2, 0, 0], &memory.to_vec()[..]);
+    }
 }
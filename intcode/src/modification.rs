@@ -0,0 +1,280 @@
+use super::{Address, Decoded, Observer, Word};
+use std::{collections::BTreeMap, fmt};
+
+/// A way in which a program modified its own code
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Modification {
+    /// The instruction at `writer` wrote to `address`, which had already
+    /// executed as part of the instruction at `instruction`
+    Overwrote {
+        writer: Address,
+        address: Address,
+        instruction: Address,
+    },
+    /// The instruction at `instruction` executed `address`, which had been
+    /// written as data by the instruction at `writer`
+    ///
+    /// `immediate` is set when the write was made by the instruction executed
+    /// just before, so that it landed on the instruction about to execute.
+    Executed {
+        instruction: Address,
+        address: Address,
+        writer: Address,
+        immediate: bool,
+    },
+}
+
+impl fmt::Display for Modification {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Modification::Overwrote {
+                writer,
+                address,
+                instruction,
+            } => write!(
+                f,
+                "{} overwrote {}, executed as part of {}",
+                writer, address, instruction
+            ),
+            Modification::Executed {
+                instruction,
+                address,
+                writer,
+                immediate,
+            } => {
+                write!(
+                    f,
+                    "{} executed {}, written as data by {}",
+                    instruction, address, writer
+                )?;
+                if *immediate {
+                    f.write_str(" just before")?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+/// A modification, along with when and how often it happened
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Finding {
+    /// What happened
+    pub modification: Modification,
+    /// The number of instructions executed before it first happened
+    pub first_step: usize,
+    /// The number of times it happened
+    pub count: usize,
+}
+
+impl fmt::Display for Finding {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "step {}: {}", self.first_step, self.modification)?;
+        if self.count > 1 {
+            write!(f, " ({} times)", self.count)?;
+        }
+        Ok(())
+    }
+}
+
+/// Detects a program modifying its own code
+///
+/// `SelfModification` is an `Observer` which flags each write landing on an
+/// address which has executed as part of an instruction, and each
+/// instruction executed from an address which was written as data. Code is
+/// only known once it has executed, so a write to code which has yet to run
+/// is flagged when that code executes. Writes made before execution began,
+/// such as the noun and verb of day 2, are not seen.
+///
+/// A run with no findings executed only the instructions the program was
+/// loaded with. That holds for the inputs of that run alone: other inputs can
+/// take other paths, so a clean run does not show that the program's code is
+/// safe to cache or translate ahead of time.
+///
+/// ## Example
+///
+/// ```
+/// use intcode::{Executable, Memory, SelfModification};
+///
+/// // Writes a halt over the first instruction's opcode and jumps back to it
+/// let memory: Memory = "1101,0,99,0,1105,1,0".parse().expect("valid data");
+/// let mut detector = SelfModification::default();
+///
+/// Executable::from(memory)
+///     .with_observer(&mut detector)
+///     .run()
+///     .expect("successful execution");
+///
+/// assert!(!detector.is_clean());
+/// assert_eq!(
+///     "step 0: 0 overwrote 0, executed as part of 0\n\
+///      step 2: 0 executed 0, written as data by 0",
+///     detector.to_string()
+/// );
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct SelfModification {
+    fetched: usize,
+    /// The instruction which each address executed as part of
+    executed: Vec<Option<Address>>,
+    /// The instruction which last wrote each address, and the step it did so
+    written: Vec<Option<(Address, usize)>>,
+    findings: Vec<Finding>,
+    index: BTreeMap<Modification, usize>,
+}
+
+impl SelfModification {
+    /// Whether no modification has been found
+    pub fn is_clean(&self) -> bool {
+        self.findings.is_empty()
+    }
+
+    /// The modifications found, in the order they first happened
+    pub fn findings(&self) -> &[Finding] {
+        &self.findings
+    }
+
+    /// Whether `address` has executed as part of an instruction
+    pub fn is_code(&self, address: Address) -> bool {
+        self.executed
+            .get(address.value())
+            .is_some_and(Option::is_some)
+    }
+
+    fn record(&mut self, modification: Modification) {
+        match self.index.get(&modification) {
+            Some(&i) => self.findings[i].count += 1,
+            None => {
+                self.index.insert(modification, self.findings.len());
+                self.findings.push(Finding {
+                    modification,
+                    first_step: self.fetched - 1,
+                    count: 1,
+                });
+            }
+        }
+    }
+}
+
+fn slot<T: Default + Clone>(slots: &mut Vec<T>, address: Address) -> &mut T {
+    let idx = address.value();
+    if idx >= slots.len() {
+        slots.resize(idx + 1, T::default());
+    }
+    &mut slots[idx]
+}
+
+impl Observer for SelfModification {
+    fn on_fetch(&mut self, pc: Address, instruction: &Decoded) {
+        self.fetched += 1;
+        let step = self.fetched - 1;
+        for idx in pc.value()..pc.value() + instruction.size() {
+            let address = Address::new(idx);
+            if let Some(&Some((writer, written))) = self.written.get(idx) {
+                self.record(Modification::Executed {
+                    instruction: pc,
+                    address,
+                    writer,
+                    immediate: written + 1 == step,
+                });
+            }
+            *slot(&mut self.executed, address) = Some(pc);
+        }
+    }
+
+    fn on_write(&mut self, pc: Address, address: Address, _prior: Word, _value: Word) {
+        if let Some(&Some(instruction)) = self.executed.get(address.value()) {
+            self.record(Modification::Overwrote {
+                writer: pc,
+                address,
+                instruction,
+            });
+        }
+        *slot(&mut self.written, address) = Some((pc, self.fetched - 1));
+    }
+}
+
+impl fmt::Display for SelfModification {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.findings.is_empty() {
+            return f.write_str("no self-modification found");
+        }
+
+        let lines: Vec<_> = self.findings.iter().map(Finding::to_string).collect();
+        f.write_str(&lines.join("\n"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Finding, Modification, SelfModification};
+    use crate::{Address, Executable, Memory};
+    use anyhow::Result;
+    use pretty_assertions::assert_eq;
+
+    fn detect(program: &str, inputs: &[crate::Word]) -> Result<SelfModification> {
+        let memory: Memory = program.parse()?;
+        let mut detector = SelfModification::default();
+        let mut exe = Executable::from(memory).with_observer(&mut detector);
+        let (tx, rx) = std::sync::mpsc::channel();
+        for &value in inputs {
+            tx.send(value)?;
+        }
+        exe.pipe_inputs_from(rx);
+        let _drain = exe.drain();
+        exe.run()?;
+        drop(exe);
+        Ok(detector)
+    }
+
+    #[test]
+    fn ordinary_programs_are_clean() -> Result<()> {
+        crate::init_logging();
+        let detector = detect("3,9,8,9,10,9,4,9,99,-1,8", &[8])?;
+
+        assert!(detector.is_clean());
+        assert!(detector.is_code(Address::new(8)));
+        assert!(!detector.is_code(Address::new(9)));
+        assert_eq!("no self-modification found", detector.to_string());
+
+        Ok(())
+    }
+
+    #[test]
+    fn input_patched_into_the_next_instruction() -> Result<()> {
+        crate::init_logging();
+        // Reads an operand straight into the output instruction which follows
+        let detector = detect("3,3,104,0,99", &[42])?;
+
+        assert_eq!(
+            &[Finding {
+                modification: Modification::Executed {
+                    instruction: Address::new(2),
+                    address: Address::new(3),
+                    writer: Address::new(0),
+                    immediate: true,
+                },
+                first_step: 1,
+                count: 1,
+            }],
+            detector.findings()
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn repeated_modifications_are_counted() -> Result<()> {
+        crate::init_logging();
+        // Counts down from 3 in the immediate condition of the jump at 8
+        let detector = detect("1101,3,0,9,101,-1,9,9,1105,0,4,99", &[])?;
+
+        assert_eq!(
+            "step 2: 8 executed 9, written as data by 4 just before (3 times)\n\
+             step 3: 4 overwrote 9, executed as part of 8 (2 times)",
+            detector.to_string()
+        );
+
+        Ok(())
+    }
+}
@@ -0,0 +1,177 @@
+use super::{Address, Decoded, Word};
+
+/// Receives notifications about the activity of an executable
+///
+/// Every method has an empty default implementation, so implementors need
+/// only override the events they are interested in. Executables use `()` as
+/// their observer unless one is attached, in which case all notifications
+/// compile away.
+///
+/// Each notification includes the address of the instruction responsible.
+pub trait Observer {
+    /// An instruction has been fetched and decoded and is about to execute
+    #[inline(always)]
+    fn on_fetch(&mut self, _pc: Address, _instruction: &Decoded) {}
+
+    /// A value has been read from memory as an instruction operand
+    #[inline(always)]
+    fn on_read(&mut self, _pc: Address, _address: Address, _value: Word) {}
+
+    /// A value has been written to memory, replacing `prior`
+    #[inline(always)]
+    fn on_write(&mut self, _pc: Address, _address: Address, _prior: Word, _value: Word) {}
+
+    /// A value has been received as input
+    #[inline(always)]
+    fn on_input(&mut self, _pc: Address, _value: Word) {}
+
+    /// A value has been sent as output
+    #[inline(always)]
+    fn on_output(&mut self, _pc: Address, _value: Word) {}
+
+    /// A conditional jump has been evaluated
+    ///
+    /// `taken` indicates whether the program counter moved to `target`.
+    #[inline(always)]
+    fn on_jump(&mut self, _pc: Address, _target: Address, _taken: bool) {}
+
+    /// The relative base has been moved from `prior` to `base`
+    #[inline(always)]
+    fn on_relative_base(&mut self, _pc: Address, _prior: Address, _base: Address) {}
+}
+
+impl Observer for () {}
+
+impl<O: Observer + ?Sized> Observer for &mut O {
+    #[inline]
+    fn on_fetch(&mut self, pc: Address, instruction: &Decoded) {
+        (**self).on_fetch(pc, instruction)
+    }
+
+    #[inline]
+    fn on_read(&mut self, pc: Address, address: Address, value: Word) {
+        (**self).on_read(pc, address, value)
+    }
+
+    #[inline]
+    fn on_write(&mut self, pc: Address, address: Address, prior: Word, value: Word) {
+        (**self).on_write(pc, address, prior, value)
+    }
+
+    #[inline]
+    fn on_input(&mut self, pc: Address, value: Word) {
+        (**self).on_input(pc, value)
+    }
+
+    #[inline]
+    fn on_output(&mut self, pc: Address, value: Word) {
+        (**self).on_output(pc, value)
+    }
+
+    #[inline]
+    fn on_jump(&mut self, pc: Address, target: Address, taken: bool) {
+        (**self).on_jump(pc, target, taken)
+    }
+
+    #[inline]
+    fn on_relative_base(&mut self, pc: Address, prior: Address, base: Address) {
+        (**self).on_relative_base(pc, prior, base)
+    }
+}
+
+/// Notifies the observer, if there is one
+impl<O: Observer> Observer for Option<O> {
+    #[inline]
+    fn on_fetch(&mut self, pc: Address, instruction: &Decoded) {
+        if let Some(o) = self {
+            o.on_fetch(pc, instruction);
+        }
+    }
+
+    #[inline]
+    fn on_read(&mut self, pc: Address, address: Address, value: Word) {
+        if let Some(o) = self {
+            o.on_read(pc, address, value);
+        }
+    }
+
+    #[inline]
+    fn on_write(&mut self, pc: Address, address: Address, prior: Word, value: Word) {
+        if let Some(o) = self {
+            o.on_write(pc, address, prior, value);
+        }
+    }
+
+    #[inline]
+    fn on_input(&mut self, pc: Address, value: Word) {
+        if let Some(o) = self {
+            o.on_input(pc, value);
+        }
+    }
+
+    #[inline]
+    fn on_output(&mut self, pc: Address, value: Word) {
+        if let Some(o) = self {
+            o.on_output(pc, value);
+        }
+    }
+
+    #[inline]
+    fn on_jump(&mut self, pc: Address, target: Address, taken: bool) {
+        if let Some(o) = self {
+            o.on_jump(pc, target, taken);
+        }
+    }
+
+    #[inline]
+    fn on_relative_base(&mut self, pc: Address, prior: Address, base: Address) {
+        if let Some(o) = self {
+            o.on_relative_base(pc, prior, base);
+        }
+    }
+}
+
+/// Notifies both observers, in order
+impl<A: Observer, B: Observer> Observer for (A, B) {
+    #[inline]
+    fn on_fetch(&mut self, pc: Address, instruction: &Decoded) {
+        self.0.on_fetch(pc, instruction);
+        self.1.on_fetch(pc, instruction);
+    }
+
+    #[inline]
+    fn on_read(&mut self, pc: Address, address: Address, value: Word) {
+        self.0.on_read(pc, address, value);
+        self.1.on_read(pc, address, value);
+    }
+
+    #[inline]
+    fn on_write(&mut self, pc: Address, address: Address, prior: Word, value: Word) {
+        self.0.on_write(pc, address, prior, value);
+        self.1.on_write(pc, address, prior, value);
+    }
+
+    #[inline]
+    fn on_input(&mut self, pc: Address, value: Word) {
+        self.0.on_input(pc, value);
+        self.1.on_input(pc, value);
+    }
+
+    #[inline]
+    fn on_output(&mut self, pc: Address, value: Word) {
+        self.0.on_output(pc, value);
+        self.1.on_output(pc, value);
+    }
+
+    #[inline]
+    fn on_jump(&mut self, pc: Address, target: Address, taken: bool) {
+        self.0.on_jump(pc, target, taken);
+        self.1.on_jump(pc, target, taken);
+    }
+
+    #[inline]
+    fn on_relative_base(&mut self, pc: Address, prior: Address, base: Address) {
+        self.0.on_relative_base(pc, prior, base);
+        self.1.on_relative_base(pc, prior, base);
+    }
+}
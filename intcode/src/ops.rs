@@ -22,6 +22,27 @@ impl Instruction {
     pub const fn param_modes(self) -> ParameterModes {
         self.modes
     }
+
+    /// Builds an instruction from its operation and the modes of its
+    /// parameters, in order
+    ///
+    /// Parameters without a mode are in position mode.
+    pub fn new(opcode: OpCode, modes: &[ParameterMode]) -> Self {
+        debug_assert!(modes.len() <= usize::from(opcode.params()));
+        let modes = modes
+            .iter()
+            .rev()
+            .fold(0, |modes, mode| modes * 10 + mode.value());
+        Self {
+            opcode,
+            modes: ParameterModes(modes),
+        }
+    }
+
+    /// Encodes the instruction as the word it is decoded from
+    pub fn encode(self) -> Word {
+        (self.modes.0 * 100 + self.opcode.stem()) as Word
+    }
 }
 
 impl TryFrom<Word> for Instruction {
@@ -43,7 +64,7 @@ impl TryFrom<Word> for Instruction {
 /// The number in the description refers to the instruction stem (`instruction %
 /// 100`), which specifies the operation to execute as well as implying the
 /// quantity of parameters required.
-#[derive(Clone, Copy, Debug)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub enum OpCode {
     /// (`99`) Halts the program
     Halt,
@@ -69,6 +90,34 @@ pub enum OpCode {
     AddRel,
 }
 
+impl OpCode {
+    /// The instruction stem which selects the operation
+    pub const fn stem(self) -> usize {
+        match self {
+            OpCode::Add => 1,
+            OpCode::Mul => 2,
+            OpCode::Input => 3,
+            OpCode::Output => 4,
+            OpCode::JumpNonZero => 5,
+            OpCode::JumpZero => 6,
+            OpCode::LessThan => 7,
+            OpCode::Equal => 8,
+            OpCode::AddRel => 9,
+            OpCode::Halt => 99,
+        }
+    }
+
+    /// The number of parameters following the instruction
+    pub const fn params(self) -> u8 {
+        match self {
+            OpCode::Halt => 0,
+            OpCode::Input | OpCode::Output | OpCode::AddRel => 1,
+            OpCode::JumpNonZero | OpCode::JumpZero => 2,
+            OpCode::Add | OpCode::Mul | OpCode::LessThan | OpCode::Equal => 3,
+        }
+    }
+}
+
 impl TryFrom<usize> for OpCode {
     type Error = InvalidInstruction;
     fn try_from(opcode: usize) -> Result<Self, Self::Error> {
@@ -103,6 +152,12 @@ impl ParameterModes {
             self.0 /= 10;
         }
     }
+
+    /// The mode of the parameter at `index`, counting from 0
+    pub fn mode(self, index: u8) -> ParameterMode {
+        let digit = self.0 / 10_usize.pow(u32::from(index)) % 10;
+        ParameterMode::from_value(digit).expect("invalid parameter mode in pre-validated context")
+    }
 }
 
 impl TryFrom<usize> for ParameterModes {
@@ -122,10 +177,11 @@ impl TryFrom<usize> for ParameterModes {
     }
 }
 
-#[derive(Clone, Copy, Debug)]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
 pub enum ParameterMode {
     /// The parameter is an address reference; the actual parameter value should
     /// be retrieved from that address
+    #[default]
     Position,
     /// The parameter is the value to be used for the operation
     Immediate,
@@ -134,12 +190,6 @@ pub enum ParameterMode {
     Relative,
 }
 
-impl Default for ParameterMode {
-    fn default() -> Self {
-        ParameterMode::Position
-    }
-}
-
 impl ParameterMode {
     fn from_value(mode: usize) -> Option<Self> {
         match mode {
@@ -149,6 +199,14 @@ impl ParameterMode {
             _ => None,
         }
     }
+
+    const fn value(self) -> usize {
+        match self {
+            ParameterMode::Position => 0,
+            ParameterMode::Immediate => 1,
+            ParameterMode::Relative => 2,
+        }
+    }
 }
 
 impl fmt::Display for ParameterMode {
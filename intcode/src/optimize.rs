@@ -0,0 +1,760 @@
+use super::{
+    decode::decode, ops::Instruction, Address, Backend, Decoded, Ending, Executable,
+    ExecutionError, JumpIfOperands, Memory, Observer, Outcome, Output, Parameter, ProgramCounter,
+    Word,
+};
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    convert::TryFrom,
+    fmt,
+    sync::mpsc,
+};
+use thiserror::Error;
+
+/// Why a program cannot be proven safe to optimize
+#[derive(Error, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NotStatic {
+    /// An instruction addresses memory through the relative base, so its
+    /// accesses cannot be determined ahead of time
+    #[error("instruction at {pc} accesses memory relative to the relative base")]
+    RelativeAccess { pc: Address },
+    /// A jump target is read from memory which the program writes to
+    #[error("instruction at {pc} jumps to a computed address")]
+    ComputedJump { pc: Address },
+    /// The program writes to the opcode or jump operands of a reachable
+    /// instruction, so where it goes next cannot be determined
+    #[error("instruction at {pc} is written by the program before it runs")]
+    SelfModifying { pc: Address },
+    /// A reachable instruction is invalid, so the program fails when run
+    #[error("instruction at {pc} cannot be decoded")]
+    Undecodable { pc: Address },
+}
+
+/// What the optimizer changed
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Report {
+    /// Arithmetic and comparisons on immediates replaced by their result
+    pub folded: usize,
+    /// Jumps retargeted past unconditional jumps
+    pub threaded: usize,
+    /// Unreachable words which were cleared or removed
+    pub cleared: usize,
+    /// Whether the reachable code could be identified
+    ///
+    /// This is `false` when the program overwrites its own instructions, as
+    /// the rewritten code may go anywhere, and then nothing is changed.
+    pub complete_cfg: bool,
+    /// The size of the program before optimization, in words
+    pub original_size: usize,
+    /// The size of the program after optimization, in words
+    pub optimized_size: usize,
+}
+
+impl Report {
+    /// The number of words removed from the end of the program
+    pub fn size_saved(&self) -> usize {
+        self.original_size - self.optimized_size
+    }
+}
+
+impl fmt::Display for Report {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "folded {}, threaded {}, cleared {}; {} -> {} words",
+            self.folded, self.threaded, self.cleared, self.original_size, self.optimized_size
+        )?;
+        if !self.complete_cfg {
+            write!(f, " (self-modifying; left unchanged)")?;
+        }
+        Ok(())
+    }
+}
+
+/// The number of instructions executed before and after optimization
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct StepSavings {
+    /// Steps taken by the original program
+    pub before: usize,
+    /// Steps taken by the optimized program
+    pub after: usize,
+}
+
+impl StepSavings {
+    /// The number of steps no longer executed
+    pub fn saved(&self) -> usize {
+        self.before.saturating_sub(self.after)
+    }
+}
+
+/// A program rewritten by `optimize`
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Optimized {
+    /// The optimized program
+    pub memory: Memory,
+    /// What was changed
+    pub report: Report,
+    original: BTreeMap<Address, Word>,
+}
+
+impl Optimized {
+    /// Restores every word the optimizer changed to its original value
+    ///
+    /// The optimizer only changes words which the program never reads or
+    /// writes, so restoring them in the final memory of an optimized run
+    /// gives the final memory of the original program.
+    pub fn restore(&self, memory: &mut Memory) {
+        for (&address, &value) in &self.original {
+            if address.value() < memory.size() || value != 0 {
+                memory.write_arbitrary(address, value);
+            }
+        }
+        if memory.size() < self.report.original_size {
+            memory.set_memory_limit(self.report.original_size);
+        }
+    }
+
+    /// Runs the original and the optimized program with `inputs`, counting
+    /// the instructions executed by each
+    pub fn step_savings(
+        &self,
+        original: &Memory,
+        inputs: &[Word],
+    ) -> Result<StepSavings, ExecutionError> {
+        Ok(StepSavings {
+            before: count_steps(original, inputs)?,
+            after: count_steps(&self.memory, inputs)?,
+        })
+    }
+}
+
+#[derive(Default)]
+struct StepCount(usize);
+
+impl Observer for StepCount {
+    fn on_fetch(&mut self, _pc: Address, _instruction: &Decoded) {
+        self.0 += 1;
+    }
+}
+
+fn count_steps(program: &Memory, inputs: &[Word]) -> Result<usize, ExecutionError> {
+    let mut exe = Executable::from(program.clone());
+    let (tx, rx) = mpsc::channel();
+    for &value in inputs {
+        tx.send(value).expect("receiver is held by the executable");
+    }
+    drop(tx);
+    exe.pipe_inputs_from(rx);
+    let _drain = exe.drain();
+
+    let mut exe = exe.with_observer(StepCount::default());
+    exe.run()?;
+    Ok(exe.into_observer().0)
+}
+
+/// A reachable instruction found by static analysis
+struct Static {
+    decoded: Decoded,
+    size: usize,
+}
+
+/// The instructions reachable from the start of a program, along with every
+/// address they read or write
+struct Analysis {
+    instructions: BTreeMap<Address, Static>,
+    reads: BTreeSet<Address>,
+    writes: BTreeSet<Address>,
+    /// Number of reachable instructions covering each word
+    owners: BTreeMap<Address, usize>,
+}
+
+impl Analysis {
+    fn of(program: &Memory) -> Result<Self, NotStatic> {
+        let mut analysis = Self {
+            instructions: BTreeMap::new(),
+            reads: BTreeSet::new(),
+            writes: BTreeSet::new(),
+            owners: BTreeMap::new(),
+        };
+
+        // Jump targets read from memory are only resolved once every write
+        // is known
+        let mut indirect = Vec::new();
+        let mut undecodable = BTreeSet::new();
+        let mut pending = vec![Address::ZERO];
+        while let Some(pc) = pending.pop() {
+            if pc.value() >= program.size() || analysis.instructions.contains_key(&pc) {
+                continue;
+            }
+
+            // Code which has already been written to may decode differently
+            // when it runs, so it is never decoded as loaded
+            if analysis.writes.contains(&pc) {
+                return Err(NotStatic::SelfModifying { pc });
+            }
+            let decoded = match decode_at(program, pc) {
+                Some(decoded) => decoded,
+                None => {
+                    undecodable.insert(pc);
+                    continue;
+                }
+            };
+            if control_words(pc, &decoded).any(|a| analysis.writes.contains(&a)) {
+                return Err(NotStatic::SelfModifying { pc });
+            }
+            let size = decoded.size();
+            let next = Address::new(pc.value() + size);
+            analysis.note_accesses(pc, &decoded)?;
+
+            match &decoded {
+                Decoded::Halt => {}
+                Decoded::JumpNonZero(ops) | Decoded::JumpZero(ops) => {
+                    let taken = match ops.value {
+                        Parameter::Immediate(v) => {
+                            Some((v != 0) == matches!(decoded, Decoded::JumpNonZero(_)))
+                        }
+                        _ => None,
+                    };
+
+                    if taken != Some(true) {
+                        pending.push(next);
+                    }
+                    if taken != Some(false) {
+                        match ops.jump_target {
+                            Parameter::Immediate(t) => {
+                                if let Ok(target) = Address::try_from(t) {
+                                    pending.push(target);
+                                }
+                            }
+                            Parameter::Position(a) => indirect.push((pc, a)),
+                            Parameter::Relative(_) => return Err(NotStatic::RelativeAccess { pc }),
+                        }
+                    }
+                }
+                _ => pending.push(next),
+            }
+
+            for offset in 0..size {
+                *analysis
+                    .owners
+                    .entry(Address::new(pc.value() + offset))
+                    .or_insert(0) += 1;
+            }
+            analysis.instructions.insert(pc, Static { decoded, size });
+
+            if pending.is_empty() {
+                for (jump, source) in indirect.drain(..) {
+                    if analysis.writes.contains(&source) {
+                        return Err(NotStatic::ComputedJump { pc: jump });
+                    }
+                    if let Ok(target) = Address::try_from(program.read_or_default(source)) {
+                        pending.push(target);
+                    }
+                }
+            }
+        }
+
+        // An invalid instruction may only become valid once written to, in
+        // which case its successors are unknown
+        if let Some(&pc) = undecodable.iter().next() {
+            let written = undecodable.iter().find(|pc| {
+                (pc.value()..pc.value() + 4).any(|a| analysis.writes.contains(&Address::new(a)))
+            });
+            return Err(match written {
+                Some(&pc) => NotStatic::SelfModifying { pc },
+                None => NotStatic::Undecodable { pc },
+            });
+        }
+
+        // Writes found after an indirect jump was resolved may have changed
+        // its target
+        for (pc, s) in &analysis.instructions {
+            if let Decoded::JumpNonZero(ops) | Decoded::JumpZero(ops) = &s.decoded {
+                if let Parameter::Position(source) = ops.jump_target {
+                    if analysis.writes.contains(&source) {
+                        return Err(NotStatic::ComputedJump { pc: *pc });
+                    }
+                }
+            }
+        }
+
+        Ok(analysis)
+    }
+
+    fn note_accesses(&mut self, pc: Address, decoded: &Decoded) -> Result<(), NotStatic> {
+        match decoded {
+            Decoded::Halt => Ok(()),
+            Decoded::Add(ops)
+            | Decoded::Mul(ops)
+            | Decoded::LessThan(ops)
+            | Decoded::Equal(ops) => {
+                self.note_params(pc, &[ops.left, ops.right])?;
+                self.note_output(pc, ops.target)
+            }
+            Decoded::Input(ops) => self.note_output(pc, ops.target),
+            Decoded::Output(ops) | Decoded::AddRel(ops) => self.note_params(pc, &[ops.source]),
+            Decoded::JumpNonZero(ops) | Decoded::JumpZero(ops) => {
+                self.note_params(pc, &[ops.value, ops.jump_target])
+            }
+        }
+    }
+
+    fn note_params(&mut self, pc: Address, params: &[Parameter]) -> Result<(), NotStatic> {
+        for param in params {
+            match param {
+                Parameter::Position(address) => {
+                    self.reads.insert(*address);
+                }
+                Parameter::Immediate(_) => {}
+                Parameter::Relative(_) => return Err(NotStatic::RelativeAccess { pc }),
+            }
+        }
+        Ok(())
+    }
+
+    fn note_output(&mut self, pc: Address, target: Output) -> Result<(), NotStatic> {
+        match target {
+            Output::Position(address) => {
+                self.writes.insert(address);
+                Ok(())
+            }
+            Output::Relative(_) => Err(NotStatic::RelativeAccess { pc }),
+        }
+    }
+
+    /// Whether a word belongs to exactly one instruction and is never
+    /// accessed as data, so that it can be rewritten without being observed
+    fn is_private(&self, address: Address) -> bool {
+        self.owners.get(&address) == Some(&1)
+            && !self.reads.contains(&address)
+            && !self.writes.contains(&address)
+    }
+
+    /// Whether any reachable instruction may be overwritten at runtime
+    fn modifies_code(&self) -> bool {
+        self.writes.iter().any(|a| self.owners.contains_key(a))
+    }
+
+    /// Follows a chain of unconditional jumps starting at `target`
+    fn final_target(&self, target: Address) -> Address {
+        let mut seen = BTreeSet::new();
+        let mut current = target;
+        while seen.insert(current) {
+            let next = match self.instructions.get(&current) {
+                Some(s) => match unconditional_target(&s.decoded) {
+                    Some(next) if self.is_unmodified(current, s.size) => next,
+                    _ => break,
+                },
+                None => break,
+            };
+            current = next;
+        }
+        current
+    }
+
+    fn is_unmodified(&self, pc: Address, size: usize) -> bool {
+        (pc.value()..pc.value() + size).all(|a| !self.writes.contains(&Address::new(a)))
+    }
+}
+
+fn decode_at(program: &Memory, pc: Address) -> Option<Decoded> {
+    let i = Instruction::try_from(program.try_read(pc).ok()?).ok()?;
+    decode(i, ProgramCounter::at(pc), program).ok()
+}
+
+/// The words of an instruction which decide where execution goes next
+fn control_words(pc: Address, decoded: &Decoded) -> impl Iterator<Item = Address> {
+    let operands = match decoded {
+        Decoded::JumpNonZero(_) | Decoded::JumpZero(_) => 2,
+        _ => 0,
+    };
+    (pc.value()..=pc.value() + operands).map(Address::new)
+}
+
+/// The destination of a jump which is always taken to an immediate address
+fn unconditional_target(decoded: &Decoded) -> Option<Address> {
+    let always = match decoded {
+        Decoded::JumpNonZero(JumpIfOperands {
+            value: Parameter::Immediate(v),
+            ..
+        }) => *v != 0,
+        Decoded::JumpZero(JumpIfOperands {
+            value: Parameter::Immediate(v),
+            ..
+        }) => *v == 0,
+        _ => false,
+    };
+
+    match decoded {
+        Decoded::JumpNonZero(ops) | Decoded::JumpZero(ops) if always => match ops.jump_target {
+            Parameter::Immediate(t) => Address::try_from(t).ok(),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+/// Rewrites a program which does not use the relative base so that it runs
+/// in fewer steps and fewer words
+///
+/// Three passes are made over the instructions reachable from address `0`:
+///
+/// * Arithmetic and comparisons on two immediates are replaced by adding
+///   the result to zero
+/// * Jumps to an unconditional jump are retargeted to its destination
+/// * Words which are neither reachable code nor accessed as data are
+///   cleared, and removed from the end of the program
+///
+/// An instruction is only rewritten if no instruction reads or writes any of
+/// the words being changed. Programs which write to their own instructions
+/// are left unchanged, as a write may be found only after the code it
+/// changes was analysed. Programs using the relative base, or jumping to
+/// addresses which they compute, cannot be analysed and are rejected.
+///
+/// ## Example
+///
+/// ```
+/// use intcode::{optimize, Memory};
+///
+/// // Jumps to a jump to an output of 1 + 2, followed by an unused word
+/// let memory: Memory = "1105,1,3,1105,1,6,1101,1,2,13,4,13,99,0,42".parse().unwrap();
+///
+/// let optimized = optimize(&memory).unwrap();
+/// assert_eq!(1, optimized.report.folded);
+/// assert_eq!(1, optimized.report.threaded);
+/// assert_eq!(1, optimized.step_savings(&memory, &[]).unwrap().saved());
+/// ```
+pub fn optimize(program: &Memory) -> Result<Optimized, NotStatic> {
+    let analysis = Analysis::of(program)?;
+    let mut memory = program.clone();
+    let mut original = BTreeMap::new();
+    let mut report = Report {
+        complete_cfg: !analysis.modifies_code(),
+        original_size: program.size(),
+        ..Report::default()
+    };
+
+    if !report.complete_cfg {
+        report.optimized_size = memory.size();
+        return Ok(Optimized {
+            memory,
+            report,
+            original,
+        });
+    }
+
+    let mut rewrite = |memory: &mut Memory, address: Address, value: Word| {
+        let prior = memory.write_arbitrary(address, value);
+        if prior != value {
+            original.entry(address).or_insert(prior);
+        }
+    };
+
+    for (&pc, s) in &analysis.instructions {
+        let words = pc.value()..pc.value() + s.size;
+        if !words.clone().all(|a| analysis.is_private(Address::new(a))) {
+            continue;
+        }
+
+        match &s.decoded {
+            Decoded::Add(ops)
+            | Decoded::Mul(ops)
+            | Decoded::LessThan(ops)
+            | Decoded::Equal(ops) => {
+                if let (Parameter::Immediate(l), Parameter::Immediate(r), Output::Position(t)) =
+                    (ops.left, ops.right, ops.target)
+                {
+                    let value = match s.decoded {
+                        // Already in folded form
+                        Decoded::Add(_) if r == 0 => continue,
+                        Decoded::Add(_) => l.wrapping_add(r),
+                        Decoded::Mul(_) => l.wrapping_mul(r),
+                        Decoded::LessThan(_) => (l < r) as Word,
+                        _ => (l == r) as Word,
+                    };
+
+                    let folded = [1101, value, 0, t.value() as Word];
+                    for (a, w) in words.zip(folded.iter()) {
+                        rewrite(&mut memory, Address::new(a), *w);
+                    }
+                    report.folded += 1;
+                }
+            }
+            Decoded::JumpNonZero(ops) | Decoded::JumpZero(ops) => {
+                if let Parameter::Immediate(t) = ops.jump_target {
+                    if let Ok(target) = Address::try_from(t) {
+                        let last = analysis.final_target(target);
+                        if last != target {
+                            let at = Address::new(pc.value() + 2);
+                            rewrite(&mut memory, at, last.value() as Word);
+                            report.threaded += 1;
+                        }
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+
+    let unused = |a: &Address| {
+        !analysis.owners.contains_key(a)
+            && !analysis.reads.contains(a)
+            && !analysis.writes.contains(a)
+    };
+
+    for address in (0..program.size()).map(Address::new).filter(unused) {
+        if memory.read_or_default(address) != 0 {
+            rewrite(&mut memory, address, 0);
+            report.cleared += 1;
+        }
+    }
+
+    let used = (0..program.size())
+        .map(Address::new)
+        .rfind(|a| !unused(a))
+        .map_or(0, |a| a.value() + 1);
+    memory.set_memory_limit(used);
+
+    report.optimized_size = memory.size();
+    Ok(Optimized {
+        memory,
+        report,
+        original,
+    })
+}
+
+/// Runs optimized programs on another backend
+///
+/// Final memory is restored with `Optimized::restore`, so that outcomes are
+/// comparable with those of the original program. Programs which cannot be
+/// optimized are run unchanged.
+#[derive(Clone, Debug)]
+pub struct OptimizedBackend<B> {
+    inner: B,
+    name: String,
+}
+
+impl<B: Backend> OptimizedBackend<B> {
+    /// Optimizes programs before running them on `inner`
+    pub fn new(inner: B) -> Self {
+        let name = format!("{} (optimized)", inner.name());
+        Self { inner, name }
+    }
+}
+
+impl<B: Backend> Backend for OptimizedBackend<B> {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn evaluate(&self, program: &Memory, inputs: &[Word], max_steps: usize) -> Outcome {
+        let optimized = match optimize(program) {
+            Ok(optimized) => optimized,
+            Err(_) => return self.inner.evaluate(program, inputs, max_steps),
+        };
+
+        let mut outcome = self.inner.evaluate(&optimized.memory, inputs, max_steps);
+        match &mut outcome.ending {
+            Ending::Halted(memory) | Ending::StepLimit(memory) | Ending::Failed(_, _, memory) => {
+                optimized.restore(memory)
+            }
+        }
+        outcome
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{optimize, NotStatic, OptimizedBackend};
+    use crate::{Address, Differential, Memory, SyncBackend, Word};
+    use anyhow::Result;
+    use pretty_assertions::assert_eq;
+
+    fn differential() -> Differential {
+        Differential::new()
+            .backend(SyncBackend::fresh())
+            .backend(OptimizedBackend::new(SyncBackend::fresh()))
+    }
+
+    #[test]
+    fn folds_threads_and_clears() -> Result<()> {
+        crate::init_logging();
+        // Jumps to a jump to an output of 1 + 2, followed by an unused word
+        let memory: Memory = "1105,1,3,1105,1,6,1101,1,2,13,4,13,99,0,42".parse()?;
+
+        let optimized = optimize(&memory)?;
+        assert_eq!(
+            "1105,1,6,1105,1,6,1101,3,0,13,4,13,99,0",
+            optimized
+                .memory
+                .iter()
+                .map(|w| w.to_string())
+                .collect::<Vec<_>>()
+                .join(",")
+        );
+        assert_eq!(
+            "folded 1, threaded 1, cleared 1; 15 -> 14 words",
+            optimized.report.to_string()
+        );
+        assert_eq!(1, optimized.report.size_saved());
+        assert_eq!(1, optimized.step_savings(&memory, &[])?.saved());
+
+        let outcome = differential()
+            .check(&memory, &[])
+            .unwrap_or_else(|m| panic!("{}", m));
+        assert_eq!(vec![3], outcome.outputs);
+
+        Ok(())
+    }
+
+    #[test]
+    fn leaves_modified_code_alone() -> Result<()> {
+        crate::init_logging();
+        // Redirects the second addition to write to address 3 before it runs
+        let memory: Memory = "1101,1,2,7,1101,5,5,0,4,3,99".parse()?;
+
+        let optimized = optimize(&memory)?;
+        assert_eq!(0, optimized.report.folded);
+        assert!(!optimized.report.complete_cfg);
+        assert_eq!(memory, optimized.memory);
+
+        let outcome = differential()
+            .check(&memory, &[])
+            .unwrap_or_else(|m| panic!("{}", m));
+        assert_eq!(vec![10], outcome.outputs);
+
+        Ok(())
+    }
+
+    #[test]
+    fn leaves_code_written_after_it_is_decoded_alone() -> Result<()> {
+        crate::init_logging();
+        // The halt at 11 is decoded before the write which turns it into a
+        // jump to an output of 2 + 3
+        let memory: Memory =
+            "1101,2,3,40,1005,41,11,1101,0,1105,11,99,1,20,0,0,0,0,0,0,4,2,99".parse()?;
+
+        let optimized = optimize(&memory)?;
+        assert!(!optimized.report.complete_cfg);
+        assert_eq!(memory, optimized.memory);
+
+        let outcome = differential()
+            .check(&memory, &[])
+            .unwrap_or_else(|m| panic!("{}", m));
+        assert_eq!(vec![3], outcome.outputs);
+
+        Ok(())
+    }
+
+    #[test]
+    fn rejects_programs_which_cannot_be_analysed() -> Result<()> {
+        crate::init_logging();
+        let relative: Memory = "109,1,204,-1,99".parse()?;
+        assert_eq!(
+            Err(NotStatic::RelativeAccess {
+                pc: Address::new(2)
+            }),
+            optimize(&relative).map(|o| o.report)
+        );
+
+        let patched: Memory = "1101,99,0,4,0".parse()?;
+        assert_eq!(
+            Err(NotStatic::SelfModifying {
+                pc: Address::new(4)
+            }),
+            optimize(&patched).map(|o| o.report)
+        );
+
+        // Jumps to the address held in 9, which was just written
+        let computed: Memory = "1101,7,0,9,5,10,9,99,99,0,1".parse()?;
+        assert_eq!(
+            Err(NotStatic::ComputedJump {
+                pc: Address::new(4)
+            }),
+            optimize(&computed).map(|o| o.report)
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn rejects_jumps_through_written_operands() -> Result<()> {
+        crate::init_logging();
+        // Day 7 reads its phase into the operand of a jump through a table
+        let memory: Memory = include_str!("../../inputs/input-07").parse()?;
+        assert_eq!(
+            Err(NotStatic::SelfModifying {
+                pc: Address::new(6)
+            }),
+            optimize(&memory).map(|o| o.report)
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn puzzle_inputs_are_unchanged_by_optimization() -> Result<()> {
+        crate::init_logging();
+        let relative = |pc| {
+            Err(NotStatic::RelativeAccess {
+                pc: Address::new(pc),
+            })
+        };
+        let modifying = |pc| {
+            Err(NotStatic::SelfModifying {
+                pc: Address::new(pc),
+            })
+        };
+        // The instructions folded and words saved by each program which can
+        // be optimized
+        type Expected = Result<(usize, usize), NotStatic>;
+        let programs: &[(&str, &[Word], Expected)] = &[
+            (include_str!("../../inputs/input-02"), &[], Ok((0, 0))),
+            (include_str!("../../inputs/input-05"), &[5], modifying(6)),
+            (include_str!("../../inputs/input-07"), &[4, 0], modifying(6)),
+            (include_str!("../../inputs/input-09"), &[1], relative(17)),
+            (
+                include_str!("../../inputs/input-11"),
+                &[0, 1, 0],
+                relative(344),
+            ),
+            (include_str!("../../inputs/input-13"), &[], relative(22)),
+            (
+                include_str!("../../inputs/input-15"),
+                &[1, 2, 3, 4],
+                Ok((0, 0)),
+            ),
+            (include_str!("../../inputs/input-17"), &[], relative(51)),
+            (include_str!("../../inputs/input-19"), &[3, 4], relative(2)),
+            (include_str!("../../inputs/input-21"), &[], relative(2)),
+            (
+                include_str!("../../inputs/input-23"),
+                &[7, -1],
+                modifying(8),
+            ),
+            (include_str!("../../inputs/input-25"), &[], relative(2)),
+        ];
+
+        for (source, inputs, expected) in programs {
+            let memory: Memory = source.parse()?;
+            let optimized = optimize(&memory);
+            assert_eq!(
+                *expected,
+                optimized
+                    .as_ref()
+                    .map(|o| (o.report.folded, o.report.size_saved()))
+                    .map_err(|e| *e)
+            );
+
+            // Rejected programs would run unchanged, so comparing them
+            // proves nothing
+            if optimized.is_ok() {
+                differential()
+                    .check(&memory, inputs)
+                    .unwrap_or_else(|m| panic!("{}", m));
+            }
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,289 @@
+use super::{ErrorKind, Executable, ExecutionError, Image, Word};
+use num_traits::ToPrimitive;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::{fmt, io, sync::mpsc::channel};
+
+/// The direction of a recorded event
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum EventKind {
+    /// Text sent to the program
+    Input,
+    /// Text received from the program
+    Output,
+}
+
+impl EventKind {
+    fn code(self) -> &'static str {
+        match self {
+            EventKind::Input => "i",
+            EventKind::Output => "o",
+        }
+    }
+}
+
+/// Text sent or received at a point in a terminal session
+#[derive(Clone, Debug, PartialEq)]
+pub struct RecordedEvent {
+    /// Seconds since the session started
+    pub time: f64,
+    /// Whether the text was sent or received
+    pub kind: EventKind,
+    /// The text, one line at a time
+    pub data: String,
+}
+
+#[derive(Serialize, Deserialize)]
+struct RawEvent(f64, String, String);
+
+/// A timestamped transcript of an `AsciiTerminal` session
+///
+/// Recordings are stored in the asciinema v2 format, so they can be played
+/// back with `asciinema play`. Input is recorded as `"i"` events, which
+/// asciinema ignores during playback.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Recording {
+    events: Vec<RecordedEvent>,
+}
+
+impl Recording {
+    /// The recorded events, in order
+    pub fn events(&self) -> &[RecordedEvent] {
+        &self.events
+    }
+
+    /// All text sent to the program
+    pub fn input(&self) -> String {
+        self.text(EventKind::Input)
+    }
+
+    /// All text received from the program
+    pub fn output(&self) -> String {
+        self.text(EventKind::Output)
+    }
+
+    pub(crate) fn push(&mut self, time: f64, kind: EventKind, data: String) {
+        self.events.push(RecordedEvent { time, kind, data });
+    }
+
+    fn text(&self, kind: EventKind) -> String {
+        self.events
+            .iter()
+            .filter(|e| e.kind == kind)
+            .map(|e| e.data.as_str())
+            .collect()
+    }
+
+    /// Writes the recording in asciinema v2 format
+    pub fn to_writer(&self, mut output: impl io::Write) -> io::Result<()> {
+        let header = json!({ "version": 2, "width": 80, "height": 24 });
+        serde_json::to_writer(&mut output, &header)?;
+        output.write_all(b"\n")?;
+
+        for event in &self.events {
+            let raw = RawEvent(
+                event.time,
+                event.kind.code().to_string(),
+                event.data.clone(),
+            );
+            serde_json::to_writer(&mut output, &raw)?;
+            output.write_all(b"\n")?;
+        }
+
+        Ok(())
+    }
+
+    /// Reads a recording in asciinema v2 format
+    ///
+    /// Events other than input and output are ignored.
+    pub fn from_reader(input: impl io::BufRead) -> io::Result<Self> {
+        let mut lines = input.lines();
+        let header: serde_json::Value = match lines.next() {
+            Some(line) => serde_json::from_str(&line?)?,
+            None => {
+                return Err(io::Error::new(
+                    io::ErrorKind::UnexpectedEof,
+                    "missing header",
+                ))
+            }
+        };
+        if header["version"] != 2 {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "unsupported recording version",
+            ));
+        }
+
+        let mut recording = Self::default();
+        for line in lines {
+            let line = line?;
+            if line.trim().is_empty() {
+                continue;
+            }
+
+            let RawEvent(time, code, data) = serde_json::from_str(&line)?;
+            let kind = match code.as_str() {
+                "i" => EventKind::Input,
+                "o" => EventKind::Output,
+                _ => continue,
+            };
+            recording.push(time, kind, data);
+        }
+
+        Ok(recording)
+    }
+
+    /// Re-runs `program` with the recorded input and compares its output
+    /// with the recording
+    ///
+    /// Returns the first line of output which differs, or `None` if the
+    /// output is identical. A session which ended while the program was
+    /// waiting for more input is replayed in the same way.
+    pub fn replay(
+        &self,
+        program: impl Into<Image>,
+    ) -> Result<Option<TranscriptDiff>, ExecutionError> {
+        let mut exe = Executable::from(program.into());
+        let (tx, rx) = channel();
+        for b in self.input().bytes() {
+            tx.send(Word::from(b))
+                .expect("receiver is held by the executable");
+        }
+        drop(tx);
+        exe.pipe_inputs_from(rx);
+
+        let drain = exe.drain();
+        let result = exe.run().map(|_| ());
+        drop(exe);
+        let actual: String = drain.to_vec().into_iter().map(render_ascii).collect();
+
+        match result {
+            Err(e) if e.kind() != ErrorKind::UnexpectedEndOfInput => return Err(e),
+            _ => {}
+        }
+
+        Ok(TranscriptDiff::between(&self.output(), &actual))
+    }
+}
+
+/// The first line at which replayed output differs from a recording
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TranscriptDiff {
+    /// The line number, counting from 1
+    pub line: usize,
+    /// The recorded line, or `None` if the recording ended first
+    pub expected: Option<String>,
+    /// The replayed line, or `None` if the replay ended first
+    pub actual: Option<String>,
+}
+
+impl TranscriptDiff {
+    fn between(expected: &str, actual: &str) -> Option<Self> {
+        let mut expected_lines = expected.split_inclusive('\n');
+        let mut actual_lines = actual.split_inclusive('\n');
+        let mut line = 0;
+        loop {
+            line += 1;
+            match (expected_lines.next(), actual_lines.next()) {
+                (None, None) => return None,
+                (e, a) if e != a => {
+                    return Some(Self {
+                        line,
+                        expected: e.map(str::to_string),
+                        actual: a.map(str::to_string),
+                    })
+                }
+                _ => {}
+            }
+        }
+    }
+}
+
+impl fmt::Display for TranscriptDiff {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "output differs at line {}", self.line)?;
+        match &self.expected {
+            Some(l) => writeln!(f, "- {}", l.trim_end_matches('\n'))?,
+            None => writeln!(f, "- <end of recording>")?,
+        }
+        match &self.actual {
+            Some(l) => write!(f, "+ {}", l.trim_end_matches('\n')),
+            None => write!(f, "+ <end of output>"),
+        }
+    }
+}
+
+/// The character an output value stands for, if any
+///
+/// Any value which fits in a byte is taken as a character.
+pub(crate) fn ascii_char(w: Word) -> Option<char> {
+    w.to_u8().map(char::from)
+}
+
+/// Renders an output value as it appears on a terminal
+pub(crate) fn render_ascii(w: Word) -> String {
+    match ascii_char(w) {
+        Some(ch) => ch.to_string(),
+        None => format!("Non-ASCII value received: {}\n", w),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{EventKind, Recording};
+    use crate::Memory;
+    use anyhow::Result;
+    use pretty_assertions::assert_eq;
+
+    /// Prints "?", then echoes input back in upper case forever
+    const SHOUT: &str =
+        "104,63,104,10,3,22,1007,22,97,23,1005,23,17,1001,22,-32,22,4,22,1105,1,4,0,0";
+
+    fn recording() -> Recording {
+        let mut recording = Recording::default();
+        recording.push(0.0, EventKind::Output, "?\n".to_string());
+        recording.push(0.5, EventKind::Input, "hi\n".to_string());
+        recording.push(0.6, EventKind::Output, "HI\n".to_string());
+        recording
+    }
+
+    #[test]
+    fn round_trips_through_asciicast() -> Result<()> {
+        crate::init_logging();
+        let recording = recording();
+
+        let mut buf = Vec::new();
+        recording.to_writer(&mut buf)?;
+        let text = String::from_utf8(buf.clone())?;
+        assert!(
+            text.starts_with(r#"{"height":24,"version":2,"width":80}"#),
+            "{}",
+            text
+        );
+        assert!(text.contains(r#"[0.5,"i","hi\n"]"#), "{}", text);
+
+        assert_eq!(recording, Recording::from_reader(&buf[..])?);
+
+        Ok(())
+    }
+
+    #[test]
+    fn replay_finds_differing_line() -> Result<()> {
+        crate::init_logging();
+        let program: Memory = SHOUT.parse()?;
+
+        let recording = recording();
+        assert_eq!(None, recording.replay(program.clone())?);
+
+        let mut altered = Recording::default();
+        altered.push(0.0, EventKind::Output, "?\n".to_string());
+        altered.push(0.5, EventKind::Input, "hi\n".to_string());
+        altered.push(0.6, EventKind::Output, "HO\n".to_string());
+        let diff = altered.replay(program)?.expect("a difference");
+
+        assert_eq!(2, diff.line);
+        assert_eq!(Some("HI\n".to_string()), diff.actual);
+
+        Ok(())
+    }
+}
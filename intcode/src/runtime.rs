@@ -0,0 +1,520 @@
+use super::{
+    emit::{Emitter, Operand},
+    ops::OpCode,
+    Memory, Module, Word,
+};
+
+/// The longest decimal number, with its sign and terminating zero
+const NUMBER_BUFFER: usize = 21;
+
+/// The number of words `heap_start` reserves for the stack, ahead of the heap
+pub const STACK_SIZE: usize = 4096;
+
+/// Lays out hand-written code, with labels named in the source
+type Assembler = Emitter<&'static str>;
+
+impl Assembler {
+    /// Jumps to the address held at `cell`
+    fn ret(&mut self, cell: &'static str) {
+        self.jump_through(Operand::At(cell));
+    }
+
+    /// Calls a runtime function, which returns to `back`
+    fn call(&mut self, function: &'static str, back: &'static str) {
+        self.copy(Operand::Addr(back), Operand::At("rt_ret"));
+        self.jump(function);
+        self.place(back);
+    }
+
+    /// Copies the word at the address held in `pointer` to `to`
+    ///
+    /// Intcode has no indirect addressing, so the address is written into
+    /// the operand of the copy which follows.
+    fn load(&mut self, pointer: &'static str, to: Operand<&'static str>) {
+        let operand = self.here() + 5;
+        self.copy(Operand::At(pointer), Operand::Offset(operand));
+        self.copy(Operand::Offset(0), to);
+    }
+
+    /// Copies `from` to the address held in `pointer`
+    fn store(&mut self, from: Operand<&'static str>, pointer: &'static str) {
+        let operand = self.here() + 7;
+        self.copy(Operand::At(pointer), Operand::Offset(operand));
+        self.copy(from, Operand::Offset(0));
+    }
+
+    /// Packages the code as a module, with labels which were never placed
+    /// left as imports for the linker to resolve
+    fn module(self, name: &str, exports: &[&'static str]) -> Module {
+        let emitted = self.finish();
+        let labels = emitted.labels;
+        let module = Module::new(name, Memory::from_vec(emitted.words));
+        let module = emitted
+            .relocations
+            .into_iter()
+            .fold(module, Module::with_relocation);
+        let module = emitted
+            .unresolved
+            .into_iter()
+            .fold(module, |m, (at, label)| m.with_import(at, label));
+        exports
+            .iter()
+            .fold(module, |m, &label| m.with_export(label, labels[label]))
+    }
+}
+
+/// The runtime library, as a module to link with a program
+///
+/// Functions take their arguments in the cells `rt_arg0` and `rt_arg1`,
+/// leave any result in `rt_result`, and return by jumping to the address held
+/// in `rt_ret`. Arguments and the return address are copied on entry, so a
+/// function may be called again as soon as it returns.
+///
+/// * `malloc` allocates a block of at least `rt_arg0` words, returning its
+///   address
+/// * `free` returns the block at `rt_arg0` to the heap, doing nothing if it
+///   is `0`
+/// * `print_string` outputs the zero-terminated string at `rt_arg0`
+/// * `format_number` writes `rt_arg0` in decimal as a zero-terminated string
+///   at `rt_arg1`, which must have room for 21 words, and returns the number
+///   of characters written
+/// * `print_number` outputs `rt_arg0` in decimal, returning the number of
+///   characters output
+///
+/// The heap begins at `heap_start`, exported by the module from
+/// `heap_start()`, which must be linked after every other module. That
+/// module first reserves `STACK_SIZE` words for the program's stack, so a
+/// stack based at `stack_start` never meets the heap. The heap grows
+/// upwards as blocks are allocated, one word of header ahead of each block.
+/// Memory past the end of a program reads as zero and is only allocated once
+/// written, so a new block from the end of the heap is zeroed and costs
+/// nothing until used. Freed blocks are kept on a list and handed out whole
+/// to the first later request they can hold, so their contents are
+/// unspecified.
+///
+/// Pointers are followed by writing them into the instructions which use
+/// them, leaving the relative base to the program.
+///
+/// ## Example
+///
+/// ```
+/// use intcode::{heap_start, runtime_library, Executable, Linker, Memory, Module};
+///
+/// // Prints the number in the word after the halt instruction
+/// let main: Memory = "1001,12,0,0,1101,11,0,0,1105,1,0,99,-1024".parse().expect("valid data");
+/// let main = Module::new("main", main)
+///     .with_code(0..12)
+///     .expect("valid code")
+///     .with_relocation(5)
+///     .with_import(3, "rt_arg0")
+///     .with_import(7, "rt_ret")
+///     .with_import(10, "print_number");
+///
+/// let linked = Linker::default()
+///     .with_module(main)
+///     .with_module(runtime_library())
+///     .with_module(heap_start())
+///     .link()
+///     .expect("all symbols are defined");
+///
+/// let mut exe = Executable::from(linked.into_memory());
+/// let drain = exe.drain();
+/// exe.run().expect("successful execution");
+/// drop(exe);
+/// let text: String = drain.to_vec().into_iter().map(|c| c as u8 as char).collect();
+/// assert_eq!("-1024", text);
+/// ```
+pub fn runtime_library() -> Module {
+    use Operand::*;
+
+    let mut a = Assembler::default();
+
+    a.place("malloc");
+    a.copy(At("rt_ret"), At("malloc_ret"));
+    a.copy(At("rt_arg0"), At("malloc_size"));
+    // Every block can hold the link to the next free block
+    a.emit(
+        OpCode::LessThan,
+        &[At("malloc_size"), Imm(1), At("malloc_tmp")],
+    );
+    a.jump_unless(At("malloc_tmp"), "malloc_sized");
+    a.copy(Imm(1), At("malloc_size"));
+    a.place("malloc_sized");
+    a.copy(Imm(0), At("malloc_prev"));
+    a.copy(At("free_list"), At("malloc_block"));
+    a.place("malloc_search");
+    a.jump_unless(At("malloc_block"), "malloc_bump");
+    a.emit(
+        OpCode::Add,
+        &[At("malloc_block"), Imm(-1), At("malloc_tmp")],
+    );
+    a.load("malloc_tmp", At("malloc_tmp"));
+    a.emit(
+        OpCode::LessThan,
+        &[At("malloc_tmp"), At("malloc_size"), At("malloc_tmp")],
+    );
+    a.jump_unless(At("malloc_tmp"), "malloc_reuse");
+    a.copy(At("malloc_block"), At("malloc_prev"));
+    a.load("malloc_block", At("malloc_block"));
+    a.jump("malloc_search");
+    a.place("malloc_reuse");
+    a.load("malloc_block", At("malloc_tmp"));
+    a.jump_if(At("malloc_prev"), "malloc_unlink");
+    a.copy(At("malloc_tmp"), At("free_list"));
+    a.jump("malloc_found");
+    a.place("malloc_unlink");
+    a.store(At("malloc_tmp"), "malloc_prev");
+    a.place("malloc_found");
+    a.copy(At("malloc_block"), At("rt_result"));
+    a.ret("malloc_ret");
+    a.place("malloc_bump");
+    a.store(At("malloc_size"), "brk");
+    a.emit(OpCode::Add, &[At("brk"), Imm(1), At("rt_result")]);
+    a.emit(
+        OpCode::Add,
+        &[At("rt_result"), At("malloc_size"), At("brk")],
+    );
+    a.ret("malloc_ret");
+
+    a.place("free");
+    a.copy(At("rt_ret"), At("free_ret"));
+    a.jump_unless(At("rt_arg0"), "free_done");
+    a.store(At("free_list"), "rt_arg0");
+    a.copy(At("rt_arg0"), At("free_list"));
+    a.place("free_done");
+    a.ret("free_ret");
+
+    a.place("print_string");
+    a.copy(At("rt_ret"), At("print_ret"));
+    a.copy(At("rt_arg0"), At("print_ptr"));
+    a.place("print_next");
+    a.load("print_ptr", At("print_char"));
+    a.jump_unless(At("print_char"), "print_done");
+    a.emit(OpCode::Output, &[At("print_char")]);
+    a.emit(OpCode::Add, &[At("print_ptr"), Imm(1), At("print_ptr")]);
+    a.jump("print_next");
+    a.place("print_done");
+    a.ret("print_ret");
+
+    a.place("format_number");
+    a.copy(At("rt_ret"), At("format_ret"));
+    a.copy(At("rt_arg0"), At("format_value"));
+    a.copy(At("rt_arg1"), At("format_out"));
+    // Digits are counted off a non-positive value, which can hold the
+    // negation of any value
+    a.emit(
+        OpCode::LessThan,
+        &[At("format_value"), Imm(0), At("format_tmp")],
+    );
+    a.jump_unless(At("format_tmp"), "format_positive");
+    a.store(Imm(Word::from(b'-')), "format_out");
+    a.emit(OpCode::Add, &[At("format_out"), Imm(1), At("format_out")]);
+    a.jump("format_digits");
+    a.place("format_positive");
+    a.emit(
+        OpCode::Mul,
+        &[At("format_value"), Imm(-1), At("format_value")],
+    );
+    a.place("format_digits");
+    a.copy(Addr("powers"), At("format_power"));
+    a.copy(Imm(0), At("format_started"));
+    a.place("format_place");
+    a.load("format_power", At("format_unit"));
+    a.copy(Imm(Word::from(b'0')), At("format_digit"));
+    a.place("format_count");
+    a.emit(
+        OpCode::Add,
+        &[At("format_value"), At("format_unit"), At("format_tmp")],
+    );
+    a.emit(
+        OpCode::LessThan,
+        &[At("format_tmp"), Imm(1), At("format_cond")],
+    );
+    a.jump_unless(At("format_cond"), "format_emit");
+    a.copy(At("format_tmp"), At("format_value"));
+    a.emit(
+        OpCode::Add,
+        &[At("format_digit"), Imm(1), At("format_digit")],
+    );
+    a.jump("format_count");
+    a.place("format_emit");
+    // Leading zeroes are skipped, but the units are always written
+    a.jump_if(At("format_started"), "format_write");
+    a.emit(
+        OpCode::Equal,
+        &[At("format_digit"), Imm(Word::from(b'0')), At("format_cond")],
+    );
+    a.jump_unless(At("format_cond"), "format_write");
+    a.emit(
+        OpCode::Equal,
+        &[At("format_unit"), Imm(1), At("format_cond")],
+    );
+    a.jump_unless(At("format_cond"), "format_skip");
+    a.place("format_write");
+    a.store(At("format_digit"), "format_out");
+    a.emit(OpCode::Add, &[At("format_out"), Imm(1), At("format_out")]);
+    a.copy(Imm(1), At("format_started"));
+    a.place("format_skip");
+    a.emit(
+        OpCode::Add,
+        &[At("format_power"), Imm(1), At("format_power")],
+    );
+    a.emit(
+        OpCode::Equal,
+        &[At("format_unit"), Imm(1), At("format_cond")],
+    );
+    a.jump_unless(At("format_cond"), "format_place");
+    a.store(Imm(0), "format_out");
+    a.emit(OpCode::Mul, &[At("rt_arg1"), Imm(-1), At("format_tmp")]);
+    a.emit(
+        OpCode::Add,
+        &[At("format_out"), At("format_tmp"), At("rt_result")],
+    );
+    a.ret("format_ret");
+
+    a.place("print_number");
+    a.copy(At("rt_ret"), At("number_ret"));
+    a.copy(Addr("number_buffer"), At("rt_arg1"));
+    a.call("format_number", "number_formatted");
+    a.copy(Addr("number_buffer"), At("rt_arg0"));
+    a.call("print_string", "number_printed");
+    a.ret("number_ret");
+
+    // Keeps a stray jump from running into the data
+    a.emit(OpCode::Halt, &[]);
+    for cell in &["rt_arg0", "rt_arg1", "rt_result", "rt_ret"] {
+        a.data(cell, &[0]);
+    }
+    a.pointer("brk", "heap_start");
+    a.data("free_list", &[0]);
+    for cell in &[
+        "malloc_ret",
+        "malloc_size",
+        "malloc_prev",
+        "malloc_block",
+        "malloc_tmp",
+        "free_ret",
+        "print_ret",
+        "print_ptr",
+        "print_char",
+        "format_ret",
+        "format_value",
+        "format_out",
+        "format_power",
+        "format_unit",
+        "format_digit",
+        "format_started",
+        "format_tmp",
+        "format_cond",
+        "number_ret",
+    ] {
+        a.data(cell, &[0]);
+    }
+    let powers: Vec<Word> = (0..19).rev().map(|e| 10_i64.pow(e)).collect();
+    a.data("powers", &powers);
+    a.data("number_buffer", &[0; NUMBER_BUFFER]);
+
+    a.module(
+        "runtime",
+        &[
+            "malloc",
+            "free",
+            "print_string",
+            "format_number",
+            "print_number",
+            "rt_arg0",
+            "rt_arg1",
+            "rt_result",
+            "rt_ret",
+        ],
+    )
+}
+
+/// A module reserving a stack and exporting where the heap starts, to be
+/// linked last so that the heap of the runtime library begins past the end
+/// of the program
+///
+/// The stack and the heap both grow upwards, so a stack which ran on from
+/// the end of the program would grow into the heap. Instead the module holds
+/// `STACK_SIZE` words of stack, starting at `stack_start`, followed by
+/// `heap_start`. A program needing a deeper stack than that overflows into
+/// the heap.
+pub fn heap_start() -> Module {
+    Module::new("stack", Memory::from_vec(vec![0; STACK_SIZE]))
+        .with_export("stack_start", 0)
+        .with_export("heap_start", STACK_SIZE)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{heap_start, runtime_library, Assembler, STACK_SIZE};
+    use crate::{emit::Operand::*, ops::OpCode, Address, Executable, Linker, Memory, Word};
+    use anyhow::Result;
+    use pretty_assertions::assert_eq;
+
+    /// Links `main` with the runtime and runs it, returning where the heap
+    /// starts, the final memory and the outputs
+    fn run(main: Assembler) -> Result<(usize, Memory, Vec<Word>)> {
+        let linked = Linker::default()
+            .with_module(main.module("main", &[]))
+            .with_module(runtime_library())
+            .with_module(heap_start())
+            .link()?;
+        let heap = linked.symbol("heap_start").expect("heap is linked");
+        assert_eq!(linked.memory().size(), heap.value());
+        let stack = linked.symbol("stack_start").expect("stack is linked");
+        assert_eq!(stack.value() + STACK_SIZE, heap.value());
+
+        let mut exe = Executable::from(linked.into_memory());
+        let drain = exe.drain();
+        exe.run()?;
+        let memory = exe.memory().clone();
+        drop(exe);
+        Ok((heap.value(), memory, drain.to_vec()))
+    }
+
+    fn malloc(a: &mut Assembler, size: Word, into: &'static str, back: &'static str) {
+        a.copy(Imm(size), At("rt_arg0"));
+        a.call("malloc", back);
+        a.copy(At("rt_result"), At(into));
+    }
+
+    fn free(a: &mut Assembler, block: &'static str, back: &'static str) {
+        a.copy(At(block), At("rt_arg0"));
+        a.call("free", back);
+    }
+
+    fn halt_with_cells(a: &mut Assembler, cells: &[&'static str]) {
+        a.emit(OpCode::Halt, &[]);
+        for &cell in cells {
+            a.data(cell, &[0]);
+        }
+    }
+
+    #[test]
+    fn allocates_past_the_program_as_memory_is_written() -> Result<()> {
+        crate::init_logging();
+        let mut a = Assembler::default();
+        malloc(&mut a, 3, "first", "first_done");
+        malloc(&mut a, 0, "second", "second_done");
+        a.emit(OpCode::Output, &[At("first")]);
+        a.emit(OpCode::Output, &[At("second")]);
+        halt_with_cells(&mut a, &["first", "second"]);
+
+        let (heap, memory, outputs) = run(a)?;
+        // Blocks follow a word of header, and are at least a word long
+        assert_eq!(vec![heap as Word + 1, heap as Word + 5], outputs);
+        assert_eq!(3, memory.read_or_default(Address::new(heap)));
+        assert_eq!(1, memory.read_or_default(Address::new(heap + 4)));
+        // Only the headers were written, so memory ends with the last one
+        assert_eq!(heap + 5, memory.size());
+
+        Ok(())
+    }
+
+    #[test]
+    fn keeps_the_stack_clear_of_the_heap() -> Result<()> {
+        crate::init_logging();
+        let mut a = Assembler::default();
+        a.emit(OpCode::AddRel, &[Addr("stack_start")]);
+        a.copy(Imm(7), Rel(STACK_SIZE as Word - 1));
+        malloc(&mut a, 1, "block", "allocated");
+        a.store(Imm(9), "block");
+        a.emit(OpCode::Output, &[At("block")]);
+        a.emit(OpCode::Output, &[Rel(STACK_SIZE as Word - 1)]);
+        halt_with_cells(&mut a, &["block"]);
+
+        // The top of the stack is untouched by the block after it
+        let (heap, _, outputs) = run(a)?;
+        assert_eq!(vec![heap as Word + 1, 7], outputs);
+
+        Ok(())
+    }
+
+    #[test]
+    fn reuses_freed_blocks_which_fit() -> Result<()> {
+        crate::init_logging();
+        let mut a = Assembler::default();
+        malloc(&mut a, 4, "large", "large_done");
+        malloc(&mut a, 2, "small", "small_done");
+        free(&mut a, "large", "large_freed");
+        free(&mut a, "small", "small_freed");
+        // The small block heads the free list, but is passed over
+        malloc(&mut a, 3, "fits_large", "fits_large_done");
+        malloc(&mut a, 1, "fits_small", "fits_small_done");
+        malloc(&mut a, 1, "fresh", "fresh_done");
+        a.copy(Imm(0), At("rt_arg0"));
+        a.call("free", "null_freed");
+        for &cell in &["large", "small", "fits_large", "fits_small", "fresh"] {
+            a.emit(OpCode::Output, &[At(cell)]);
+        }
+        halt_with_cells(
+            &mut a,
+            &["large", "small", "fits_large", "fits_small", "fresh"],
+        );
+
+        let (heap, memory, outputs) = run(a)?;
+        let heap = heap as Word;
+        assert_eq!(
+            vec![heap + 1, heap + 6, heap + 1, heap + 6, heap + 9],
+            outputs
+        );
+        assert_eq!(heap as usize + 9, memory.size());
+
+        Ok(())
+    }
+
+    #[test]
+    fn prints_strings_and_numbers() -> Result<()> {
+        crate::init_logging();
+        let mut a = Assembler::default();
+        a.copy(Addr("greeting"), At("rt_arg0"));
+        a.call("print_string", "greeted");
+        let values = [
+            ("zero", 0),
+            ("seven", 7),
+            ("negative", -42),
+            ("million", 1_000_000),
+            ("max", Word::MAX),
+            ("min", Word::MIN),
+        ];
+        for &(back, value) in &values {
+            a.copy(Imm(value), At("rt_arg0"));
+            a.call("print_number", back);
+            a.emit(OpCode::Output, &[Imm(Word::from(b' '))]);
+        }
+        a.emit(OpCode::Halt, &[]);
+        let greeting: Vec<Word> = b"Hi!\n\0".iter().copied().map(Word::from).collect();
+        a.data("greeting", &greeting);
+
+        let (_, _, outputs) = run(a)?;
+        let text: String = outputs.into_iter().map(|c| c as u8 as char).collect();
+        assert_eq!(
+            "Hi!\n0 7 -42 1000000 9223372036854775807 -9223372036854775808 ",
+            text
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn formats_numbers_into_allocated_blocks() -> Result<()> {
+        crate::init_logging();
+        let mut a = Assembler::default();
+        malloc(&mut a, 21, "buffer", "allocated");
+        a.copy(Imm(-305), At("rt_arg0"));
+        a.copy(At("buffer"), At("rt_arg1"));
+        a.call("format_number", "formatted");
+        a.emit(OpCode::Output, &[At("rt_result")]);
+        halt_with_cells(&mut a, &["buffer"]);
+
+        let (heap, memory, outputs) = run(a)?;
+        assert_eq!(vec![4], outputs);
+        let text: Vec<Word> = (heap + 1..heap + 6)
+            .map(|i| memory.read_or_default(Address::new(i)))
+            .collect();
+        assert_eq!(vec![45, 51, 48, 53, 0], text);
+
+        Ok(())
+    }
+}
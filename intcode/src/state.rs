@@ -0,0 +1,139 @@
+use super::{Address, Decoded, Memory};
+use std::fmt;
+
+/// The registers and memory of a machine at one point in its execution
+///
+/// A state taken from one executable may be restored into any other,
+/// including one running a different program, to resume execution from the
+/// same point. Inputs which were waiting to be read and outputs which were
+/// not yet consumed are not part of the state.
+///
+/// ## Example
+///
+/// ```
+/// use intcode::{Address, Executable, Memory};
+///
+/// let memory: Memory = "1001,5,1,5,99,0".parse().expect("valid data");
+/// let mut exe = Executable::from(memory);
+///
+/// let before = exe.state();
+/// exe.step().expect("valid instruction");
+/// assert_eq!(1, exe.memory().read_or_default(Address::new(5)));
+///
+/// exe.restore_state(before);
+/// assert_eq!(Address::ZERO, exe.pc());
+/// assert_eq!(0, exe.memory().read_or_default(Address::new(5)));
+/// ```
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct MachineState {
+    /// The address of the next instruction to execute
+    pub pc: Address,
+    /// The base address for relative mode parameters
+    pub relative_base: Address,
+    /// The number of instructions executed so far
+    pub steps: usize,
+    /// The contents of memory
+    pub memory: Memory,
+}
+
+impl fmt::Display for MachineState {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write_status(f, self.pc, self.relative_base, self.steps, &self.memory)
+    }
+}
+
+/// Writes a one-line summary of a machine, ending with the instruction it
+/// will execute next
+pub(crate) fn write_status(
+    f: &mut fmt::Formatter,
+    pc: Address,
+    relative_base: Address,
+    steps: usize,
+    memory: &Memory,
+) -> fmt::Result {
+    write!(f, "pc={} rel={} steps={}: ", pc, relative_base, steps)?;
+    match Decoded::at(memory, pc) {
+        Some(decoded) => write!(f, "{}", decoded),
+        None => f.write_str("<invalid>"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::MachineState;
+    use crate::{Address, AsyncExecutable, Executable, Memory};
+    use anyhow::Result;
+    use pretty_assertions::assert_eq;
+
+    /// Counts down from 5 using the relative base, adding each value to a
+    /// total held at address 21
+    const COUNTDOWN: &str = "109,20,21101,5,0,0,22201,0,1,1,21201,0,-1,0,1205,0,6,99";
+
+    #[test]
+    fn states_move_between_executables() -> Result<()> {
+        crate::init_logging();
+        let memory: Memory = COUNTDOWN.parse()?;
+        let expected = Executable::from(memory.clone()).execute()?;
+
+        let mut first = Executable::from(memory.clone());
+        for _ in 0..5 {
+            first.step()?;
+        }
+        let state = first.state();
+        assert_eq!(Address::new(20), state.relative_base);
+        assert_eq!(5, state.steps);
+
+        let mut second = Executable::from(memory);
+        second.restore_state(state.clone());
+        assert_eq!(state, second.state());
+        second.run()?;
+
+        assert_eq!(expected, *second.memory());
+        assert_eq!(15, expected.read_or_default(Address::new(21)));
+        assert!(second.steps() > 5);
+
+        Ok(())
+    }
+
+    #[test]
+    fn status_fits_on_one_line() -> Result<()> {
+        crate::init_logging();
+        let exe = Executable::from(COUNTDOWN.parse::<Memory>()?);
+
+        assert_eq!(
+            format!("#{} pc=0 rel=0 steps=0: add rel, $20 => rel", exe.id()),
+            exe.to_string()
+        );
+
+        let state = MachineState {
+            pc: Address::new(3),
+            relative_base: Address::ZERO,
+            steps: 1,
+            memory: Memory::from_vec(vec![0; 4]),
+        };
+        assert_eq!("pc=3 rel=0 steps=1: <invalid>", state.to_string());
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn async_executables_can_be_inspected() -> Result<()> {
+        crate::init_logging();
+        let mut exe = AsyncExecutable::from(COUNTDOWN.parse::<Memory>()?);
+
+        while exe.memory().read_or_default(Address::new(20)) != 5 {
+            exe.step().await?;
+        }
+        assert_eq!(Address::new(6), exe.pc());
+        assert_eq!(Address::new(20), exe.relative_base());
+
+        let state = exe.state();
+        exe.run().await?;
+        let total = exe.memory().read_or_default(Address::new(21));
+        exe.restore_state(state);
+        exe.run().await?;
+        assert_eq!(total, exe.memory().read_or_default(Address::new(21)));
+
+        Ok(())
+    }
+}
@@ -0,0 +1,887 @@
+use super::{
+    ops::{Instruction, OpCode, ParameterMode},
+    Address, Memory, Word,
+};
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    convert::TryFrom,
+    fmt,
+};
+
+/// An unknown value which a program's behaviour depends on
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Var {
+    /// The value of the nth input, counting from 0
+    Input(usize),
+    /// The value initially held at an address
+    Cell(Address),
+}
+
+impl fmt::Display for Var {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Var::Input(n) => write!(f, "in{}", n),
+            Var::Cell(address) => write!(f, "[{}]", address),
+        }
+    }
+}
+
+/// A constant plus a sum of variables, each multiplied by a constant
+#[derive(Clone, Debug, Default, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Linear {
+    constant: Word,
+    terms: BTreeMap<Var, Word>,
+}
+
+impl Linear {
+    /// A constant value
+    pub fn constant(value: Word) -> Self {
+        Self {
+            constant: value,
+            terms: BTreeMap::new(),
+        }
+    }
+
+    /// A single variable
+    pub fn var(var: Var) -> Self {
+        let mut terms = BTreeMap::new();
+        terms.insert(var, 1);
+        Self { constant: 0, terms }
+    }
+
+    /// The value, if it does not depend on any variable
+    pub fn as_constant(&self) -> Option<Word> {
+        if self.terms.is_empty() {
+            Some(self.constant)
+        } else {
+            None
+        }
+    }
+
+    /// The constant part of the value
+    pub fn constant_term(&self) -> Word {
+        self.constant
+    }
+
+    /// The multiplier of `var`, which is `0` if the value does not depend
+    /// on it
+    pub fn coefficient(&self, var: Var) -> Word {
+        self.terms.get(&var).copied().unwrap_or(0)
+    }
+
+    /// Iterates over the variables the value depends on, with their
+    /// multipliers
+    pub fn terms(&self) -> impl Iterator<Item = (Var, Word)> + '_ {
+        self.terms.iter().map(|(v, c)| (*v, *c))
+    }
+
+    /// Computes the value given values for its variables
+    ///
+    /// Returns `None` if a variable has no value.
+    pub fn evaluate(&self, values: &BTreeMap<Var, Word>) -> Option<Word> {
+        self.terms.iter().try_fold(self.constant, |sum, (var, c)| {
+            values
+                .get(var)
+                .map(|v| sum.wrapping_add(c.wrapping_mul(*v)))
+        })
+    }
+
+    fn add(&self, other: &Self) -> Self {
+        let mut sum = self.clone();
+        sum.constant = sum.constant.wrapping_add(other.constant);
+        for (var, c) in &other.terms {
+            let entry = sum.terms.entry(*var).or_insert(0);
+            *entry = entry.wrapping_add(*c);
+            if *entry == 0 {
+                sum.terms.remove(var);
+            }
+        }
+        sum
+    }
+
+    fn scale(&self, factor: Word) -> Self {
+        if factor == 0 {
+            return Self::constant(0);
+        }
+        Self {
+            constant: self.constant.wrapping_mul(factor),
+            terms: self
+                .terms
+                .iter()
+                .map(|(v, c)| (*v, c.wrapping_mul(factor)))
+                .collect(),
+        }
+    }
+
+    fn mul(&self, other: &Self) -> Option<Self> {
+        match (self.as_constant(), other.as_constant()) {
+            (Some(c), _) => Some(other.scale(c)),
+            (_, Some(c)) => Some(self.scale(c)),
+            (None, None) => None,
+        }
+    }
+
+    fn sub(&self, other: &Self) -> Self {
+        self.add(&other.scale(-1))
+    }
+}
+
+impl fmt::Display for Linear {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mut first = true;
+        for (var, &c) in &self.terms {
+            match (first, c < 0) {
+                (true, true) => f.write_str("-")?,
+                (true, false) => {}
+                (false, true) => f.write_str(" - ")?,
+                (false, false) => f.write_str(" + ")?,
+            }
+            if c.abs() != 1 {
+                write!(f, "{}*", c.abs())?;
+            }
+            var.fmt(f)?;
+            first = false;
+        }
+        match (first, self.constant) {
+            (true, c) => c.fmt(f),
+            (false, 0) => Ok(()),
+            (false, c) if c < 0 => write!(f, " - {}", c.abs()),
+            (false, c) => write!(f, " + {}", c),
+        }
+    }
+}
+
+/// A value computed along a path
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Value {
+    /// A linear combination of variables
+    Linear(Linear),
+    /// A value which cannot be described, such as one read through an
+    /// address which depends on a variable
+    Unknown,
+}
+
+impl Value {
+    fn constant(value: Word) -> Self {
+        Value::Linear(Linear::constant(value))
+    }
+
+    /// The value, if it is known and does not depend on any variable
+    pub fn as_constant(&self) -> Option<Word> {
+        match self {
+            Value::Linear(l) => l.as_constant(),
+            Value::Unknown => None,
+        }
+    }
+}
+
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Value::Linear(l) => l.fmt(f),
+            Value::Unknown => f.write_str("?"),
+        }
+    }
+}
+
+/// A condition on the variables which held for a path to be taken
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Constraint {
+    Zero(Linear),
+    NonZero(Linear),
+    /// Recorded for `a < b` as `a - b < 0`, which only agrees with the
+    /// comparison when the subtraction does not overflow
+    Negative(Linear),
+    NonNegative(Linear),
+}
+
+impl Constraint {
+    fn negate(&self) -> Self {
+        match self {
+            Constraint::Zero(l) => Constraint::NonZero(l.clone()),
+            Constraint::NonZero(l) => Constraint::Zero(l.clone()),
+            Constraint::Negative(l) => Constraint::NonNegative(l.clone()),
+            Constraint::NonNegative(l) => Constraint::Negative(l.clone()),
+        }
+    }
+
+    /// Whether the condition holds given values for its variables
+    ///
+    /// Returns `None` if a variable has no value.
+    pub fn holds(&self, values: &BTreeMap<Var, Word>) -> Option<bool> {
+        Some(match self {
+            Constraint::Zero(l) => l.evaluate(values)? == 0,
+            Constraint::NonZero(l) => l.evaluate(values)? != 0,
+            Constraint::Negative(l) => l.evaluate(values)? < 0,
+            Constraint::NonNegative(l) => l.evaluate(values)? >= 0,
+        })
+    }
+}
+
+impl fmt::Display for Constraint {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Constraint::Zero(l) => write!(f, "{} == 0", l),
+            Constraint::NonZero(l) => write!(f, "{} != 0", l),
+            Constraint::Negative(l) => write!(f, "{} < 0", l),
+            Constraint::NonNegative(l) => write!(f, "{} >= 0", l),
+        }
+    }
+}
+
+/// Why exploration of a path stopped
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum End {
+    /// The program halted
+    Halted,
+    /// The path ran for more steps than allowed
+    StepLimit,
+    /// The instruction at `pc` is invalid
+    InvalidInstruction { pc: Address },
+    /// Two values which depend on variables were multiplied
+    NonLinear { pc: Address },
+    /// A comparison or branch depends on an unknown value
+    UnknownCondition { pc: Address },
+    /// An instruction, jump target, write target or relative base depends on
+    /// a variable
+    SymbolicAddress { pc: Address },
+}
+
+impl fmt::Display for End {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            End::Halted => f.write_str("halted"),
+            End::StepLimit => f.write_str("step limit reached"),
+            End::InvalidInstruction { pc } => write!(f, "invalid instruction; pc = {}", pc),
+            End::NonLinear { pc } => write!(f, "non-linear multiplication; pc = {}", pc),
+            End::UnknownCondition { pc } => write!(f, "condition on unknown value; pc = {}", pc),
+            End::SymbolicAddress { pc } => write!(f, "address depends on input; pc = {}", pc),
+        }
+    }
+}
+
+/// One way through a program, and what it computed
+#[derive(Clone, Debug)]
+pub struct Path {
+    /// Conditions on the variables under which this path is taken
+    pub constraints: Vec<Constraint>,
+    /// Values output along the path, in order
+    pub outputs: Vec<Value>,
+    /// The number of inputs read along the path
+    pub inputs: usize,
+    /// Why the path ended
+    pub end: End,
+    /// The number of instructions executed along the path
+    pub steps: usize,
+    memory: Memory,
+    symbolic: BTreeMap<Address, Value>,
+}
+
+impl Path {
+    /// The value held at `address` when the path ended
+    pub fn read(&self, address: Address) -> Value {
+        match self.symbolic.get(&address) {
+            Some(value) => value.clone(),
+            None => Value::constant(self.memory.read_or_default(address)),
+        }
+    }
+
+    /// Whether the path is taken given values for its variables
+    ///
+    /// Returns `None` if a variable has no value.
+    pub fn accepts(&self, values: &BTreeMap<Var, Word>) -> Option<bool> {
+        self.constraints
+            .iter()
+            .try_fold(true, |all, c| Some(all && c.holds(values)?))
+    }
+}
+
+impl fmt::Display for Path {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} after {} steps", self.end, self.steps)?;
+        if !self.constraints.is_empty() {
+            f.write_str(" when ")?;
+            for (i, c) in self.constraints.iter().enumerate() {
+                if i > 0 {
+                    f.write_str(" && ")?;
+                }
+                c.fmt(f)?;
+            }
+        }
+        if !self.outputs.is_empty() {
+            f.write_str("; output ")?;
+            for (i, o) in self.outputs.iter().enumerate() {
+                if i > 0 {
+                    f.write_str(", ")?;
+                }
+                o.fmt(f)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Every path found through a program
+#[derive(Clone, Debug)]
+pub struct Exploration {
+    /// Paths, in the order they were completed
+    pub paths: Vec<Path>,
+    /// Whether some paths were abandoned because there were too many
+    pub truncated: bool,
+}
+
+impl Exploration {
+    /// Paths which ended with the program halting
+    pub fn halted(&self) -> impl Iterator<Item = &Path> + '_ {
+        self.paths.iter().filter(|p| p.end == End::Halted)
+    }
+}
+
+/// Runs a program with inputs, and optionally some memory, treated as
+/// variables rather than values
+///
+/// Values are tracked as linear combinations of the variables through
+/// additions and multiplications by constants. When a comparison or branch
+/// depends on a variable, exploration forks, and each side records the
+/// condition under which it is taken. Conditions are not checked for
+/// satisfiability, beyond spotting a test which repeats an earlier one, so
+/// some paths may be impossible.
+///
+/// This is enough to solve simple programs by inverting their outputs rather
+/// than running them for every possible input.
+///
+/// ## Example
+///
+/// ```
+/// use intcode::{Address, Linear, Symbolic, Var};
+///
+/// // Reads a value, doubles it, and adds the value held at address 11
+/// let program = "3,12,1002,12,2,12,1,12,11,12,99,5,0".parse().expect("valid data");
+///
+/// let exploration = Symbolic::new(program)
+///     .with_symbolic_cell(Address::new(11))
+///     .explore();
+///
+/// let path = &exploration.paths[0];
+/// assert_eq!("2*in0 + [11]", path.read(Address::new(12)).to_string());
+/// ```
+#[derive(Clone, Debug)]
+pub struct Symbolic {
+    memory: Memory,
+    cells: BTreeSet<Address>,
+    max_paths: usize,
+    max_steps: usize,
+}
+
+impl Symbolic {
+    /// Prepares to explore a program, with only its inputs as variables
+    pub fn new(memory: Memory) -> Self {
+        Self {
+            memory,
+            cells: BTreeSet::new(),
+            max_paths: 1024,
+            max_steps: 100_000,
+        }
+    }
+
+    /// Treats the initial value at `address` as a variable, as though the
+    /// program were patched before running
+    pub fn with_symbolic_cell(mut self, address: Address) -> Self {
+        self.cells.insert(address);
+        self
+    }
+
+    /// Limits the number of paths explored, which is 1024 by default
+    pub fn with_max_paths(mut self, max_paths: usize) -> Self {
+        self.max_paths = max_paths;
+        self
+    }
+
+    /// Limits the number of steps along each path, which is 100,000 by
+    /// default
+    pub fn with_max_steps(mut self, max_steps: usize) -> Self {
+        self.max_steps = max_steps;
+        self
+    }
+
+    /// Follows every path through the program
+    pub fn explore(&self) -> Exploration {
+        let initial = State {
+            memory: self.memory.clone(),
+            symbolic: self
+                .cells
+                .iter()
+                .map(|&a| (a, Value::Linear(Linear::var(Var::Cell(a)))))
+                .collect(),
+            pc: 0,
+            rel: 0,
+            steps: 0,
+            inputs: 0,
+            outputs: Vec::new(),
+            constraints: Vec::new(),
+            known: BTreeSet::new(),
+        };
+
+        let mut pending = vec![initial];
+        let mut paths = Vec::new();
+        let mut started = 1;
+        let mut truncated = false;
+        while let Some(mut state) = pending.pop() {
+            let end = loop {
+                if state.steps >= self.max_steps {
+                    break End::StepLimit;
+                }
+                match state.step() {
+                    Step::Continue => {}
+                    Step::Fork(other) => {
+                        if started < self.max_paths {
+                            started += 1;
+                            pending.push(*other);
+                        } else {
+                            truncated = true;
+                        }
+                    }
+                    Step::End(end) => break end,
+                }
+            };
+            paths.push(state.finish(end));
+        }
+
+        Exploration { paths, truncated }
+    }
+}
+
+enum Step {
+    Continue,
+    /// Both sides of a branch are possible; the current state follows one
+    /// and the other is returned
+    Fork(Box<State>),
+    End(End),
+}
+
+#[derive(Clone)]
+struct State {
+    memory: Memory,
+    symbolic: BTreeMap<Address, Value>,
+    pc: usize,
+    rel: Word,
+    steps: usize,
+    inputs: usize,
+    outputs: Vec<Value>,
+    constraints: Vec<Constraint>,
+    /// The same constraints, for quick lookup
+    known: BTreeSet<Constraint>,
+}
+
+impl State {
+    fn finish(self, end: End) -> Path {
+        Path {
+            constraints: self.constraints,
+            outputs: self.outputs,
+            inputs: self.inputs,
+            end,
+            steps: self.steps,
+            memory: self.memory,
+            symbolic: self.symbolic,
+        }
+    }
+
+    fn read(&self, address: Address) -> Value {
+        match self.symbolic.get(&address) {
+            Some(value) => value.clone(),
+            None => Value::constant(self.memory.read_or_default(address)),
+        }
+    }
+
+    fn write(&mut self, address: Address, value: Value) {
+        match value.as_constant() {
+            Some(c) => {
+                self.symbolic.remove(&address);
+                self.memory.write_arbitrary(address, c);
+            }
+            None => {
+                self.symbolic.insert(address, value);
+            }
+        }
+    }
+
+    /// The address a parameter refers to, if it is in position or relative
+    /// mode
+    fn address_of(&self, mode: ParameterMode, word: &Value) -> Result<Option<Address>, End> {
+        let pc = Address::new(self.pc);
+        let offset = match mode {
+            ParameterMode::Position => 0,
+            ParameterMode::Immediate => return Ok(None),
+            ParameterMode::Relative => self.rel,
+        };
+        match word.as_constant() {
+            Some(w) => Address::try_from(offset + w)
+                .map(Some)
+                .map_err(|_| End::InvalidInstruction { pc }),
+            None => Err(End::SymbolicAddress { pc }),
+        }
+    }
+
+    fn load(&self, mode: ParameterMode, word: Value) -> Result<Value, End> {
+        match self.address_of(mode, &word) {
+            Ok(Some(address)) => Ok(self.read(address)),
+            Ok(None) => Ok(word),
+            // Reading through a symbolic address gives an unknown value,
+            // which only matters if it is used
+            Err(End::SymbolicAddress { .. }) => Ok(Value::Unknown),
+            Err(end) => Err(end),
+        }
+    }
+
+    fn store(&mut self, mode: ParameterMode, word: &Value, value: Value) -> Result<(), End> {
+        match self.address_of(mode, word)? {
+            Some(address) => {
+                self.write(address, value);
+                Ok(())
+            }
+            None => Err(End::InvalidInstruction {
+                pc: Address::new(self.pc),
+            }),
+        }
+    }
+
+    /// Whether an earlier test already decided `constraint`
+    fn decided(&self, constraint: &Constraint) -> Option<bool> {
+        if self.known.contains(constraint) {
+            Some(true)
+        } else if self.known.contains(&constraint.negate()) {
+            Some(false)
+        } else {
+            None
+        }
+    }
+
+    fn assume(&mut self, constraint: Constraint) {
+        self.known.insert(constraint.clone());
+        self.constraints.push(constraint);
+    }
+
+    /// Decides `constraint`, forking if both outcomes are possible
+    ///
+    /// The current state assumes the constraint holds, and the fork assumes
+    /// it does not.
+    fn branch(&mut self, constraint: Constraint) -> (bool, Option<Box<State>>) {
+        match self.decided(&constraint) {
+            Some(holds) => (holds, None),
+            None => {
+                let mut other = self.clone();
+                other.assume(constraint.negate());
+                self.assume(constraint);
+                (true, Some(Box::new(other)))
+            }
+        }
+    }
+
+    fn step(&mut self) -> Step {
+        match self.try_step() {
+            Ok(step) => step,
+            Err(end) => Step::End(end),
+        }
+    }
+
+    fn try_step(&mut self) -> Result<Step, End> {
+        let pc = Address::new(self.pc);
+        let op = self
+            .read(pc)
+            .as_constant()
+            .ok_or(End::SymbolicAddress { pc })?;
+        let instruction = Instruction::try_from(op).map_err(|_| End::InvalidInstruction { pc })?;
+        let opcode = instruction.opcode();
+        if opcode == OpCode::Halt {
+            return Err(End::Halted);
+        }
+        let params = usize::from(opcode.params());
+        let mode = |i: u8| instruction.param_modes().mode(i);
+        let words: Vec<Value> = (1..=params)
+            .map(|i| self.read(Address::new(self.pc + i)))
+            .collect();
+        self.steps += 1;
+        let next = self.pc + 1 + params;
+
+        let linear = |value: Value| match value {
+            Value::Linear(l) => Ok(l),
+            Value::Unknown => Err(End::UnknownCondition { pc }),
+        };
+
+        let mut fork = None;
+        match opcode {
+            OpCode::Add | OpCode::Mul => {
+                let a = self.load(mode(0), words[0].clone())?;
+                let b = self.load(mode(1), words[1].clone())?;
+                let result = match (a, b) {
+                    (Value::Linear(a), Value::Linear(b)) if opcode == OpCode::Add => {
+                        Value::Linear(a.add(&b))
+                    }
+                    (Value::Linear(a), Value::Linear(b)) => {
+                        Value::Linear(a.mul(&b).ok_or(End::NonLinear { pc })?)
+                    }
+                    _ => Value::Unknown,
+                };
+                self.store(mode(2), &words[2], result)?;
+            }
+            OpCode::Input => {
+                let value = Value::Linear(Linear::var(Var::Input(self.inputs)));
+                self.inputs += 1;
+                self.store(mode(0), &words[0], value)?;
+            }
+            OpCode::Output => {
+                let value = self.load(mode(0), words[0].clone())?;
+                self.outputs.push(value);
+            }
+            OpCode::JumpNonZero | OpCode::JumpZero => {
+                let value = linear(self.load(mode(0), words[0].clone())?)?;
+                let non_zero = match value.as_constant() {
+                    Some(c) => c != 0,
+                    None => {
+                        let (holds, other) = self.branch(Constraint::NonZero(value));
+                        fork = other;
+                        holds
+                    }
+                };
+                let target = self.load(mode(1), words[1].clone())?;
+                let jump_to = |target: &Value| match target.as_constant() {
+                    Some(t) => usize::try_from(t).map_err(|_| End::InvalidInstruction { pc }),
+                    None => Err(End::SymbolicAddress { pc }),
+                };
+                let jumps_on = |non_zero: bool| non_zero == (opcode == OpCode::JumpNonZero);
+                if let Some(other) = fork.as_mut() {
+                    other.steps = self.steps;
+                    other.pc = if jumps_on(!non_zero) {
+                        jump_to(&target)?
+                    } else {
+                        next
+                    };
+                }
+                self.pc = if jumps_on(non_zero) {
+                    jump_to(&target)?
+                } else {
+                    next
+                };
+                return Ok(fork.map_or(Step::Continue, Step::Fork));
+            }
+            OpCode::LessThan | OpCode::Equal => {
+                let a = linear(self.load(mode(0), words[0].clone())?)?;
+                let b = linear(self.load(mode(1), words[1].clone())?)?;
+                let diff = a.sub(&b);
+                // The difference of two constants can overflow, so they are
+                // compared directly. Otherwise `a < b` is taken to mean
+                // `a - b < 0`, which assumes the subtraction doesn't overflow.
+                let result = match (a.as_constant(), b.as_constant(), diff.as_constant()) {
+                    (Some(a), Some(b), _) if opcode == OpCode::LessThan => a < b,
+                    (Some(a), Some(b), _) => a == b,
+                    (_, _, Some(d)) if opcode == OpCode::LessThan => d < 0,
+                    (_, _, Some(d)) => d == 0,
+                    _ => {
+                        let constraint = if opcode == OpCode::LessThan {
+                            Constraint::Negative(diff)
+                        } else {
+                            Constraint::Zero(diff)
+                        };
+                        let (holds, other) = self.branch(constraint);
+                        if let Some(mut other) = other {
+                            other.store(mode(2), &words[2], Value::constant(!holds as Word))?;
+                            other.pc = next;
+                            fork = Some(other);
+                        }
+                        holds
+                    }
+                };
+                self.store(mode(2), &words[2], Value::constant(result as Word))?;
+            }
+            OpCode::AddRel => {
+                let value = self.load(mode(0), words[0].clone())?;
+                let offset = value.as_constant().ok_or(End::SymbolicAddress { pc })?;
+                self.rel += offset;
+            }
+            OpCode::Halt => unreachable!("halting is handled above"),
+        }
+
+        self.pc = next;
+        Ok(fork.map_or(Step::Continue, Step::Fork))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Constraint, End, Linear, Symbolic, Value, Var};
+    use crate::{Address, Executable, Memory, Word};
+    use anyhow::Result;
+    use pretty_assertions::assert_eq;
+    use std::collections::BTreeMap;
+
+    #[test]
+    fn tracks_linear_expressions() -> Result<()> {
+        crate::init_logging();
+        // Outputs 3 * (in0 + in1) - 7
+        let program: Memory =
+            "3,19,3,20,1,19,20,21,1002,21,3,21,101,-7,21,21,4,21,99,0,0,0".parse()?;
+
+        let exploration = Symbolic::new(program).explore();
+
+        assert_eq!(1, exploration.paths.len());
+        let path = &exploration.paths[0];
+        assert_eq!(End::Halted, path.end);
+        assert_eq!(2, path.inputs);
+        assert_eq!(
+            vec!["3*in0 + 3*in1 - 7".to_string()],
+            path.outputs
+                .iter()
+                .map(Value::to_string)
+                .collect::<Vec<_>>()
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn forks_on_comparisons() -> Result<()> {
+        crate::init_logging();
+        // Outputs 1 if the input is less than 8, and 2 otherwise
+        let program: Memory = "3,16,1007,16,8,17,1005,17,12,104,2,99,104,1,99,0,0,0".parse()?;
+
+        let exploration = Symbolic::new(program.clone()).explore();
+
+        let mut summaries: Vec<String> = exploration.paths.iter().map(|p| p.to_string()).collect();
+        summaries.sort();
+        assert_eq!(
+            vec![
+                "halted after 4 steps when in0 - 8 < 0; output 1",
+                "halted after 4 steps when in0 - 8 >= 0; output 2",
+            ],
+            summaries
+        );
+        assert!(!exploration.truncated);
+
+        // Check each path against a concrete run
+        for input in &[-3, 7, 8, 100] {
+            let mut values = BTreeMap::new();
+            values.insert(Var::Input(0), *input);
+            let path = exploration
+                .paths
+                .iter()
+                .find(|p| p.accepts(&values) == Some(true))
+                .expect("a path accepts every input");
+
+            let mut exe = Executable::from(program.clone());
+            exe.single_input(*input);
+            let drain = exe.drain();
+            exe.run()?;
+            drop(exe);
+            let expected: Vec<Word> = path
+                .outputs
+                .iter()
+                .map(|o| o.as_constant().expect("constant output"))
+                .collect();
+            assert_eq!(expected, drain.to_vec());
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn compares_constants_without_overflow() -> Result<()> {
+        crate::init_logging();
+        // Outputs whether the smallest word is less than 1, where the
+        // difference of the two overflows
+        let program: Memory = "1107,-9223372036854775808,1,7,4,7,99,0".parse()?;
+
+        let exploration = Symbolic::new(program).explore();
+
+        let path = exploration.halted().next().expect("a halted path");
+        assert_eq!(vec![Value::constant(1)], path.outputs);
+
+        Ok(())
+    }
+
+    #[test]
+    fn repeated_tests_do_not_fork_again() -> Result<()> {
+        crate::init_logging();
+        // Compares the input with 5 twice, outputting each result
+        let program: Memory = "3,100,1008,100,5,101,1008,100,5,102,4,101,4,102,99".parse()?;
+
+        let exploration = Symbolic::new(program).explore();
+
+        assert_eq!(2, exploration.paths.len());
+        for path in &exploration.paths {
+            assert_eq!(1, path.constraints.len());
+            assert_eq!(path.outputs[0], path.outputs[1]);
+        }
+        assert!(exploration.paths.iter().any(|p| p.constraints[0]
+            == Constraint::Zero(Linear::var(Var::Input(0)).sub(&Linear::constant(5)))));
+
+        Ok(())
+    }
+
+    #[test]
+    fn stops_where_values_cannot_be_tracked() -> Result<()> {
+        crate::init_logging();
+        let squares: Memory = "3,9,2,9,9,10,4,10,99,0,0".parse()?;
+        let exploration = Symbolic::new(squares).explore();
+        assert_eq!(
+            End::NonLinear {
+                pc: Address::new(2)
+            },
+            exploration.paths[0].end
+        );
+
+        let jumps: Memory = "3,5,1105,1,0,0".parse()?;
+        let exploration = Symbolic::new(jumps)
+            .with_symbolic_cell(Address::new(4))
+            .explore();
+        assert_eq!(
+            End::SymbolicAddress {
+                pc: Address::new(2)
+            },
+            exploration.paths[0].end
+        );
+
+        let looping: Memory = "3,6,1005,6,0,99,0".parse()?;
+        let exploration = Symbolic::new(looping)
+            .with_max_paths(4)
+            .with_max_steps(100)
+            .explore();
+        assert!(exploration.truncated);
+        assert_eq!(4, exploration.paths.len());
+
+        Ok(())
+    }
+
+    #[test]
+    fn solves_day_2_by_inversion() -> Result<()> {
+        crate::init_logging();
+        let program: Memory = include_str!("../../inputs/input-02").parse()?;
+        let (noun, verb) = (Address::new(1), Address::new(2));
+
+        let exploration = Symbolic::new(program.clone())
+            .with_symbolic_cell(noun)
+            .with_symbolic_cell(verb)
+            .explore();
+
+        let path = exploration.halted().next().expect("the program halts");
+        assert!(path.constraints.is_empty());
+        let result = match path.read(Address::ZERO) {
+            Value::Linear(l) => l,
+            Value::Unknown => panic!("result depends on unknown values"),
+        };
+
+        // Compare against a concrete run with the 1202 state
+        let mut patched = program;
+        patched.try_write(noun, 12)?;
+        patched.try_write(verb, 2)?;
+        let memory = Executable::from(patched).execute()?;
+        let mut values = BTreeMap::new();
+        values.insert(Var::Cell(noun), 12);
+        values.insert(Var::Cell(verb), 2);
+        assert_eq!(
+            Some(memory.read_or_default(Address::ZERO)),
+            result.evaluate(&values)
+        );
+
+        Ok(())
+    }
+}
@@ -1,20 +1,62 @@
-use super::{AsyncExecutable, ExecutionError, Memory, Word};
+use super::{
+    recording::{ascii_char, render_ascii, EventKind},
+    AsyncExecutable, CancelHandle, ExecutionError, Image, Memory, Recording, Termination, Word,
+};
+use futures::{
+    channel::mpsc::{channel, Receiver, Sender},
+    SinkExt, StreamExt,
+};
 use num_traits::ToPrimitive;
-use tokio::{
-    io::{AsyncBufReadExt, AsyncWrite, AsyncWriteExt, BufReader},
-    sync::mpsc::{channel, Receiver, Sender},
+use std::{
+    sync::{Arc, Mutex},
+    time::Instant,
 };
+use tokio::io::{AsyncBufReadExt, AsyncWrite, AsyncWriteExt, BufReader};
+
+/// How a terminal handles output values which are not ASCII characters
+///
+/// Intcode programs commonly report their final answer as a single value too
+/// large to be a character.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum NonAsciiPolicy {
+    /// Writes each value inline with the text, as
+    /// `Non-ASCII value received: N`, as well as collecting it
+    #[default]
+    Format,
+    /// Collects values without writing them
+    Collect,
+    /// Collects the first value, then stops the program
+    Stop,
+}
+
+/// The result of running a program on an `AsciiTerminal`
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TerminalExit {
+    /// Non-ASCII values output by the program
+    pub values: Vec<Word>,
+    /// The final state of memory, or `None` if the program was stopped on a
+    /// non-ASCII value
+    pub memory: Option<Memory>,
+}
+
+impl TerminalExit {
+    /// The first non-ASCII value output by the program
+    pub fn answer(&self) -> Option<Word> {
+        self.values.first().copied()
+    }
+}
 
 pub struct AsciiTerminal {
     tx: Sender<Word>,
     rx: Receiver<Word>,
     exe: AsyncExecutable,
-    predefined_input: &'static [&'static str],
+    predefined_input: Vec<String>,
+    policy: NonAsciiPolicy,
 }
 
 impl AsciiTerminal {
-    pub fn new(program: Memory) -> Self {
-        let mut exe = AsyncExecutable::from(program);
+    pub fn new(program: impl Into<Image>) -> Self {
+        let mut exe = AsyncExecutable::from(program.into());
         let input = channel(20);
         let output = channel(20);
         exe.pipe_inputs_from(input.1);
@@ -23,12 +65,19 @@ impl AsciiTerminal {
             tx: input.0,
             rx: output.1,
             exe,
-            predefined_input: &[],
+            predefined_input: Vec::new(),
+            policy: NonAsciiPolicy::default(),
         }
     }
 
-    pub fn with_input(program: Memory, predefined_input: &'static [&'static str]) -> Self {
-        let mut exe = AsyncExecutable::from(program);
+    /// Creates a terminal which sends each line of `predefined_input` before
+    /// reading from stdin
+    pub fn with_input<I>(program: impl Into<Image>, predefined_input: I) -> Self
+    where
+        I: IntoIterator,
+        I::Item: Into<String>,
+    {
+        let mut exe = AsyncExecutable::from(program.into());
         let input = channel(20);
         let output = channel(20);
         exe.pipe_inputs_from(input.1);
@@ -37,22 +86,89 @@ impl AsciiTerminal {
             tx: input.0,
             rx: output.1,
             exe,
-            predefined_input
+            predefined_input: predefined_input.into_iter().map(Into::into).collect(),
+            policy: NonAsciiPolicy::default(),
+        }
+    }
+
+    /// Sets how output values which are not ASCII characters are handled
+    pub fn non_ascii(mut self, policy: NonAsciiPolicy) -> Self {
+        self.policy = policy;
+        self
+    }
+
+    pub async fn execute(self) -> Result<TerminalExit, ExecutionError> {
+        self.execute_with(None).await
+    }
+
+    /// Executes the program while recording everything sent and received
+    ///
+    /// The recording is returned even if execution fails, so that the
+    /// failure can be reproduced. Non-ASCII values are recorded as formatted
+    /// text, whatever the policy.
+    pub async fn execute_recorded(self) -> (Result<TerminalExit, ExecutionError>, Recording) {
+        let recorder = Recorder::new();
+        let result = self.execute_with(Some(recorder.clone())).await;
+        (result, recorder.finish())
+    }
+
+    async fn execute_with(
+        mut self,
+        recorder: Option<Recorder>,
+    ) -> Result<TerminalExit, ExecutionError> {
+        let cancel = self.exe.cancel_handle();
+        tokio::spawn(run_input(self.tx, self.predefined_input, recorder.clone()));
+        let out = tokio::spawn(run_output(self.rx, self.policy, cancel, recorder));
+        let join = tokio::spawn(self.exe.execute_cancellable());
+        let termination = join.await.unwrap()?;
+        let values = out.await.unwrap_or_default();
+
+        let memory = match termination {
+            Termination::Halted(memory) => Some(memory),
+            Termination::Cancelled(_) => None,
+        };
+        Ok(TerminalExit { values, memory })
+    }
+}
+
+/// Shares a recording between the input and output tasks of a terminal
+#[derive(Clone)]
+struct Recorder {
+    start: Instant,
+    recording: Arc<Mutex<Recording>>,
+}
+
+impl Recorder {
+    fn new() -> Self {
+        Self {
+            start: Instant::now(),
+            recording: Arc::default(),
         }
     }
 
-    pub async fn execute(self) -> Result<Memory, ExecutionError> {
-        tokio::spawn(run_input(self.tx, self.predefined_input));
-        let out = tokio::spawn(run_output(self.rx));
-        let join = tokio::spawn(self.exe.execute());
-        let r = join.await.unwrap();
-        let _ = out.await;
-        r
+    fn record(&self, kind: EventKind, data: String) {
+        let time = self.start.elapsed().as_secs_f64();
+        self.recording
+            .lock()
+            .expect("recording lock poisoned")
+            .push(time, kind, data);
+    }
+
+    fn finish(self) -> Recording {
+        let recording = self.recording.lock().expect("recording lock poisoned");
+        recording.clone()
     }
 }
 
-async fn run_input(mut input: Sender<Word>, predefined_input: &'static [&'static str]) -> tokio::io::Result<()> {
+async fn run_input(
+    mut input: Sender<Word>,
+    predefined_input: Vec<String>,
+    recorder: Option<Recorder>,
+) -> tokio::io::Result<()> {
     for line in predefined_input {
+        if let Some(r) = &recorder {
+            r.record(EventKind::Input, format!("{}\n", line));
+        }
         for ch in line.bytes() {
             let _ = input.send(ch as i64).await;
         }
@@ -66,6 +182,9 @@ async fn run_input(mut input: Sender<Word>, predefined_input: &'static [&'static
         if c == 0 {
             break;
         }
+        if let Some(r) = &recorder {
+            r.record(EventKind::Input, buf.clone());
+        }
         for ch in buf.bytes() {
             let _ = input.send(ch as i64).await;
         }
@@ -74,23 +193,51 @@ async fn run_input(mut input: Sender<Word>, predefined_input: &'static [&'static
     Ok(())
 }
 
-async fn run_output(mut output: Receiver<Word>) -> tokio::io::Result<()> {
+async fn run_output(
+    mut output: Receiver<Word>,
+    policy: NonAsciiPolicy,
+    cancel: CancelHandle,
+    recorder: Option<Recorder>,
+) -> Vec<Word> {
     let mut o = tokio::io::stdout();
-    while let Some(w) = output.recv().await {
-        if let Some(ch) = w.to_u8() {
-            o.write(&[ch]).await?;
-        } else {
-            let data = format!("Non-ASCII value received: {}\n", w);
-            o.write(data.as_bytes()).await?;
+    let mut values = Vec::new();
+    let mut line = String::new();
+    while let Some(w) = output.next().await {
+        let text = render_ascii(w);
+        if let Some(r) = &recorder {
+            line.push_str(&text);
+            if line.ends_with('\n') {
+                r.record(EventKind::Output, std::mem::take(&mut line));
+            }
+        }
+
+        if ascii_char(w).is_none() {
+            values.push(w);
+            if policy == NonAsciiPolicy::Stop {
+                cancel.cancel();
+                break;
+            }
+        }
+        if (ascii_char(w).is_some() || policy == NonAsciiPolicy::Format)
+            && o.write_all(&terminal_bytes(w)).await.is_err()
+        {
+            break;
         }
     }
-    Ok(())
+
+    if let Some(r) = &recorder {
+        if !line.is_empty() {
+            r.record(EventKind::Output, line);
+        }
+    }
+    values
 }
 
 #[derive(Debug)]
 pub struct TerminalOut {
     tx: Sender<Word>,
     rx: Receiver<Word>,
+    policy: NonAsciiPolicy,
 }
 
 impl Default for TerminalOut {
@@ -102,7 +249,20 @@ impl Default for TerminalOut {
 impl TerminalOut {
     pub fn new() -> Self {
         let (tx, rx) = channel(20);
-        Self { tx, rx }
+        Self {
+            tx,
+            rx,
+            policy: NonAsciiPolicy::default(),
+        }
+    }
+
+    /// Sets how output values which are not ASCII characters are handled
+    ///
+    /// When stopping on a value, the terminal stops reading output, so a
+    /// program which continues to write will find its output pipe closed.
+    pub fn non_ascii(mut self, policy: NonAsciiPolicy) -> Self {
+        self.policy = policy;
+        self
     }
 
     pub fn tx(&self) -> &Sender<Word> {
@@ -113,20 +273,149 @@ impl TerminalOut {
         &mut self.tx
     }
 
+    /// Writes output to `o` until all senders have been dropped
+    ///
+    /// Returns any non-ASCII values, whether or not they were formatted
+    /// inline.
     pub async fn write_ascii_output_to_writer(
         mut self,
         mut o: impl AsyncWrite + Unpin,
-    ) -> tokio::io::Result<()> {
+    ) -> tokio::io::Result<Vec<Word>> {
         drop(self.tx);
-        while let Some(w) = self.rx.recv().await {
-            if let Some(ch) = w.to_u8() {
-                o.write(&[ch]).await?;
-            } else {
-                let data = format!("Non-ASCII value received: {}\n", w);
-                o.write(data.as_bytes()).await?;
+        let mut values = Vec::new();
+        while let Some(w) = self.rx.next().await {
+            if ascii_char(w).is_none() {
+                values.push(w);
+                if self.policy == NonAsciiPolicy::Stop {
+                    break;
+                }
+            }
+            if ascii_char(w).is_some() || self.policy == NonAsciiPolicy::Format {
+                o.write_all(&terminal_bytes(w)).await?;
             }
         }
 
+        Ok(values)
+    }
+}
+
+/// The bytes written to a terminal for an output value
+///
+/// A value which fits in a byte is written as that byte, so that output
+/// outside ASCII passes through unchanged.
+fn terminal_bytes(w: Word) -> Vec<u8> {
+    match w.to_u8() {
+        Some(b) => vec![b],
+        None => render_ascii(w).into_bytes(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{run_output, NonAsciiPolicy, TerminalExit, TerminalOut};
+    use crate::{AsyncExecutable, Memory, Termination};
+    use anyhow::Result;
+    use futures::channel::mpsc::channel;
+    use pretty_assertions::assert_eq;
+
+    /// Prints "Hi", then 1000, then "!"
+    const ANSWER: &str = "104,72,104,105,104,10,104,1000,104,33,99";
+
+    async fn write_output(policy: NonAsciiPolicy) -> Result<(String, Vec<i64>)> {
+        let terminal = TerminalOut::new().non_ascii(policy);
+        let mut exe = AsyncExecutable::from(ANSWER.parse::<Memory>()?);
+        exe.pipe_outputs_to(terminal.tx().clone());
+        let join = tokio::spawn(exe.execute());
+
+        let mut text = Vec::new();
+        let values = terminal.write_ascii_output_to_writer(&mut text).await?;
+        // Stopping early may close the pipe before the program halts
+        let _ = join.await?;
+
+        Ok((String::from_utf8(text)?, values))
+    }
+
+    #[tokio::test]
+    async fn formats_non_ascii_inline() -> Result<()> {
+        crate::init_logging();
+        let (text, values) = write_output(NonAsciiPolicy::Format).await?;
+        assert_eq!("Hi\nNon-ASCII value received: 1000\n!", text);
+        assert_eq!(vec![1000], values);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn collects_non_ascii_separately() -> Result<()> {
+        crate::init_logging();
+        let (text, values) = write_output(NonAsciiPolicy::Collect).await?;
+        assert_eq!("Hi\n!", text);
+        assert_eq!(vec![1000], values);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn stops_on_non_ascii() -> Result<()> {
+        crate::init_logging();
+        let (text, values) = write_output(NonAsciiPolicy::Stop).await?;
+        assert_eq!("Hi\n", text);
+        assert_eq!(vec![1000], values);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn writes_bytes_outside_ascii_unchanged() -> Result<()> {
+        crate::init_logging();
+        let terminal = TerminalOut::new().non_ascii(NonAsciiPolicy::Collect);
+        let mut exe = AsyncExecutable::from("104,200,99".parse::<Memory>()?);
+        exe.pipe_outputs_to(terminal.tx().clone());
+        let join = tokio::spawn(exe.execute());
+
+        let mut text = Vec::new();
+        let values = terminal.write_ascii_output_to_writer(&mut text).await?;
+        join.await??;
+
+        assert_eq!(vec![200], text);
+        assert!(values.is_empty());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn answers_with_formatted_values() -> Result<()> {
+        crate::init_logging();
+        let mut exe = AsyncExecutable::from(ANSWER.parse::<Memory>()?);
+        let (tx, rx) = channel(20);
+        exe.pipe_outputs_to(tx);
+        let cancel = exe.cancel_handle();
+        let join = tokio::spawn(exe.execute());
+
+        let values = run_output(rx, NonAsciiPolicy::default(), cancel, None).await;
+        let memory = join.await??;
+
+        let exit = TerminalExit {
+            values,
+            memory: Some(memory),
+        };
+        assert_eq!(Some(1000), exit.answer());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn stopping_cancels_program() -> Result<()> {
+        crate::init_logging();
+        // Outputs 1000, then waits for input which never arrives
+        let memory: Memory = "104,1000,3,0,99".parse()?;
+        let mut exe = AsyncExecutable::from(memory);
+        let (_input, input_rx) = channel(20);
+        let (tx, rx) = channel(20);
+        exe.pipe_inputs_from(input_rx);
+        exe.pipe_outputs_to(tx);
+        let cancel = exe.cancel_handle();
+        let join = tokio::spawn(exe.execute_cancellable());
+
+        let values = run_output(rx, NonAsciiPolicy::Stop, cancel, None).await;
+
+        assert_eq!(vec![1000], values);
+        assert!(matches!(join.await??, Termination::Cancelled(_)));
         Ok(())
     }
 }
@@ -0,0 +1,361 @@
+use super::{Address, DebugInfo, Decoded, Executable, Memory, Observer, Word};
+use serde::{Deserialize, Serialize};
+use std::{fmt, io, sync::mpsc::channel};
+
+/// A record of a single executed instruction
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TraceRecord {
+    /// The index of the instruction within the execution
+    pub step: usize,
+    /// The address of the instruction
+    pub pc: usize,
+    /// The decoded instruction
+    pub instruction: String,
+    /// Operand values read from memory, as `(address, value)` pairs
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub reads: Vec<(usize, Word)>,
+    /// Values written to memory, as `(address, value)` pairs
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub writes: Vec<(usize, Word)>,
+    /// The value received as input
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub input: Option<Word>,
+    /// The value sent as output
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub output: Option<Word>,
+    /// The target of a conditional jump and whether it was taken
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub jump: Option<(usize, bool)>,
+    /// The new relative base after an adjustment
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub rel: Option<usize>,
+}
+
+impl fmt::Display for TraceRecord {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let line = serde_json::to_string(self).map_err(|_| fmt::Error)?;
+        f.write_str(&line)
+    }
+}
+
+/// A structured execution trace
+///
+/// A `Trace` is an `Observer`, so attaching one to an executable records every
+/// instruction executed. Traces are stored as JSON Lines, one `TraceRecord`
+/// per line.
+///
+/// ## Example
+///
+/// ```
+/// use intcode::{Executable, Memory, Trace};
+///
+/// let memory: Memory = "3,9,8,9,10,9,4,9,99,-1,8".parse().expect("valid data");
+///
+/// let mut exe = Executable::from(memory.clone()).with_observer(Trace::default());
+/// exe.single_input(8);
+/// let _drain = exe.drain();
+/// exe.run().expect("successful execution");
+/// let trace = exe.into_observer();
+///
+/// assert_eq!(vec![8], trace.inputs().collect::<Vec<_>>());
+/// assert!(intcode::replay(memory, &trace).is_none());
+/// ```
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Trace {
+    records: Vec<TraceRecord>,
+}
+
+impl Trace {
+    /// The recorded instructions, in execution order
+    pub fn records(&self) -> &[TraceRecord] {
+        &self.records
+    }
+
+    /// The values received as input over the course of the execution
+    pub fn inputs(&self) -> impl Iterator<Item = Word> + '_ {
+        self.records.iter().filter_map(|r| r.input)
+    }
+
+    /// The values sent as output over the course of the execution
+    pub fn outputs(&self) -> impl Iterator<Item = Word> + '_ {
+        self.records.iter().filter_map(|r| r.output)
+    }
+
+    /// Writes the trace as JSON Lines
+    pub fn to_writer(&self, mut output: impl io::Write) -> io::Result<()> {
+        for record in &self.records {
+            serde_json::to_writer(&mut output, record)?;
+            output.write_all(b"\n")?;
+        }
+
+        Ok(())
+    }
+
+    /// Reads a trace from JSON Lines
+    ///
+    /// Blank lines are ignored.
+    pub fn from_reader(input: impl io::BufRead) -> io::Result<Self> {
+        let mut records = Vec::new();
+        for line in input.lines() {
+            let line = line?;
+            if line.trim().is_empty() {
+                continue;
+            }
+
+            records.push(serde_json::from_str(&line)?);
+        }
+
+        Ok(Self { records })
+    }
+
+    /// Renders the trace one instruction per line, locating each
+    /// instruction by label and source line
+    pub fn symbolize<'a>(&'a self, debug: &'a DebugInfo) -> SymbolizedTrace<'a> {
+        SymbolizedTrace { trace: self, debug }
+    }
+
+    fn current(&mut self) -> &mut TraceRecord {
+        self.records
+            .last_mut()
+            .expect("events are preceded by an instruction fetch")
+    }
+}
+
+impl Observer for Trace {
+    fn on_fetch(&mut self, pc: Address, instruction: &Decoded) {
+        self.records.push(TraceRecord {
+            step: self.records.len(),
+            pc: pc.value(),
+            instruction: instruction.to_string(),
+            reads: Vec::new(),
+            writes: Vec::new(),
+            input: None,
+            output: None,
+            jump: None,
+            rel: None,
+        });
+    }
+
+    fn on_read(&mut self, _pc: Address, address: Address, value: Word) {
+        self.current().reads.push((address.value(), value));
+    }
+
+    fn on_write(&mut self, _pc: Address, address: Address, _prior: Word, value: Word) {
+        self.current().writes.push((address.value(), value));
+    }
+
+    fn on_input(&mut self, _pc: Address, value: Word) {
+        self.current().input = Some(value);
+    }
+
+    fn on_output(&mut self, _pc: Address, value: Word) {
+        self.current().output = Some(value);
+    }
+
+    fn on_jump(&mut self, _pc: Address, target: Address, taken: bool) {
+        self.current().jump = Some((target.value(), taken));
+    }
+
+    fn on_relative_base(&mut self, _pc: Address, _prior: Address, base: Address) {
+        self.current().rel = Some(base.value());
+    }
+}
+
+/// A trace rendered with debug information
+///
+/// Created by `Trace::symbolize`.
+pub struct SymbolizedTrace<'a> {
+    trace: &'a Trace,
+    debug: &'a DebugInfo,
+}
+
+impl fmt::Display for SymbolizedTrace<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for record in &self.trace.records {
+            let location = self.debug.locate(Address::new(record.pc));
+            writeln!(f, "{:>6} {}: {}", record.step, location, record.instruction)?;
+        }
+        Ok(())
+    }
+}
+
+/// The first point at which a replayed execution differs from its trace
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Divergence {
+    /// The index of the first differing instruction
+    pub step: usize,
+    /// The recorded instruction, or `None` if the trace ended first
+    pub expected: Option<TraceRecord>,
+    /// The replayed instruction, or `None` if the replay stopped first
+    pub actual: Option<TraceRecord>,
+}
+
+impl fmt::Display for Divergence {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "execution diverged at step {}", self.step)?;
+        match &self.expected {
+            Some(r) => writeln!(f, "expected: {}", r)?,
+            None => writeln!(f, "expected: end of trace")?,
+        }
+        match &self.actual {
+            Some(r) => write!(f, "actual:   {}", r),
+            None => write!(f, "actual:   end of execution"),
+        }
+    }
+}
+
+/// Re-runs a program against the inputs recorded in a trace
+///
+/// Returns the first point at which the replayed execution differs from the
+/// trace, or `None` if the two are identical. Replay stops as soon as a
+/// divergence is found, so a program which diverges into an infinite loop
+/// will still terminate.
+///
+/// A replay which fails with an `ExecutionError` at the same point that the
+/// trace ends is considered identical, so traces of failed executions can be
+/// replayed as well.
+pub fn replay(program: Memory, trace: &Trace) -> Option<Divergence> {
+    let mut exe = Executable::from(program).with_observer(Trace::default());
+
+    let (tx, rx) = channel();
+    for value in trace.inputs() {
+        tx.send(value).expect("receiver is held by the executable");
+    }
+    drop(tx);
+    exe.pipe_inputs_from(rx);
+    let _drain = exe.drain();
+
+    let expected = trace.records();
+    let mut step = 0;
+    loop {
+        let result = exe.step();
+        let actual = exe.observer().records.get(step);
+
+        let running = match result {
+            Ok(running) => running,
+            // The failing instruction is recorded if it was fetched, so the
+            // trace ends either with it or just before it
+            Err(_) if actual == expected.get(step) && expected.len() <= step + 1 => return None,
+            Err(e) => {
+                log::debug!("replay stopped at step {}: {}", step, e);
+                return Some(Divergence {
+                    step,
+                    expected: expected.get(step).cloned(),
+                    actual: actual.cloned(),
+                });
+            }
+        };
+
+        if actual != expected.get(step) {
+            return Some(Divergence {
+                step,
+                expected: expected.get(step).cloned(),
+                actual: actual.cloned(),
+            });
+        }
+
+        step += 1;
+        if !running {
+            return expected.get(step).map(|r| Divergence {
+                step,
+                expected: Some(r.clone()),
+                actual: None,
+            });
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{replay, Trace};
+    use crate::{Executable, Memory, Word};
+    use anyhow::Result;
+    use pretty_assertions::assert_eq;
+
+    const PUZ_5_PART_2_EXAMPLE: &str = "
+        3,21,1008,21,8,20,1005,20,22,107,8,21,20,1006,20,31,
+        1106,0,36,98,0,0,1002,21,125,20,4,20,1105,1,46,104,
+        999,1105,1,46,1101,1000,1,20,4,20,1105,1,46,98,99";
+
+    fn record(program: &Memory, input: Word) -> Result<Trace> {
+        let mut exe = Executable::from(program.clone()).with_observer(Trace::default());
+        exe.single_input(input);
+        let _drain = exe.drain();
+        exe.run()?;
+        Ok(exe.into_observer())
+    }
+
+    #[test]
+    fn round_trips_through_json_lines() -> Result<()> {
+        crate::init_logging();
+        let program: Memory = PUZ_5_PART_2_EXAMPLE.parse()?;
+        let trace = record(&program, 8)?;
+
+        let mut buf = Vec::new();
+        trace.to_writer(&mut buf)?;
+        let parsed = Trace::from_reader(&buf[..])?;
+
+        assert_eq!(trace, parsed);
+        assert_eq!(vec![1000], parsed.outputs().collect::<Vec<_>>());
+
+        Ok(())
+    }
+
+    #[test]
+    fn replay_matches_recording() -> Result<()> {
+        crate::init_logging();
+        let program: Memory = PUZ_5_PART_2_EXAMPLE.parse()?;
+        let trace = record(&program, 99)?;
+
+        assert_eq!(None, replay(program, &trace));
+
+        Ok(())
+    }
+
+    #[test]
+    fn replay_reports_first_divergence() -> Result<()> {
+        crate::init_logging();
+        let program: Memory = PUZ_5_PART_2_EXAMPLE.parse()?;
+        let trace = record(&program, 99)?;
+
+        // Comparing against 9 rather than 8 alters the second instruction
+        let mut patched = program.to_vec();
+        patched[4] = 9;
+        let divergence = replay(Memory::from_vec(patched), &trace).expect("a divergence");
+
+        assert_eq!(1, divergence.step);
+        assert_eq!(Some(2), divergence.actual.map(|r| r.pc));
+
+        Ok(())
+    }
+
+    #[test]
+    fn replay_with_missing_input_diverges() -> Result<()> {
+        crate::init_logging();
+        let program: Memory = PUZ_5_PART_2_EXAMPLE.parse()?;
+        let mut trace = record(&program, 99)?;
+        trace.records[0].input = None;
+
+        let divergence = replay(program, &trace).expect("a divergence");
+
+        assert_eq!(0, divergence.step);
+
+        Ok(())
+    }
+
+    #[test]
+    fn replay_matches_failed_recording() -> Result<()> {
+        crate::init_logging();
+        // Reads from an input which is never provided
+        let program: Memory = "3,0,99".parse()?;
+        let mut exe = Executable::from(program.clone()).with_observer(Trace::default());
+        let _drain = exe.drain();
+        exe.run().expect_err("input is missing");
+        let trace = exe.into_observer();
+
+        assert_eq!(1, trace.records().len());
+        assert_eq!(None, replay(program, &trace));
+
+        Ok(())
+    }
+}
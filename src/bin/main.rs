@@ -8,6 +8,9 @@ struct Opt {
     day: Option<u8>,
     #[structopt(long)]
     all: bool,
+    /// Shows the Intcode machines of days 7 and 23 on a live dashboard
+    #[structopt(long)]
+    dashboard: bool,
 }
 
 fn main() -> Result<()> {
@@ -21,7 +24,11 @@ fn main() -> Result<()> {
 
         Ok(())
     } else {
-        run_day(opt.day.unwrap_or_default())
+        match (opt.dashboard, opt.day.unwrap_or_default()) {
+            (true, 7) => day07::run_with_dashboard(),
+            (true, 23) => day23::run_with_dashboard(),
+            (_, day) => run_day(day),
+        }
     }
 }
 
@@ -33,10 +40,7 @@ fn run_day(day: u8) -> Result<()> {
         4 => day04::run(),
         5 => day05::run()?,
         6 => day06::run()?,
-        7 => {
-            //day07::run()?
-            tokio::runtime::Runtime::new()?.block_on(day07::run_async())?
-        }
+        7 => day07::run()?,
         8 => day08::run()?,
         9 => day09::run()?,
         10 => day10::run()?,
@@ -0,0 +1,551 @@
+//! A live terminal view of running Intcode machines
+//!
+//! Each machine is given a `Probe`, obtained from `Dashboard::attach`, which
+//! is installed as the machine's observer. The probe mirrors the machine's
+//! registers, memory and I/O into a panel which the dashboard draws while the
+//! machines run, on a thread of its own.
+//!
+//! A handful of machines are each shown in full: the instructions around the
+//! program counter, the relative base, how quickly the machine is stepping,
+//! how many inputs are queued for it, its most recent outputs, and a dump of
+//! the memory it most recently wrote with changed words highlighted. With
+//! more machines than that, such as the day 23 network, only the one which
+//! most recently produced output is shown in full, above a single line for
+//! every machine.
+
+use intcode::{Address, Decoded, Memory, Observer, Word};
+use std::{
+    collections::{BTreeSet, VecDeque},
+    io::{self, Write},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex, MutexGuard,
+    },
+    thread,
+    time::{Duration, Instant},
+};
+use termion::{clear, color, cursor, style};
+
+/// The number of machines which are shown in full before switching to one
+/// line per machine
+const FULL_PANELS: usize = 5;
+/// The number of previously executed instructions shown before the pc
+const HISTORY: usize = 3;
+/// The number of instructions shown from the pc onwards
+const LOOKAHEAD: usize = 4;
+/// The number of recent outputs kept for each machine
+const OUTPUTS: usize = 8;
+/// The words shown on each row of a memory dump
+const WORDS_PER_ROW: usize = 8;
+/// The rows in each memory dump
+const ROWS: usize = 4;
+
+#[derive(Debug)]
+struct Panel {
+    name: String,
+    memory: Memory,
+    pc: Address,
+    relative_base: Address,
+    steps: usize,
+    halted: bool,
+    recent: VecDeque<Address>,
+    outputs: VecDeque<Word>,
+    queued: isize,
+    last_write: Option<Address>,
+    changed: BTreeSet<Address>,
+    fresh: BTreeSet<Address>,
+    last_output: Option<Instant>,
+    sampled: (usize, Instant),
+    rate: f64,
+}
+
+impl Panel {
+    fn new(name: String, memory: Memory) -> Self {
+        Self {
+            name,
+            memory,
+            pc: Address::ZERO,
+            relative_base: Address::ZERO,
+            steps: 0,
+            halted: false,
+            recent: VecDeque::with_capacity(HISTORY + 1),
+            outputs: VecDeque::with_capacity(OUTPUTS + 1),
+            queued: 0,
+            last_write: None,
+            changed: BTreeSet::new(),
+            fresh: BTreeSet::new(),
+            last_output: None,
+            sampled: (0, Instant::now()),
+            rate: 0.0,
+        }
+    }
+
+    /// Updates the step rate from the steps taken since it was last sampled
+    fn sample(&mut self, now: Instant) {
+        let (steps, at) = self.sampled;
+        let elapsed = now.duration_since(at).as_secs_f64();
+        if elapsed > 0.0 {
+            self.rate = (self.steps - steps) as f64 / elapsed;
+            self.sampled = (self.steps, now);
+        }
+    }
+
+    fn summary(&self) -> String {
+        let state = if self.halted { "halted" } else { "running" };
+        format!(
+            "{:<8} pc={:<6} rel={:<6} steps={:<10} {:>9}/s queued={:<3} {}",
+            self.name,
+            self.pc.to_string(),
+            self.relative_base.to_string(),
+            self.steps,
+            format_rate(self.rate),
+            self.queued.max(0),
+            state,
+        )
+    }
+
+    fn lines(&self) -> Vec<String> {
+        let mut lines = vec![format!("{}{}{}", style::Bold, self.summary(), style::Reset)];
+
+        for &address in &self.recent {
+            lines.push(format!("    {}", self.disassemble(address).0));
+        }
+        let mut next = self.pc;
+        for i in 0..LOOKAHEAD {
+            let (text, size) = self.disassemble(next);
+            if i == 0 {
+                lines.push(format!(
+                    "  {}> {}{}",
+                    color::Fg(color::Green),
+                    text,
+                    style::Reset
+                ));
+            } else {
+                lines.push(format!("    {}", text));
+            }
+            next = Address::new(next.value() + size);
+        }
+
+        let outputs: Vec<_> = self.outputs.iter().map(Word::to_string).collect();
+        lines.push(format!("  out: {}", outputs.join(", ")));
+
+        let focus = self.last_write.unwrap_or(self.pc).value();
+        let first = (focus / WORDS_PER_ROW).saturating_sub(ROWS / 2);
+        for row in first..first + ROWS {
+            let start = row * WORDS_PER_ROW;
+            let mut line = format!("  {:06x}:", start);
+            for address in (start..start + WORDS_PER_ROW).map(Address::new) {
+                let text = format_hex(self.memory.read_or_default(address));
+                if self.fresh.contains(&address) {
+                    line += &format!(
+                        " {}{}{:>9}{}",
+                        style::Bold,
+                        color::Fg(color::Yellow),
+                        text,
+                        style::Reset
+                    );
+                } else if self.changed.contains(&address) {
+                    line += &format!(" {}{:>9}{}", color::Fg(color::Cyan), text, style::Reset);
+                } else {
+                    line += &format!(" {:>9}", text);
+                }
+            }
+            lines.push(line);
+        }
+
+        lines
+    }
+
+    /// Disassembles the instruction at `address`, returning its text and size
+    fn disassemble(&self, address: Address) -> (String, usize) {
+        match Decoded::at(&self.memory, address) {
+            Some(decoded) => (
+                format!("{:>6}: {}", address.value(), decoded),
+                decoded.size(),
+            ),
+            None => (
+                format!(
+                    "{:>6}: data {}",
+                    address.value(),
+                    self.memory.read_or_default(address)
+                ),
+                1,
+            ),
+        }
+    }
+}
+
+fn format_rate(rate: f64) -> String {
+    if rate >= 1e6 {
+        format!("{:.1}M", rate / 1e6)
+    } else if rate >= 1e3 {
+        format!("{:.1}k", rate / 1e3)
+    } else {
+        format!("{:.0}", rate)
+    }
+}
+
+fn format_hex(value: Word) -> String {
+    if value < 0 {
+        format!("-{:x}", value.unsigned_abs())
+    } else {
+        format!("{:x}", value)
+    }
+}
+
+fn lock(panel: &Mutex<Panel>) -> MutexGuard<'_, Panel> {
+    // A machine which panicked while holding its panel leaves it usable
+    panel.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+/// An observer which publishes a machine's progress to a `Dashboard`
+///
+/// Probes are cheap to clone, and every clone reports to the same panel, so
+/// one may be kept by whatever feeds the machine its input to report how many
+/// values are waiting.
+#[derive(Clone, Debug)]
+pub struct Probe {
+    panel: Arc<Mutex<Panel>>,
+    downstream: Option<Arc<Mutex<Panel>>>,
+}
+
+impl Probe {
+    /// Counts each output of this machine as input queued for `target`
+    pub fn with_downstream(mut self, target: &Probe) -> Self {
+        self.downstream = Some(Arc::clone(&target.panel));
+        self
+    }
+
+    /// Records that `count` values have been queued for the machine to read
+    pub fn queue_input(&self, count: usize) {
+        lock(&self.panel).queued += count as isize;
+    }
+}
+
+impl Observer for Probe {
+    fn on_fetch(&mut self, pc: Address, instruction: &Decoded) {
+        let mut panel = lock(&self.panel);
+        if panel.steps > 0 && panel.pc != pc {
+            let prior = panel.pc;
+            panel.recent.push_back(prior);
+            if panel.recent.len() > HISTORY {
+                panel.recent.pop_front();
+            }
+        }
+        panel.pc = pc;
+        panel.steps += 1;
+        panel.halted = matches!(instruction, Decoded::Halt);
+    }
+
+    fn on_write(&mut self, _pc: Address, address: Address, _prior: Word, value: Word) {
+        let mut panel = lock(&self.panel);
+        panel.memory.write_arbitrary(address, value);
+        panel.changed.insert(address);
+        panel.fresh.insert(address);
+        panel.last_write = Some(address);
+    }
+
+    fn on_input(&mut self, _pc: Address, _value: Word) {
+        lock(&self.panel).queued -= 1;
+    }
+
+    fn on_output(&mut self, _pc: Address, value: Word) {
+        {
+            let mut panel = lock(&self.panel);
+            panel.outputs.push_back(value);
+            if panel.outputs.len() > OUTPUTS {
+                panel.outputs.pop_front();
+            }
+            panel.last_output = Some(Instant::now());
+        }
+        if let Some(downstream) = &self.downstream {
+            lock(downstream).queued += 1;
+        }
+    }
+
+    fn on_relative_base(&mut self, _pc: Address, _prior: Address, base: Address) {
+        lock(&self.panel).relative_base = base;
+    }
+}
+
+/// A set of panels, one for each machine being watched
+#[derive(Clone, Debug, Default)]
+pub struct Dashboard {
+    panels: Arc<Mutex<Vec<Arc<Mutex<Panel>>>>>,
+}
+
+impl Dashboard {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Creates a probe for a machine starting with `memory`
+    ///
+    /// Attaching a name which is already on the dashboard starts its panel
+    /// afresh, so a machine which is run repeatedly keeps its place.
+    pub fn attach(&self, name: &str, memory: &Memory) -> Probe {
+        let mut panels = self.panels.lock().unwrap_or_else(|e| e.into_inner());
+        let existing = panels.iter().find(|p| lock(p).name == name).cloned();
+        let panel = match existing {
+            Some(panel) => {
+                *lock(&panel) = Panel::new(name.to_string(), memory.clone());
+                panel
+            }
+            None => {
+                let panel = Arc::new(Mutex::new(Panel::new(name.to_string(), memory.clone())));
+                panels.push(Arc::clone(&panel));
+                panel
+            }
+        };
+        Probe {
+            panel,
+            downstream: None,
+        }
+    }
+
+    /// Draws one frame of at most `height` lines, without clearing the screen
+    ///
+    /// Words written since the previous frame are highlighted.
+    pub fn render(&self, out: &mut dyn Write, height: usize) -> io::Result<()> {
+        let panels = self
+            .panels
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .clone();
+        let now = Instant::now();
+        let mut lines = Vec::new();
+
+        if panels.len() <= FULL_PANELS {
+            for panel in &panels {
+                let mut panel = lock(panel);
+                panel.sample(now);
+                lines.extend(panel.lines());
+                lines.push(String::new());
+                panel.fresh.clear();
+            }
+        } else {
+            let mut summaries = Vec::new();
+            let mut focus: Option<(Instant, &Arc<Mutex<Panel>>)> = None;
+            for panel in &panels {
+                let mut guard = lock(panel);
+                guard.sample(now);
+                summaries.push(guard.summary());
+                if let Some(at) = guard.last_output {
+                    if focus.is_none_or(|(latest, _)| at > latest) {
+                        focus = Some((at, panel));
+                    }
+                }
+            }
+            // The machine with the most recent output goes first, so it
+            // stays on screen however many machines there are
+            let focus = focus.map_or(&panels[0], |(_, panel)| panel);
+            lines.extend(lock(focus).lines());
+            lines.push(String::new());
+            lines.extend(summaries);
+            for panel in &panels {
+                lock(panel).fresh.clear();
+            }
+        }
+
+        for line in lines.iter().take(height) {
+            write!(out, "{}{}\r\n", line, clear::UntilNewline)?;
+        }
+        out.flush()
+    }
+
+    /// Starts drawing the dashboard to the terminal every `interval`
+    ///
+    /// Drawing continues until the returned `Screen` is closed or dropped.
+    pub fn show(&self, interval: Duration) -> Screen {
+        let dashboard = self.clone();
+        let stop = Arc::new(AtomicBool::new(false));
+        let stopped = Arc::clone(&stop);
+        let thread = thread::spawn(move || -> io::Result<()> {
+            let stdout = io::stdout();
+            write!(stdout.lock(), "{}{}", clear::All, cursor::Hide)?;
+            loop {
+                // The final frame is drawn after the machines have finished
+                let last = stopped.load(Ordering::SeqCst);
+                let height = termion::terminal_size().map_or(50, |(_, h)| h as usize);
+                let mut out = stdout.lock();
+                write!(out, "{}", cursor::Goto(1, 1))?;
+                dashboard.render(&mut out, height.saturating_sub(1))?;
+                if last {
+                    return write!(out, "{}{}", clear::AfterCursor, cursor::Show);
+                }
+                drop(out);
+                thread::sleep(interval);
+            }
+        });
+
+        Screen {
+            stop,
+            thread: Some(thread),
+        }
+    }
+}
+
+/// The terminal on which a dashboard is being drawn
+#[derive(Debug)]
+pub struct Screen {
+    stop: Arc<AtomicBool>,
+    thread: Option<thread::JoinHandle<io::Result<()>>>,
+}
+
+impl Screen {
+    /// Draws the final frame and stops drawing
+    pub fn close(mut self) -> io::Result<()> {
+        self.finish()
+    }
+
+    fn finish(&mut self) -> io::Result<()> {
+        self.stop.store(true, Ordering::SeqCst);
+        match self.thread.take() {
+            Some(thread) => thread.join().expect("dashboard thread panicked"),
+            None => Ok(()),
+        }
+    }
+}
+
+impl Drop for Screen {
+    fn drop(&mut self) {
+        let _ = self.finish();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Dashboard;
+    use anyhow::Result;
+    use pretty_assertions::assert_eq;
+
+    fn init_logging() {
+        let _ = env_logger::builder().is_test(true).try_init();
+    }
+
+    fn strip_escapes(text: &str) -> String {
+        let mut plain = String::new();
+        let mut chars = text.chars();
+        while let Some(c) = chars.next() {
+            if c == '\u{1b}' {
+                // Skip a CSI sequence up to its final letter
+                for c in chars.by_ref() {
+                    if c.is_ascii_alphabetic() {
+                        break;
+                    }
+                }
+            } else {
+                plain.push(c);
+            }
+        }
+        plain
+    }
+
+    fn render(dashboard: &Dashboard) -> Result<Vec<String>> {
+        let mut out = Vec::new();
+        dashboard.render(&mut out, 100)?;
+        let text = strip_escapes(&String::from_utf8(out)?);
+        Ok(text.lines().map(|l| l.trim_end().to_string()).collect())
+    }
+
+    #[test]
+    fn shows_registers_disassembly_and_memory() -> Result<()> {
+        init_logging();
+        // Reads a value, doubles it into address 9 and outputs it
+        let memory: intcode::Memory = "3,9,1002,9,2,9,4,9,99,0".parse()?;
+        let dashboard = Dashboard::new();
+        let probe = dashboard.attach("doubler", &memory);
+        probe.queue_input(1);
+
+        let mut exe = intcode::Executable::from(memory).with_observer(probe);
+        exe.single_input(21);
+        let drain = exe.drain();
+        exe.run()?;
+        drop(exe);
+        assert_eq!(vec![42], drain.to_vec());
+
+        let lines = render(&dashboard)?;
+        assert!(lines[0].starts_with("doubler  pc=8      rel=0      steps=4"));
+        assert!(lines[0].ends_with("queued=0   halted"));
+        assert_eq!(
+            vec![
+                "         0: read => (9)",
+                "         2: mul (9), $2 => (9)",
+                "         6: write (9) =>",
+                "  >      8: halt",
+                "         9: data 42",
+                "        10: data 0",
+                "        11: data 0",
+            ],
+            lines[1..8].to_vec()
+        );
+        assert_eq!("  out: 42", lines[8]);
+        assert_eq!(
+            "  000000:         3         9       3ea         9         2         9         4         9",
+            lines[9]
+        );
+        assert_eq!(
+            "  000008:        63        2a         0         0         0         0         0         0",
+            lines[10]
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn highlights_words_written_since_the_last_frame() -> Result<()> {
+        init_logging();
+        let memory: intcode::Memory = "1101,2,3,5,99,0".parse()?;
+        let dashboard = Dashboard::new();
+        let probe = dashboard.attach("adder", &memory);
+        intcode::Executable::from(memory)
+            .with_observer(probe)
+            .run()?;
+
+        let yellow = termion::color::Fg(termion::color::Yellow).to_string();
+        let cyan = termion::color::Fg(termion::color::Cyan).to_string();
+        let frame = |dashboard: &Dashboard| -> Result<String> {
+            let mut out = Vec::new();
+            dashboard.render(&mut out, 100)?;
+            Ok(String::from_utf8(out)?)
+        };
+
+        let first = frame(&dashboard)?;
+        assert!(first.contains(&format!("{}        5", yellow)));
+        let second = frame(&dashboard)?;
+        assert!(!second.contains(&yellow));
+        assert!(second.contains(&format!("{}        5", cyan)));
+
+        Ok(())
+    }
+
+    #[test]
+    fn many_machines_share_a_line_each() -> Result<()> {
+        init_logging();
+        let memory: intcode::Memory = "104,7,99".parse()?;
+        let dashboard = Dashboard::new();
+        for i in 0..8 {
+            let probe = dashboard.attach(&format!("nic {}", i), &memory);
+            let mut exe = intcode::Executable::from(memory.clone()).with_observer(probe);
+            let drain = exe.drain();
+            if i == 5 {
+                exe.run()?;
+            }
+            drop(exe);
+            drop(drain);
+        }
+        // Reattaching a machine reuses its panel
+        dashboard.attach("nic 0", &memory);
+
+        let lines = render(&dashboard)?;
+        assert!(lines[0].starts_with("nic 5    pc=2"));
+        assert_eq!("  out: 7", lines[6]);
+        assert_eq!("", lines[11]);
+        assert!(lines[12].starts_with("nic 0    pc=0"));
+        assert!(lines[17].starts_with("nic 5    pc=2"));
+        assert!(lines[19].starts_with("nic 7    pc=0"));
+        assert_eq!(20, lines.len());
+
+        Ok(())
+    }
+}
@@ -166,20 +166,58 @@ pub fn search_for_noun_and_verb(
     memory: intcode::Memory,
     target: intcode::Word,
 ) -> Result<(intcode::Word, intcode::Word)> {
-    for noun in 0..99 {
-        for verb in 0..99 {
-            let output = run_with_specific_state(memory.clone(), noun, verb)?;
+    let candidates = (0..99).flat_map(|noun| (0..99).map(move |verb| vec![noun, verb]));
 
-            if output == target {
-                return Ok((noun, verb));
-            }
-        }
+    let found = intcode::Batch::new(memory)
+        .patching(&[intcode::Address::new(1), intcode::Address::new(2)])
+        .find_first(candidates, |e| {
+            e.memory.read_or_default(intcode::Address::new(0)) == target
+        })?;
+
+    match found {
+        Some((idx, _)) => Ok((idx as intcode::Word / 99, idx as intcode::Word % 99)),
+        None => Err(anyhow!(
+            "Unable to find (noun, verb) pair that outputs {}",
+            target
+        )),
     }
+}
+
+/// Finds the noun and verb by treating them as unknowns and inverting the
+/// program's result, rather than running it for every pair
+pub fn solve_for_noun_and_verb(
+    memory: intcode::Memory,
+    target: intcode::Word,
+) -> Result<(intcode::Word, intcode::Word)> {
+    let (noun, verb) = (intcode::Address::new(1), intcode::Address::new(2));
+    let exploration = intcode::Symbolic::new(memory)
+        .with_symbolic_cell(noun)
+        .with_symbolic_cell(verb)
+        .explore();
+
+    let path = exploration
+        .halted()
+        .next()
+        .ok_or_else(|| anyhow!("Program does not halt"))?;
+    let result = match path.read(intcode::Address::new(0)) {
+        intcode::Value::Linear(result) if exploration.paths.len() == 1 => result,
+        _ => return Err(anyhow!("Output is not a linear function of noun and verb")),
+    };
+
+    let a = result.coefficient(intcode::Var::Cell(noun));
+    let b = result.coefficient(intcode::Var::Cell(verb));
+    let remainder = target - result.constant_term();
+    let found = (0..100).find_map(|noun| {
+        let rest = remainder - a * noun;
+        match rest.checked_rem(b) {
+            Some(0) if (0..100).contains(&(rest / b)) => Some((noun, rest / b)),
+            // The verb doesn't affect the result, so any verb will do
+            None if rest == 0 => Some((noun, 0)),
+            _ => None,
+        }
+    });
 
-    Err(anyhow!(
-        "Unable to find (noun, verb) pair that outputs {}",
-        target
-    ))
+    found.ok_or_else(|| anyhow!("Unable to find (noun, verb) pair that outputs {}", target))
 }
 
 pub fn run() -> Result<()> {
@@ -189,8 +227,48 @@ pub fn run() -> Result<()> {
     println!("Diagnostic: (12, 02): {}", output);
 
     const TARGET: intcode::Word = 19_690_720;
-    let (noun, verb) = search_for_noun_and_verb(memory, TARGET)?;
+    let (noun, verb) = search_for_noun_and_verb(memory.clone(), TARGET)?;
     println!("Searching: ({:02}, {:02}): {}", noun, verb, TARGET);
 
+    let (noun, verb) = solve_for_noun_and_verb(memory, TARGET)?;
+    println!("Solving: ({:02}, {:02}): {}", noun, verb, TARGET);
+
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{search_for_noun_and_verb, solve_for_noun_and_verb, PUZZLE_INPUT};
+    use anyhow::Result;
+    use pretty_assertions::assert_eq;
+
+    fn init_logging() {
+        let _ = env_logger::builder().is_test(true).try_init();
+    }
+
+    #[test]
+    fn solving_matches_searching() -> Result<()> {
+        init_logging();
+        const TARGET: intcode::Word = 19_690_720;
+        let memory: intcode::Memory = PUZZLE_INPUT.parse()?;
+
+        let searched = search_for_noun_and_verb(memory.clone(), TARGET)?;
+        let solved = solve_for_noun_and_verb(memory, TARGET)?;
+
+        assert_eq!(searched, solved);
+
+        Ok(())
+    }
+
+    #[test]
+    fn solves_when_the_verb_is_unused() -> Result<()> {
+        init_logging();
+        // Stores noun + verb - verb + 4 in address 0
+        let memory: intcode::Memory =
+            "1101,0,0,20,1002,2,-1,21,1,20,21,0,1001,0,4,0,99,0,0,0,0,0".parse()?;
+
+        assert_eq!((15, 0), solve_for_noun_and_verb(memory, 19)?);
+
+        Ok(())
+    }
+}
@@ -207,7 +207,7 @@ impl fmt::Display for Directive {
 #[derive(Debug)]
 enum InvalidDirective {
     InvalidDirection,
-    BadDistance(std::num::ParseIntError),
+    BadDistance(#[allow(dead_code)] std::num::ParseIntError),
 }
 
 impl From<InvalidDirection> for InvalidDirective {
@@ -245,7 +245,7 @@ pub struct CandidatePoint {
 
 impl CandidatePoint {
     pub fn manhattan_distance_from_origin(self) -> usize {
-        self.point.0.abs() as usize + self.point.1.abs() as usize
+        self.point.0.unsigned_abs() as usize + self.point.1.unsigned_abs() as usize
     }
 
     pub fn wire_delay_to_point(self) -> usize {
@@ -272,8 +272,8 @@ impl CandidatePoint {
         if ver_seg_starts_below_candidate == ver_seg_ends_above_candidate
             && hor_seg_starts_left_of_candidate == hor_seg_ends_right_of_candidate
         {
-            let added_ver_dist = (candidate.1 - ver_seg.point.1).abs() as usize;
-            let added_hor_dist = (candidate.0 - hor_seg.point.0).abs() as usize;
+            let added_ver_dist = (candidate.1 - ver_seg.point.1).unsigned_abs() as usize;
+            let added_hor_dist = (candidate.0 - hor_seg.point.0).unsigned_abs() as usize;
 
             let candidate = CandidatePoint {
                 point: candidate,
@@ -50,7 +50,7 @@ pub fn parse_input(input: &str) -> ops::RangeInclusive<u32> {
         .map(|op| op.trim().parse().expect("data must be a valid integer"))
         .collect();
 
-    (values[0]..=values[1])
+    values[0]..=values[1]
 }
 
 struct DigitIterator(u32);
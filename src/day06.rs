@@ -280,7 +280,7 @@ fn checksum_node(initial: Rc<OrbitNode>) -> usize {
         let next = current.inner.borrow().as_ref().map(|n| Rc::clone(&n.node));
         match next {
             Some(n) => {
-                std::mem::replace(&mut current, n);
+                current = n;
                 count += 1;
             }
             None => break,
@@ -300,14 +300,17 @@
 //! Try every combination of the new phase settings on the amplifier feedback
 //! loop. What is the highest signal that can be sent to the thrusters?
 
+use crate::Dashboard;
 use anyhow::Result;
-use std::sync::Arc;
+use futures::SinkExt;
+use std::{sync::Arc, time::Duration};
 
 pub const PUZZLE_INPUT: &str = include_str!("../inputs/input-07");
 
 fn run_amplifier_sequence(
     memory: &intcode::Memory,
     phase_sequence: [intcode::Word; 5],
+    dashboard: Option<&Dashboard>,
 ) -> Result<intcode::Word> {
     let mut amp_a = intcode::Executable::from(memory.clone());
     let mut amp_b = intcode::Executable::from(memory.clone());
@@ -322,6 +325,32 @@ fn run_amplifier_sequence(
     let d_in = amp_c.pipe_to(&mut amp_d);
     let e_in = amp_d.pipe_to(&mut amp_e);
 
+    let probes = dashboard.map(|dashboard| {
+        let probes: Vec<_> = ["amp A", "amp B", "amp C", "amp D", "amp E"]
+            .iter()
+            .map(|name| dashboard.attach(name, memory))
+            .collect();
+        for probe in &probes {
+            // Each amplifier is first sent its phase setting
+            probe.queue_input(1);
+        }
+        probes
+    });
+    let probe = |i: usize| {
+        probes
+            .as_ref()
+            .map(|p| p[i].clone().with_downstream(&p[(i + 1) % 5]))
+    };
+    let amp_a = amp_a.with_observer(probe(0));
+    let amp_b = amp_b.with_observer(probe(1));
+    let amp_c = amp_c.with_observer(probe(2));
+    let amp_d = amp_d.with_observer(probe(3));
+    let amp_e = amp_e.with_observer(probe(4));
+    if let Some(probes) = &probes {
+        // The initial signal
+        probes[0].queue_input(1);
+    }
+
     let exec_a = amp_a.execute_in_thread();
     let exec_b = amp_b.execute_in_thread();
     let exec_c = amp_c.execute_in_thread();
@@ -350,16 +379,27 @@ pub fn permute(
     memory: &intcode::Memory,
     phase_sequence: [intcode::Word; 5],
 ) -> Result<([intcode::Word; 5], intcode::Word)> {
-    permute_impl(memory, phase_sequence, 0)
+    permute_impl(memory, phase_sequence, 0, None)
+}
+
+/// Searches as `permute` does, showing each run of the amplifiers on
+/// `dashboard`
+pub fn permute_with_dashboard(
+    memory: &intcode::Memory,
+    phase_sequence: [intcode::Word; 5],
+    dashboard: &Dashboard,
+) -> Result<([intcode::Word; 5], intcode::Word)> {
+    permute_impl(memory, phase_sequence, 0, Some(dashboard))
 }
 
 fn permute_impl(
     memory: &intcode::Memory,
     mut phase_sequence: [intcode::Word; 5],
     start: usize,
+    dashboard: Option<&Dashboard>,
 ) -> Result<([intcode::Word; 5], intcode::Word)> {
     if start == 5 {
-        let result = run_amplifier_sequence(memory, phase_sequence)?;
+        let result = run_amplifier_sequence(memory, phase_sequence, dashboard)?;
         log::debug!("With sequence {:?}, gives {}", phase_sequence, result);
         Ok((phase_sequence, result))
     } else {
@@ -367,7 +407,7 @@ fn permute_impl(
         let mut max = 0;
         for i in start..=4 {
             phase_sequence.swap(i, start);
-            let (best_seq, seq_max) = permute_impl(memory, phase_sequence, start + 1)?;
+            let (best_seq, seq_max) = permute_impl(memory, phase_sequence, start + 1, dashboard)?;
             if seq_max > max {
                 best_sequence = best_seq;
                 max = seq_max;
@@ -377,10 +417,57 @@ fn permute_impl(
     }
 }
 
+/// Searches the phase sequences of amplifiers connected in series
+///
+/// Without feedback, each amplifier runs once on its phase and the signal
+/// from the one before it. Every sequence is extended one amplifier at a
+/// time, with each stage of all the sequences evaluated in a single batch.
 pub fn find_best_phase_sequence(
     memory: &intcode::Memory,
 ) -> Result<([intcode::Word; 5], intcode::Word)> {
-    permute(memory, [0, 1, 2, 3, 4])
+    let batch = intcode::Batch::new(memory.clone());
+
+    // Each partial phase sequence with the signal it produces
+    let mut sequences: Vec<(Vec<intcode::Word>, intcode::Word)> = vec![(Vec::new(), 0)];
+    for _ in 0..5 {
+        let extended: Vec<_> = sequences
+            .iter()
+            .flat_map(|(phases, signal)| {
+                (0..5).filter(move |p| !phases.contains(p)).map(move |p| {
+                    let mut phases = phases.clone();
+                    phases.push(p);
+                    (phases, *signal)
+                })
+            })
+            .collect();
+
+        let results = batch.evaluate(
+            extended
+                .iter()
+                .map(|(phases, signal)| vec![phases[phases.len() - 1], *signal]),
+        );
+        sequences = extended
+            .into_iter()
+            .zip(results)
+            .map(|((phases, _), result)| {
+                let signal =
+                    result?.outputs.first().copied().ok_or_else(|| {
+                        anyhow::anyhow!("amplifier sequence did not produce a value")
+                    })?;
+                Ok((phases, signal))
+            })
+            .collect::<Result<_>>()?;
+    }
+
+    let (phases, max) = sequences
+        .into_iter()
+        .max_by_key(|&(_, signal)| signal)
+        .expect("there are phase sequences to try");
+    log::debug!("Best sequence {:?} gives {}", phases, max);
+
+    let mut best_sequence = [0; 5];
+    best_sequence.copy_from_slice(&phases);
+    Ok((best_sequence, max))
 }
 
 pub fn find_best_phase_sequence_with_feedback(
@@ -493,6 +580,22 @@ pub fn run() -> Result<()> {
     Ok(())
 }
 
+/// Runs both searches while showing the amplifiers on a live dashboard
+pub fn run_with_dashboard() -> Result<()> {
+    let memory = PUZZLE_INPUT.parse()?;
+    let dashboard = Dashboard::new();
+
+    let screen = dashboard.show(Duration::from_millis(50));
+    let first = permute_with_dashboard(&memory, [0, 1, 2, 3, 4], &dashboard)?;
+    let second = permute_with_dashboard(&memory, [5, 6, 7, 8, 9], &dashboard)?;
+    screen.close()?;
+
+    println!("Best sequence: {:?}, end value = {}", first.0, first.1);
+    println!("Best sequence: {:?}, end value = {}", second.0, second.1);
+
+    Ok(())
+}
+
 pub async fn run_async() -> Result<()> {
     let memory = Arc::new(PUZZLE_INPUT.parse()?);
 
@@ -507,7 +610,7 @@ pub async fn run_async() -> Result<()> {
 
 #[cfg(test)]
 mod tests {
-    use super::run_amplifier_sequence;
+    use super::{find_best_phase_sequence, run_amplifier_sequence};
     use anyhow::Result;
     use pretty_assertions::assert_eq;
 
@@ -523,7 +626,7 @@ mod tests {
 
         let memory = PROGRAM.parse()?;
 
-        let actual = run_amplifier_sequence(&memory, *PHASES)?;
+        let actual = run_amplifier_sequence(&memory, *PHASES, None)?;
         const EXPECTED: intcode::Word = 43210;
 
         assert_eq!(EXPECTED, actual);
@@ -540,7 +643,7 @@ mod tests {
 
         let memory = PROGRAM.parse()?;
 
-        let actual = run_amplifier_sequence(&memory, *PHASES)?;
+        let actual = run_amplifier_sequence(&memory, *PHASES, None)?;
         const EXPECTED: intcode::Word = 54321;
 
         assert_eq!(EXPECTED, actual);
@@ -557,7 +660,7 @@ mod tests {
 
         let memory = PROGRAM.parse()?;
 
-        let actual = run_amplifier_sequence(&memory, *PHASES)?;
+        let actual = run_amplifier_sequence(&memory, *PHASES, None)?;
         const EXPECTED: intcode::Word = 65210;
 
         assert_eq!(EXPECTED, actual);
@@ -565,6 +668,20 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn part_1_search() -> Result<()> {
+        init_logging();
+        const PROGRAM: &str = "3,15,3,16,1002,16,10,16,1,16,15,15,4,15,99,0,0";
+
+        let memory = PROGRAM.parse()?;
+
+        let actual = find_best_phase_sequence(&memory)?;
+
+        assert_eq!(([4, 3, 2, 1, 0], 43210), actual);
+
+        Ok(())
+    }
+
     #[test]
     fn part_2_example_1() -> Result<()> {
         init_logging();
@@ -574,7 +691,7 @@ mod tests {
 
         let memory = PROGRAM.parse()?;
 
-        let actual = run_amplifier_sequence(&memory, PHASES)?;
+        let actual = run_amplifier_sequence(&memory, PHASES, None)?;
         const EXPECTED: intcode::Word = 139_629_729;
 
         assert_eq!(EXPECTED, actual);
@@ -592,7 +709,7 @@ mod tests {
 
         let memory = PROGRAM.parse()?;
 
-        let actual = run_amplifier_sequence(&memory, PHASES)?;
+        let actual = run_amplifier_sequence(&memory, PHASES, None)?;
         const EXPECTED: intcode::Word = 18216;
 
         assert_eq!(EXPECTED, actual);
@@ -118,7 +118,7 @@ impl Image {
         Self { dimensions, data }
     }
 
-    fn layer_at(&self, start: usize) -> Layer {
+    fn layer_at(&self, start: usize) -> Layer<'_> {
         let layer = &self.data[start..start + self.layer_size()];
         debug_assert_eq!(layer.len(), self.layer_size());
         Layer {
@@ -131,7 +131,7 @@ impl Image {
         self.data.len() / self.layer_size()
     }
 
-    pub fn layers(&self) -> LayerIterator {
+    pub fn layers(&self) -> LayerIterator<'_> {
         LayerIterator {
             lower: 0,
             upper: self.layer_count(),
@@ -139,13 +139,13 @@ impl Image {
         }
     }
 
-    fn layer(&self, idx: usize) -> Layer {
+    fn layer(&self, idx: usize) -> Layer<'_> {
         let start = idx * self.layer_size();
         self.layer_at(start)
     }
 
     pub fn checksum(&self) -> (usize, u32) {
-        let mut best_layer = [u32::max_value(); 3];
+        let mut best_layer = [u32::MAX; 3];
         let mut best_layer_num = 0;
         for layer in self.layers().enumerate() {
             let current_count = layer.1.count_chars();
@@ -158,7 +158,7 @@ impl Image {
         (best_layer_num, best_layer[1] * best_layer[2])
     }
 
-    pub fn resolve(&self) -> Layer {
+    pub fn resolve(&self) -> Layer<'_> {
         let mut layers = self.layers();
         let foreground = layers.next().unwrap();
 
@@ -170,7 +170,7 @@ impl Image {
         })
     }
 
-    pub fn resolve_back(&self) -> Layer {
+    pub fn resolve_back(&self) -> Layer<'_> {
         let mut layers = self.layers().rev();
         let background = layers.next().unwrap();
 
@@ -183,7 +183,7 @@ impl Image {
     }
 }
 
-impl<'a> fmt::Display for Image {
+impl fmt::Display for Image {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         let image = self.resolve();
 
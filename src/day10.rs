@@ -254,13 +254,13 @@ pub struct Slope {
 
 impl PartialOrd for Slope {
     fn partial_cmp(&self, r: &Self) -> Option<Ordering> {
-        self.theta().partial_cmp(&r.theta())
+        Some(self.cmp(r))
     }
 }
 
 impl Ord for Slope {
     fn cmp(&self, r: &Self) -> Ordering {
-        self.partial_cmp(r).unwrap()
+        self.theta().partial_cmp(&r.theta()).unwrap()
     }
 }
 
@@ -888,7 +888,7 @@ mod tests {
     fn min_value() {
         const MIN: Slope = Slope::new(0, -1);
         let mut success = true;
-        for l in &ORDERING[..] {
+        for l in ORDERING {
             if *l != MIN {
                 let cmp = l.cmp(&MIN);
                 println!("{:?} {:?} {:?}", l, cmp, MIN);
@@ -903,9 +903,9 @@ mod tests {
 
     #[test]
     fn max_value() {
-        const MAX: Slope = Slope::new(-1, isize::min_value() / 3);
+        const MAX: Slope = Slope::new(-1, isize::MIN / 3);
         let mut success = true;
-        for l in &ORDERING[..] {
+        for l in ORDERING {
             if *l != MAX {
                 let cmp = l.cmp(&MAX);
                 println!("{:?} {:?} {:?}", l, cmp, MAX);
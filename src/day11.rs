@@ -124,23 +124,21 @@
 //! registration identifier does it paint on your hull?
 
 use super::{Orientation, Position2D};
+use futures::{
+    channel::mpsc::{channel, Receiver, Sender},
+    SinkExt, StreamExt,
+};
 use std::{collections::HashMap, convert::TryFrom, fmt, ops};
-use tokio::sync::mpsc::{channel, Receiver, Sender};
 
 pub const PUZZLE_INPUT: &str = include_str!("../inputs/input-11");
 
-#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default)]
 enum PanelColor {
+    #[default]
     Black,
     White,
 }
 
-impl Default for PanelColor {
-    fn default() -> Self {
-        Self::Black
-    }
-}
-
 impl fmt::Display for PanelColor {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         let value = match self {
@@ -249,14 +247,14 @@ impl EmergencyHullPaintingRobot {
                 break;
             }
 
-            let paint = if let Some(p) = commands.recv().await {
+            let paint = if let Some(p) = commands.next().await {
                 PanelColor::try_from(p)?
             } else {
                 log::warn!("connection closed before receiving paint command; halting");
                 break;
             };
 
-            let turn = if let Some(t) = commands.recv().await {
+            let turn = if let Some(t) = commands.next().await {
                 Turn::try_from(t)?
             } else {
                 log::warn!("connection closed before receiving paint command; halting");
@@ -297,8 +295,8 @@ impl EmergencyHullPaintingRobot {
 fn convert_painted_panels_to_image(
     painted: &HashMap<Position2D, PanelColor>,
 ) -> Vec<Vec<PanelColor>> {
-    let min = Position2D::new(i64::max_value(), i64::max_value());
-    let max = Position2D::new(i64::min_value(), i64::min_value());
+    let min = Position2D::new(i64::MAX, i64::MAX);
+    let max = Position2D::new(i64::MIN, i64::MIN);
 
     let (min, max) = painted.keys().fold((min, max), |(mut min, mut max), p| {
         min = Position2D::new(min.x.min(p.x), min.y.min(p.y));
@@ -307,13 +305,13 @@ fn convert_painted_panels_to_image(
     });
 
     println!("min: {}; max: {}", min, max);
-    let row_count = (max.y - min.y + 1).abs() as usize;
-    let col_count = (max.x - min.x + 1).abs() as usize;
+    let row_count = (max.y - min.y + 1).unsigned_abs() as usize;
+    let col_count = (max.x - min.x + 1).unsigned_abs() as usize;
     let mut image = Vec::with_capacity(row_count);
     image.resize(row_count, vec![PanelColor::Black; col_count]);
 
     for (k, v) in painted.iter().filter(|(_, &v)| v != PanelColor::Black) {
-        image[(k.y - max.y).abs() as usize][(k.x - min.x) as usize] = *v;
+        image[(k.y - max.y).unsigned_abs() as usize][(k.x - min.x) as usize] = *v;
     }
 
     image
@@ -404,7 +404,9 @@ impl std::ops::AddAssign<Velocity3D> for Position3D {
 
 impl Energetic for Position3D {
     fn energy(&self) -> usize {
-        self.x.abs() as usize + self.y.abs() as usize + self.z.abs() as usize
+        self.x.unsigned_abs() as usize
+            + self.y.unsigned_abs() as usize
+            + self.z.unsigned_abs() as usize
     }
 }
 
@@ -466,7 +468,9 @@ impl std::ops::SubAssign for Velocity3D {
 
 impl Energetic for Velocity3D {
     fn energy(&self) -> usize {
-        self.x.abs() as usize + self.y.abs() as usize + self.z.abs() as usize
+        self.x.unsigned_abs() as usize
+            + self.y.unsigned_abs() as usize
+            + self.z.unsigned_abs() as usize
     }
 }
 
@@ -481,7 +485,7 @@ fn parse_input(input: &str) -> anyhow::Result<Vec<Position3D>> {
                 if trimmed.is_empty() {
                     None
                 } else {
-                    Some(trimmed.parse().map_err(anyhow::Error::from))
+                    Some(trimmed.parse())
                 }
             }
             Err(err) => Some(Err(anyhow::Error::from(err))),
@@ -575,12 +579,12 @@ impl From<Axis3D> for Velocity3D {
 
 impl Energetic for Axis3D {
     fn energy(&self) -> usize {
-        let potential = self.x.position.abs() as usize
-            + self.y.position.abs() as usize
-            + self.z.position.abs() as usize;
-        let kinetic = self.x.velocity.abs() as usize
-            + self.y.velocity.abs() as usize
-            + self.z.velocity.abs() as usize;
+        let potential = self.x.position.unsigned_abs() as usize
+            + self.y.position.unsigned_abs() as usize
+            + self.z.position.unsigned_abs() as usize;
+        let kinetic = self.x.velocity.unsigned_abs() as usize
+            + self.y.velocity.unsigned_abs() as usize
+            + self.z.velocity.unsigned_abs() as usize;
         potential * kinetic
     }
 }
@@ -52,10 +52,11 @@
 //! block is broken?
 
 use super::Position2D;
+use futures::{channel::mpsc, StreamExt, TryStreamExt};
 use itertools::Itertools;
 use std::{cmp::Ordering, collections::HashMap, convert::TryFrom, fmt};
 use termion::{clear, color, cursor, style};
-use tokio::sync::{mpsc, watch};
+use tokio::sync::watch;
 
 const PUZZLE_INPUT: &str = include_str!("../inputs/input-13");
 
@@ -417,23 +418,11 @@ impl Field {
 async fn receive_next(
     display: &mut mpsc::Receiver<intcode::Word>,
 ) -> Option<(intcode::Word, intcode::Word, intcode::Word)> {
-    let x = if let Some(x) = display.recv().await {
-        x
-    } else {
-        return None;
-    };
-
-    let y = if let Some(y) = display.recv().await {
-        y
-    } else {
-        return None;
-    };
-
-    let t = if let Some(t) = display.recv().await {
-        t
-    } else {
-        return None;
-    };
+    let x = display.next().await?;
+
+    let y = display.next().await?;
+
+    let t = display.next().await?;
 
     Some((x, y, t))
 }
@@ -587,16 +576,9 @@ pub fn run() -> anyhow::Result<()> {
 
     let mut runtime = tokio::runtime::Runtime::new()?;
 
-    let mut exe = intcode::AsyncExecutable::from(game.clone());
-
-    let result: anyhow::Result<_> = runtime.block_on(async move {
-        let drain = exe.drain().into_vec();
-
-        exe.execute().await?;
-        Ok(drain.await?)
-    });
+    let exe = intcode::AsyncExecutable::from(game.clone());
 
-    let data = result?;
+    let data: Vec<_> = runtime.block_on(exe.into_stream().try_collect())?;
 
     let mut blocks = HashMap::<Position2D, Tile>::new();
     blocks.extend(data.into_iter().chunks(3).into_iter().map(|mut c| {
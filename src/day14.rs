@@ -298,8 +298,8 @@ fn find_ore_requirement(reactions: &[Reaction], fuel_needed: usize) -> usize {
                 let (ins, amt) = &reaction_map[need];
                 let produced = *amt as isize;
                 let mul = (-*req + produced - 1) / produced;
-                *req += produced as isize * mul;
-                (ins, mul as isize)
+                *req += produced * mul;
+                (ins, mul)
             } else {
                 break;
             }
@@ -312,7 +312,7 @@ fn find_ore_requirement(reactions: &[Reaction], fuel_needed: usize) -> usize {
         log::debug!("State {:?}", in_store);
     }
 
-    in_store["ORE"].abs() as usize
+    in_store["ORE"].unsigned_abs()
 }
 
 pub fn run() -> Result<()> {
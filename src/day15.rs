@@ -166,9 +166,12 @@
 
 use super::{Orientation, Position2D};
 use anyhow::Result;
+use futures::{
+    channel::mpsc::{channel, Receiver, Sender},
+    SinkExt, StreamExt,
+};
 use petgraph::prelude::*;
 use std::{convert::TryFrom, fmt};
-use tokio::sync::mpsc::{channel, Receiver, Sender};
 
 const PUZZLE_INPUT: &str = include_str!("../inputs/input-15");
 
@@ -295,7 +298,7 @@ impl RepairDroid {
                         break;
                     }
 
-                    let move_result = if let Some(p) = camera.recv().await {
+                    let move_result = if let Some(p) = camera.next().await {
                         NodeType::try_from(p)?
                     } else {
                         log::warn!("connection closed before receiving movement result; halting");
@@ -321,7 +324,7 @@ impl RepairDroid {
                     break;
                 }
 
-                let move_result = if let Some(p) = camera.recv().await {
+                let move_result = if let Some(p) = camera.next().await {
                     NodeType::try_from(p)?
                 } else {
                     log::warn!("connection closed before receiving movement result; halting");
@@ -176,7 +176,7 @@ impl Iterator for FftIter {
     type Item = ();
     fn next(&mut self) -> Option<()> {
         log::info!("Iteration {}", self.iterations);
-        fft(&self.data, &self.sequence, &mut self.buffer);
+        fft(&self.data, self.sequence, &mut self.buffer);
         std::mem::swap(&mut self.data, &mut self.buffer);
         self.iterations += 1;
         Some(())
@@ -199,7 +199,7 @@ fn fft_step(data: &[u8], sequence: &[i8], step: usize) -> u8 {
             sequence
                 .iter()
                 .copied()
-                .flat_map(move |i| std::iter::repeat(i).take(step + 1))
+                .flat_map(move |i| std::iter::repeat_n(i, step + 1))
                 .cycle()
                 .skip(1),
         )
@@ -212,7 +212,7 @@ pub fn calc_offset(data: &[u8]) -> usize {
     data[..7]
         .iter()
         .copied()
-        .fold(0, |s, c| s * 10 + c as usize) as usize
+        .fold(0, |s, c| s * 10 + c as usize)
 }
 
 pub fn short_cut_high_offset(data: &[u8], repeats: usize, offset: usize, rounds: usize) -> [u8; 8] {
@@ -198,13 +198,17 @@
 
 use super::{Grid, GridPosition, Orientation, Turn};
 use anyhow::{anyhow, Result};
+use futures::{
+    channel::mpsc::{channel, Receiver},
+    SinkExt, StreamExt,
+};
 use num_traits::ToPrimitive;
-use tokio::sync::mpsc::{channel, Receiver};
 
 const PUZZLE_INPUT: &str = include_str!("../inputs/input-17");
 
-#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Default)]
 enum Element {
+    #[default]
     Empty,
     Scaffold,
     Robot(Orientation),
@@ -213,25 +217,11 @@ enum Element {
 
 impl Element {
     fn is_robot(self) -> bool {
-        match self {
-            Self::Robot(_) => true,
-            Self::DeadRobot => true,
-            _ => false,
-        }
+        matches!(self, Self::Robot(_) | Self::DeadRobot)
     }
 
     fn is_scaffold(self) -> bool {
-        match self {
-            Self::Robot(_) => true,
-            Self::Scaffold => true,
-            _ => false,
-        }
-    }
-}
-
-impl Default for Element {
-    fn default() -> Self {
-        Element::Empty
+        matches!(self, Self::Robot(_) | Self::Scaffold)
     }
 }
 
@@ -419,7 +409,7 @@ impl VaccumRobot {
     async fn read_field(camera: &mut Receiver<intcode::Word>) -> Result<Field> {
         let mut data = String::new();
         let mut nl = false;
-        while let Some(w) = camera.recv().await {
+        while let Some(w) = camera.next().await {
             let ch = w
                 .to_u32()
                 .and_then(std::char::from_u32)
@@ -455,7 +445,8 @@ impl VaccumRobot {
         Ok(field)
     }
 
-    async fn clean(&self) -> Result<()> {
+    /// Returns the amount of dust collected
+    async fn clean(&self) -> Result<intcode::Word> {
         // A,C,A,C,B,B,C,A,C,B
 
         // A: L,8,R,12,R,12,R,10
@@ -463,7 +454,7 @@ impl VaccumRobot {
         // C: R,10,R,12,R,10
 
         let mut command = channel(20);
-        let terminal = intcode::TerminalOut::new();
+        let terminal = intcode::TerminalOut::new().non_ascii(intcode::NonAsciiPolicy::Collect);
         let mut prog = self.program.clone();
         prog.write_arbitrary(intcode::Address::new(0), 2);
         let mut exe = intcode::AsyncExecutable::from(prog);
@@ -490,10 +481,12 @@ impl VaccumRobot {
 
         log::debug!("waiting for vaccum robot to halt");
         join.await??;
-        term_join.await??;
+        let dust = term_join.await??;
         log::debug!("vaccum robot halted");
 
-        Ok(())
+        dust.first()
+            .copied()
+            .ok_or_else(|| anyhow!("vaccum robot did not report the dust collected"))
     }
 }
 
@@ -509,7 +502,9 @@ pub fn run() -> Result<()> {
 
     field.find_path();
 
-    runtime.block_on(robot.clean())?;
+    let dust = runtime.block_on(robot.clean())?;
+
+    println!("Dust collected:\n{}", dust);
 
     Ok(())
 }
@@ -361,31 +361,19 @@ enum Element {
 
 impl Element {
     fn is_origin(self) -> bool {
-        match self {
-            Element::Origin => true,
-            _ => false,
-        }
+        matches!(self, Element::Origin)
     }
 
     fn is_wall(self) -> bool {
-        match self {
-            Element::Wall => true,
-            _ => false,
-        }
+        matches!(self, Element::Wall)
     }
 
     fn is_key(self) -> bool {
-        match self {
-            Element::Key(_) => true,
-            _ => false,
-        }
+        matches!(self, Element::Key(_))
     }
 
     fn is_door(self) -> bool {
-        match self {
-            Element::Door(_) => true,
-            _ => false,
-        }
+        matches!(self, Element::Door(_))
     }
 
     fn key_id(self) -> Option<KeyId> {
@@ -415,10 +403,10 @@ impl std::str::FromStr for Element {
             " " | "." => Self::Empty,
             "@" => Self::Origin,
             x => {
-                let b = x.bytes().nth(0).unwrap();
-                if b >= b'a' && b <= b'z' {
+                let b = x.as_bytes()[0];
+                if b.is_ascii_lowercase() {
                     Self::Key(KeyId(b - b'a'))
-                } else if b >= b'A' && b <= b'Z' {
+                } else if b.is_ascii_uppercase() {
                     Self::Door(KeyId(b - b'A'))
                 } else {
                     return Err(anyhow!("unrecognized element: {}", x));
@@ -597,7 +585,7 @@ impl Maze {
                 {
                     score += KeyWithScore(k, v.steps);
 
-                    if best == None || *best.as_ref().unwrap() > score {
+                    if best.is_none() || *best.as_ref().unwrap() > score {
                         best = Some(score);
                     }
                 }
@@ -617,9 +605,7 @@ impl Maze {
         let greedy: ScoreWithPath = self.greedy()?;
         let mut result: ScoreWithPath = self.memoized_dfs(
             &mut HashMap::new(),
-            std::iter::repeat(None)
-                .take(self.origin_paths.len())
-                .collect(),
+            std::iter::repeat_n(None, self.origin_paths.len()).collect(),
             self.keys_to_find,
             greedy.score,
         )?;
@@ -631,9 +617,7 @@ impl Maze {
         let greedy_score: usize = self.greedy()?;
         let result: usize = self.memoized_dfs(
             &mut HashMap::new(),
-            std::iter::repeat(None)
-                .take(self.origin_paths.len())
-                .collect(),
+            std::iter::repeat_n(None, self.origin_paths.len()).collect(),
             self.keys_to_find,
             greedy_score,
         )?;
@@ -774,6 +758,7 @@ impl<'a> cmp::PartialOrd for Queue<'a> {
     }
 }
 
+#[allow(dead_code)]
 #[derive(Clone, Debug, Default, Eq, PartialEq)]
 struct Path {
     steps: usize,
@@ -1018,7 +1003,7 @@ fn key_finder_dijkstra(
                     next_score = *ent.get();
                 }
                 Entry::Vacant(ent) => {
-                    ent.insert(next_score.clone());
+                    ent.insert(next_score);
                 }
             }
             visit_next.push(MinScored(next_score, next));
@@ -108,23 +108,21 @@
 
 use super::{Grid, GridPosition, Orientation};
 use anyhow::{anyhow, Result};
+use futures::{
+    channel::mpsc::{channel, Receiver, Sender},
+    SinkExt,
+};
 use std::{cmp::Ordering, fmt};
-use tokio::sync::mpsc::channel;
 
 const PUZZLE_INPUT: &str = include_str!("../inputs/input-19");
 
-#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default)]
 enum BeamPosition {
+    #[default]
     OutOfBeam,
     InBeam,
 }
 
-impl Default for BeamPosition {
-    fn default() -> Self {
-        Self::OutOfBeam
-    }
-}
-
 impl fmt::Display for BeamPosition {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         let ch = match *self {
@@ -135,43 +133,63 @@ impl fmt::Display for BeamPosition {
     }
 }
 
-async fn read_position(program: &intcode::Memory, pos: GridPosition) -> Result<BeamPosition> {
-    let mut camera = channel(1);
-    let mut command = channel(2);
-    let mut exe = intcode::AsyncExecutable::from(program.clone());
-    exe.pipe_outputs_to(camera.0);
-    exe.pipe_inputs_from(command.1);
-    tokio::spawn(exe.execute());
+/// The drone program, which is reset for each reading rather than rebuilt
+struct Drone {
+    exe: intcode::AsyncExecutable,
+    command: Sender<intcode::Word>,
+    camera: Receiver<intcode::Word>,
+}
 
-    if command.0.send(pos.col as intcode::Word).await.is_err() {
-        return Err(anyhow!("Unexpected end on col"));
-    }
-    if command.0.send(pos.row as intcode::Word).await.is_err() {
-        return Err(anyhow!("Unexpected end on row"));
+impl Drone {
+    fn new(program: intcode::Memory) -> Self {
+        let (command, commands) = channel(2);
+        let (reports, camera) = channel(1);
+        let mut exe = intcode::AsyncExecutable::from(program);
+        exe.pipe_inputs_from(commands);
+        exe.pipe_outputs_to(reports);
+
+        Self {
+            exe,
+            command,
+            camera,
+        }
     }
 
-    if let Some(result) = camera.1.recv().await {
-        match result {
-            0 => Ok(BeamPosition::OutOfBeam),
-            1 => Ok(BeamPosition::InBeam),
-            _ => Err(anyhow!("Unknown beam response: {}", result)),
+    async fn read_position(&mut self, pos: GridPosition) -> Result<BeamPosition> {
+        self.exe.reset();
+
+        if self.command.send(pos.col as intcode::Word).await.is_err() {
+            return Err(anyhow!("Unexpected end on col"));
+        }
+        if self.command.send(pos.row as intcode::Word).await.is_err() {
+            return Err(anyhow!("Unexpected end on row"));
+        }
+        self.exe.run().await?;
+
+        match self.camera.try_next() {
+            Ok(Some(0)) => Ok(BeamPosition::OutOfBeam),
+            Ok(Some(1)) => Ok(BeamPosition::InBeam),
+            Ok(Some(result)) => Err(anyhow!("Unknown beam response: {}", result)),
+            _ => Err(anyhow!("Unexpected end on read")),
         }
-    } else {
-        Err(anyhow!("Unexpected end on read"))
     }
 }
 
 async fn test_orientation(
-    program: &intcode::Memory,
+    drone: &mut Drone,
     hint: GridPosition,
     goal: usize,
     orientation: Orientation,
 ) -> Result<Ordering> {
-    if read_position(program, hint.relative(orientation, goal - 1).unwrap()).await?
+    if drone
+        .read_position(hint.relative(orientation, goal - 1).unwrap())
+        .await?
         == BeamPosition::OutOfBeam
     {
         Ok(Ordering::Less)
-    } else if read_position(program, hint.relative(orientation, goal).unwrap()).await?
+    } else if drone
+        .read_position(hint.relative(orientation, goal).unwrap())
+        .await?
         == BeamPosition::OutOfBeam
     {
         Ok(Ordering::Equal)
@@ -181,7 +199,7 @@ async fn test_orientation(
 }
 
 async fn find_for_size(
-    program: &intcode::Memory,
+    drone: &mut Drone,
     mut hint: GridPosition,
     goal: usize,
 ) -> Result<GridPosition> {
@@ -189,7 +207,7 @@ async fn find_for_size(
     let mut correction = Orientation::South;
     loop {
         let mut attempts = 0;
-        while read_position(program, hint).await? == BeamPosition::OutOfBeam {
+        while drone.read_position(hint).await? == BeamPosition::OutOfBeam {
             log::warn!(
                 "hint {} outside of beam, trying to correct {:?}",
                 hint,
@@ -205,8 +223,8 @@ async fn find_for_size(
             // return Err(anyhow!("hint {} outside of beam", hint));
         }
 
-        let height_cmp = test_orientation(program, hint, goal, Orientation::South).await?;
-        let width_cmp = test_orientation(program, hint, goal, Orientation::East).await?;
+        let height_cmp = test_orientation(drone, hint, goal, Orientation::South).await?;
+        let width_cmp = test_orientation(drone, hint, goal, Orientation::East).await?;
 
         log::debug!(
             "testing {}: height {:?}, width {:?}, goal: {}",
@@ -329,47 +347,32 @@ async fn find_for_size(
 //     }
 // }
 
-async fn define_beam(program: intcode::Memory) -> Result<Grid<intcode::Word>> {
+fn define_beam(program: intcode::Memory) -> Result<Grid<intcode::Word>> {
     const SIZE: usize = 50;
     let mut grid = Grid::new(intcode::Word::default(), SIZE, SIZE);
 
-    'outer: for col in 0..SIZE {
-        for row in 0..SIZE {
-            let mut camera = channel(1);
-            let mut command = channel(2);
-            let mut exe = intcode::AsyncExecutable::from(program.clone());
-            exe.pipe_outputs_to(camera.0);
-            exe.pipe_inputs_from(command.1);
-            tokio::spawn(exe.execute());
-
-            if command.0.send(col as intcode::Word).await.is_err() {
-                break 'outer;
-            }
-            if command.0.send(row as intcode::Word).await.is_err() {
-                break 'outer;
-            }
-            if let Some(result) = camera.1.recv().await {
-                grid.set(
-                    GridPosition {
-                        row: row as usize,
-                        col: col as usize,
-                    },
-                    result,
-                );
-            } else {
-                break 'outer;
+    let positions =
+        || (0..SIZE).flat_map(|col| (0..SIZE).map(move |row| GridPosition { row, col }));
+    let inputs = positions().map(|pos| vec![pos.col as intcode::Word, pos.row as intcode::Word]);
+
+    let results = intcode::Batch::new(program).evaluate(inputs);
+    for (pos, result) in positions().zip(results) {
+        match result?.outputs.first() {
+            Some(&value) => {
+                grid.set(pos, value);
             }
+            None => return Err(anyhow!("Unexpected end on read at {}", pos)),
         }
     }
 
     Ok(grid)
 }
 
-async fn find_sleigh(program: &intcode::Memory) -> Result<GridPosition> {
+async fn find_sleigh(drone: &mut Drone) -> Result<GridPosition> {
     let mut hint = GridPosition { row: 4, col: 5 };
     for goal in 2..=100 {
         log::info!("Looking for size {} starting from {}", goal, hint);
-        hint = find_for_size(program, hint, goal).await?;
+        hint = find_for_size(drone, hint, goal).await?;
     }
     Ok(hint)
 }
@@ -379,7 +382,7 @@ pub fn run() -> Result<()> {
 
     let mut runtime = tokio::runtime::Runtime::new()?;
 
-    let grid = runtime.block_on(define_beam(program.clone()))?;
+    let grid = define_beam(program.clone())?;
 
     let ones = grid.enumerate().filter(|&(_, &x)| x != 0).count();
     println!("1s: {}", ones);
@@ -415,7 +418,8 @@ pub fn run() -> Result<()> {
 
     println!("Upper: {}, lower: {}", upper, lower);
 
-    let sleigh = runtime.block_on(find_sleigh(&program))?;
+    let mut drone = Drone::new(program);
+    let sleigh = runtime.block_on(find_sleigh(&mut drone))?;
     //let sleigh = runtime.block_on(shrink(&program, sleigh, 100))?;
 
     println!("Found sleigh at {}", sleigh);
@@ -284,17 +284,11 @@ enum Warp {
 
 impl Warp {
     fn is_inner(self) -> bool {
-        match self {
-            Self::Inner(_) => true,
-            _ => false,
-        }
+        matches!(self, Self::Inner(_))
     }
 
     fn is_outer(self) -> bool {
-        match self {
-            Self::Outer(_) => true,
-            _ => false,
-        }
+        matches!(self, Self::Outer(_))
     }
 
     fn id(self) -> WarpId {
@@ -350,7 +344,7 @@ impl std::str::FromStr for Element {
             "#" => Element::Wall,
             x if x.len() == 1 => {
                 let b = x.as_bytes()[0] as char;
-                if b >= 'A' && b <= 'Z' {
+                if b.is_ascii_uppercase() {
                     Element::Letter(b)
                 } else {
                     return Err(anyhow!("Bad element: {}", b));
@@ -393,7 +387,7 @@ impl DonutMaze {
             |e| *e.weight(),
             |_| 0,
         )?;
-        Some(shortest as usize)
+        Some(shortest)
     }
 }
 
@@ -141,49 +141,108 @@
 //! will enable extended sensor mode, capable of sensing ground up to nine tiles
 //! away. This data is available in five new read-only registers:
 //!
-//!     Register `E` indicates whether there is ground five tiles away.
-//!     Register `F` indicates whether there is ground six tiles away.
-//!     Register `G` indicates whether there is ground seven tiles away.
-//!     Register `H` indicates whether there is ground eight tiles away.
-//!     Register `I` indicates whether there is ground nine tiles away.
+//! * Register `E` indicates whether there is ground five tiles away.
+//! * Register `F` indicates whether there is ground six tiles away.
+//! * Register `G` indicates whether there is ground seven tiles away.
+//! * Register `H` indicates whether there is ground eight tiles away.
+//! * Register `I` indicates whether there is ground nine tiles away.
 //!
 //! All other functions remain the same.
 //!
 //! Successfully survey the rest of the hull by ending your program with `RUN`.
 //! What amount of hull damage does the springdroid now report?
 
-use anyhow::Result;
+use anyhow::{anyhow, Result};
+use lazy_static::lazy_static;
+use regex::Regex;
 
 const PUZZLE_INPUT: &str = include_str!("../inputs/input-21");
 
+/// Jumps whenever there is a hole within three tiles and ground to land on
+pub const WALK_SCRIPT: &[&str] = &[
+    "NOT B J", "NOT C T", "OR T J", "AND D J", "NOT A T", "OR T J", "WALK",
+];
+
+/// As `WALK_SCRIPT`, but only jumps early when a second jump will be possible
+/// after landing
+pub const RUN_SCRIPT: &[&str] = &[
+    "NOT B J", "NOT C T", "OR T J", "AND D J", "AND H J", "NOT A T", "OR T J", "RUN",
+];
+
+/// Runs a springscript program and reports the hull damage found
+///
+/// Fails with the droid's last moments if it falls into space.
+pub async fn survey_hull(program: intcode::Memory, script: &[&str]) -> Result<intcode::Word> {
+    lazy_static! {
+        static ref PROMPT: Regex = Regex::new(r"Input instructions:\n").unwrap();
+    }
+
+    let mut session = intcode::AsciiSession::start(program);
+    session.expect(&PROMPT).await?;
+    for line in script {
+        session.send_line(line).await?;
+    }
+
+    let finished = session.finish().await?;
+    finished
+        .values
+        .first()
+        .copied()
+        .ok_or_else(|| anyhow!("springdroid fell into space:\n{}", finished.output))
+}
+
 pub fn run() -> Result<()> {
     let program: intcode::Memory = PUZZLE_INPUT.parse()?;
 
-    let term = intcode::AsciiTerminal::new(program);
-
     let mut runtime = tokio::runtime::Runtime::new()?;
-    runtime.block_on(term.execute())?;
-
-    // Part 1 solution
-    // Input instructions:
-    // NOT B J
-    // NOT C T
-    // OR T J
-    // AND D J
-    // NOT A T
-    // OR T J
-    // WALK
-
-    // Part 2 solution
-    // Input instructions:
-    // NOT B J
-    // NOT C T
-    // OR T J
-    // AND D J
-    // AND H J
-    // NOT A T
-    // OR T J
-    // RUN
+    let walked = runtime.block_on(survey_hull(program.clone(), WALK_SCRIPT))?;
+    println!("Hull damage found walking: {}", walked);
+
+    let ran = runtime.block_on(survey_hull(program, RUN_SCRIPT))?;
+    println!("Hull damage found running: {}", ran);
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{survey_hull, PUZZLE_INPUT, RUN_SCRIPT, WALK_SCRIPT};
+    use anyhow::Result;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn walking_survey() -> Result<()> {
+        let program: intcode::Memory = PUZZLE_INPUT.parse()?;
+
+        let mut runtime = tokio::runtime::Runtime::new()?;
+        let damage = runtime.block_on(survey_hull(program, WALK_SCRIPT))?;
+
+        assert_eq!(19_361_332, damage);
+
+        Ok(())
+    }
+
+    #[test]
+    fn running_survey() -> Result<()> {
+        let program: intcode::Memory = PUZZLE_INPUT.parse()?;
+
+        let mut runtime = tokio::runtime::Runtime::new()?;
+        let damage = runtime.block_on(survey_hull(program, RUN_SCRIPT))?;
+
+        assert_eq!(1_143_351_187, damage);
+
+        Ok(())
+    }
+
+    #[test]
+    fn falling_into_space_is_reported() -> Result<()> {
+        let program: intcode::Memory = PUZZLE_INPUT.parse()?;
+
+        let mut runtime = tokio::runtime::Runtime::new()?;
+        let result = runtime.block_on(survey_hull(program, &["WALK"]));
+
+        assert!(result.is_err());
+
+        Ok(())
+    }
+}
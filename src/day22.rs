@@ -88,7 +88,7 @@
 //! For example, to `deal with increment 3`:
 //!
 //! ```text
-//! 
+//!
 //! 0 1 2 3 4 5 6 7 8 9   Your deck
 //! . . . . . . . . . .   Space on table
 //! ^                     Current position
@@ -234,7 +234,7 @@ fn parse_input(s: &str) -> Result<Vec<Technique>> {
                 if trimmed.is_empty() {
                     None
                 } else {
-                    Some(trimmed.parse().map_err(Error::from))
+                    Some(trimmed.parse())
                 }
             }
             Err(e) => Some(Err(Error::from(e))),
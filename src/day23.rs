@@ -66,8 +66,19 @@
 //! the first Y value delivered by the NAT to the computer at address 0 twice in
 //! a row?
 
+use crate::{Dashboard, Probe};
 use anyhow::Result;
-use tokio::sync::mpsc::{channel, Receiver, Sender};
+use futures::{
+    channel::mpsc::{channel, Receiver, Sender},
+    SinkExt, StreamExt,
+};
+use std::{
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
 
 const PUZZLE_INPUT: &str = include_str!("../inputs/input-23");
 
@@ -88,7 +99,8 @@ struct NetworkRouter {
     tx: Sender<Packet>,
     rx: Receiver<Packet>,
     members: Vec<Sender<PacketData>>,
-    watchers: Vec<tokio::sync::watch::Receiver<bool>>,
+    probes: Vec<Option<Probe>>,
+    idle: Vec<Arc<AtomicBool>>,
 }
 
 impl NetworkRouter {
@@ -98,23 +110,26 @@ impl NetworkRouter {
             tx,
             rx,
             members: Vec::new(),
-            watchers: Vec::new(),
+            probes: Vec::new(),
+            idle: Vec::new(),
         }
     }
 
-    async fn attach_client<S>(
+    async fn attach_client<S, O>(
         &mut self,
-        mut exe: intcode::AsyncExecutable<S>,
-    ) -> Result<intcode::AsyncExecutable<FromNetworkTranslator>> {
+        mut exe: intcode::AsyncExecutable<S, O>,
+        probe: Option<Probe>,
+    ) -> Result<intcode::AsyncExecutable<FromNetworkTranslator, O>> {
         let id = self.members.len() as intcode::Word;
         let (tx, net_translator) = ToNetworkTranslator::new(id, &*self);
         exe.pipe_outputs_to(tx);
         tokio::spawn(net_translator.execute());
 
-        let net_in = FromNetworkTranslator::new(id);
+        let net_in = FromNetworkTranslator::new(id, probe.clone());
 
         self.members.push(net_in.tx());
-        self.watchers.push(net_in.idle());
+        self.probes.push(probe);
+        self.idle.push(net_in.idle());
 
         let exe = exe.input_stream(net_in);
 
@@ -125,24 +140,25 @@ impl NetworkRouter {
         let mut last_sent: Option<PacketData> = None;
         let mut nat_packet: Option<PacketData> = None;
         loop {
-            if let Ok(pkt) = self.rx.try_recv() {
+            if let Ok(Some(pkt)) = self.rx.try_next() {
                 log::debug!("Router packet received: {:?}", pkt);
                 if pkt.address == 255 {
                     nat_packet = Some(pkt.data);
                 } else {
-                    self.members[pkt.address as usize].send(pkt.data).await?;
+                    self.deliver(pkt.address as usize, pkt.data).await?;
                 }
                 continue;
-            } else if let Some(pkt) = nat_packet.take() {
+            } else if let Some(pkt) = nat_packet {
                 log::trace!("Maybe idle");
-                if self.watchers.iter().all(|w| *w.borrow()) {
+                if self.idle.iter().all(|i| i.load(Ordering::Acquire)) {
+                    nat_packet = None;
                     if let Some(last) = last_sent {
                         if last.y == pkt.y {
                             return Ok(last.y);
                         }
                     }
                     last_sent = Some(pkt);
-                    self.members[0].send(pkt).await?;
+                    self.deliver(0, pkt).await?;
                     continue;
                 }
             }
@@ -151,15 +167,25 @@ impl NetworkRouter {
     }
 
     async fn execute(mut self) -> Result<intcode::Word> {
-        while let Some(pkt) = self.rx.recv().await {
+        while let Some(pkt) = self.rx.next().await {
             log::debug!("Router packet received: {:?}", pkt);
             if pkt.address == 255 {
                 return Ok(pkt.data.y);
             }
-            self.members[pkt.address as usize].send(pkt.data).await?;
+            self.deliver(pkt.address as usize, pkt.data).await?;
         }
         Ok(-1)
     }
+
+    async fn deliver(&mut self, address: usize, data: PacketData) -> Result<()> {
+        if let Some(probe) = &self.probes[address] {
+            probe.queue_input(2);
+        }
+        // The member is busy until it has read the packet
+        self.idle[address].store(false, Ordering::Release);
+        self.members[address].send(data).await?;
+        Ok(())
+    }
 }
 
 struct ToNetworkTranslator {
@@ -188,11 +214,11 @@ impl ToNetworkTranslator {
     async fn execute_inner(mut self) -> Option<()> {
         loop {
             log::trace!("{} Waiting for data", self.id);
-            let address = self.from_exe_rx.recv().await?;
+            let address = self.from_exe_rx.next().await?;
             log::trace!("{} Sent address: {}", self.id, address);
-            let x = self.from_exe_rx.recv().await?;
+            let x = self.from_exe_rx.next().await?;
             log::trace!("{} Sent x: {}", self.id, x);
-            let y = self.from_exe_rx.recv().await?;
+            let y = self.from_exe_rx.next().await?;
             log::trace!("{} Sent y: {}", self.id, y);
             let packet = Packet {
                 address,
@@ -212,28 +238,31 @@ struct FromNetworkTranslator {
     tx: Sender<PacketData>,
     packet_source: Receiver<PacketData>,
     packet_y: Option<intcode::Word>,
-    idle_signal: tokio::sync::watch::Sender<bool>,
-    idle_watch: tokio::sync::watch::Receiver<bool>,
+    idle: Arc<AtomicBool>,
     yielded: bool,
+    probe: Option<Probe>,
 }
 
 impl FromNetworkTranslator {
-    fn new(network_id: intcode::Word) -> Self {
+    fn new(network_id: intcode::Word, probe: Option<Probe>) -> Self {
         let (tx, rx) = channel(1);
-        let (idle_signal, idle_watch) = tokio::sync::watch::channel(false);
+        // The network address is queued as the first input
+        if let Some(probe) = &probe {
+            probe.queue_input(1);
+        }
         Self {
             id: network_id,
             tx,
             packet_source: rx,
             packet_y: Some(network_id),
-            idle_signal,
-            idle_watch,
+            idle: Arc::default(),
             yielded: false,
+            probe,
         }
     }
 
-    fn idle(&self) -> tokio::sync::watch::Receiver<bool> {
-        self.idle_watch.clone()
+    fn idle(&self) -> Arc<AtomicBool> {
+        self.idle.clone()
     }
 
     fn tx(&self) -> Sender<PacketData> {
@@ -256,7 +285,8 @@ impl futures::stream::Stream for FromNetworkTranslator {
         } else if let std::task::Poll::Ready(Some(data)) =
             std::pin::Pin::new(&mut pin.packet_source).poll_next(ctx)
         {
-            let _ = pin.idle_signal.broadcast(false);
+            pin.idle.store(false, Ordering::Release);
+            pin.yielded = false;
             log::debug!("{} Received packet: {:?}", pin.id, data);
             pin.packet_y = Some(data.y);
             log::trace!("{} Receiving x: {}", pin.id, data.x);
@@ -268,20 +298,23 @@ impl futures::stream::Stream for FromNetworkTranslator {
             std::task::Poll::Pending
         } else {
             pin.yielded = false;
-            let _ = pin.idle_signal.broadcast(true);
+            pin.idle.store(true, Ordering::Release);
             log::trace!("{} Idle", pin.id);
+            if let Some(probe) = &pin.probe {
+                probe.queue_input(1);
+            }
             std::task::Poll::Ready(Some(-1))
         }
     }
 }
 
-async fn part1(program: &intcode::Memory) -> Result<intcode::Word> {
+async fn part1(program: &intcode::Memory, dashboard: Option<&Dashboard>) -> Result<intcode::Word> {
     let mut router = NetworkRouter::new();
     let mut futs = Vec::new();
-    for _ in 0..50_u8 {
-        let exe = router
-            .attach_client(intcode::AsyncExecutable::from(program.clone()))
-            .await?;
+    for i in 0..50_u8 {
+        let probe = dashboard.map(|d| d.attach(&format!("nic {}", i), program));
+        let exe = intcode::AsyncExecutable::from(program.clone()).with_observer(probe.clone());
+        let exe = router.attach_client(exe, probe).await?;
         let fut = exe.execute();
         futs.push(fut);
     }
@@ -293,13 +326,13 @@ async fn part1(program: &intcode::Memory) -> Result<intcode::Word> {
     j.await
 }
 
-async fn part2(program: &intcode::Memory) -> Result<intcode::Word> {
+async fn part2(program: &intcode::Memory, dashboard: Option<&Dashboard>) -> Result<intcode::Word> {
     let mut router = NetworkRouter::new();
     let mut futs = Vec::new();
-    for _ in 0..50_u8 {
-        let exe = router
-            .attach_client(intcode::AsyncExecutable::from(program.clone()))
-            .await?;
+    for i in 0..50_u8 {
+        let probe = dashboard.map(|d| d.attach(&format!("nic {}", i), program));
+        let exe = intcode::AsyncExecutable::from(program.clone()).with_observer(probe.clone());
+        let exe = router.attach_client(exe, probe).await?;
         let fut = exe.execute();
         futs.push(fut);
     }
@@ -314,13 +347,36 @@ async fn part2(program: &intcode::Memory) -> Result<intcode::Word> {
 pub fn run() -> Result<()> {
     let program: intcode::Memory = PUZZLE_INPUT.parse()?;
     let mut runtime = tokio::runtime::Runtime::new()?;
-    let result = runtime.block_on(part1(&program))?;
+    let result = runtime.block_on(part1(&program, None))?;
 
     println!("Packet to 255: {}", result);
 
-    let result = runtime.block_on(part2(&program))?;
+    let result = runtime.block_on(part2(&program, None))?;
 
     println!("Last doubled to 0: {}", result);
 
     Ok(())
 }
+
+/// Runs both parts while showing the network on a live dashboard
+pub fn run_with_dashboard() -> Result<()> {
+    let program: intcode::Memory = PUZZLE_INPUT.parse()?;
+    let mut runtime = tokio::runtime::Runtime::new()?;
+
+    // The machines of the first part keep running, so each part has a
+    // dashboard of its own
+    let dashboard = Dashboard::new();
+    let screen = dashboard.show(Duration::from_millis(100));
+    let first = runtime.block_on(part1(&program, Some(&dashboard)))?;
+    screen.close()?;
+
+    let dashboard = Dashboard::new();
+    let screen = dashboard.show(Duration::from_millis(100));
+    let second = runtime.block_on(part2(&program, Some(&dashboard)))?;
+    screen.close()?;
+
+    println!("Packet to 255: {}", first);
+    println!("Last doubled to 0: {}", second);
+
+    Ok(())
+}
@@ -60,56 +60,138 @@
 //!
 //! The navigation console beeps again.
 
-use anyhow::Result;
+use anyhow::{bail, Result};
+use lazy_static::lazy_static;
+use regex::Regex;
 
 const PUZZLE_INPUT: &str = include_str!("../inputs/input-25");
 
+/// Collects every safe item, then drops those which make the droid too heavy
+/// before stepping onto the pressure-sensitive floor
+pub const ROUTE: &[&str] = &[
+    "south",
+    "take cake",
+    "south",
+    "west",
+    "take mutex",
+    "east",
+    "north",
+    "north",
+    "west",
+    "take klein bottle",
+    "south",
+    "east",
+    "take monolith",
+    "south",
+    "take fuel cell",
+    "west",
+    "west",
+    "take astrolabe",
+    "east",
+    "east",
+    "north",
+    "west",
+    "north",
+    "west",
+    "north",
+    "take tambourine",
+    "south",
+    "west",
+    "take dark matter",
+    "west",
+    "drop mutex",
+    "drop klein bottle",
+    "drop fuel cell",
+    "drop cake",
+    "north",
+];
+
+/// Follows a route through the ship and reports the airlock password
+pub async fn find_password(program: intcode::Memory, route: &[&str]) -> Result<String> {
+    lazy_static! {
+        static ref COMMAND: Regex = Regex::new(r"Command\?\n").unwrap();
+        static ref PASSWORD: Regex = Regex::new(r"typing (\d+) on the keypad").unwrap();
+        static ref EJECTED: Regex = Regex::new(r"Alert! Droids on this ship are (\w+)").unwrap();
+    }
+
+    let mut session = intcode::AsciiSession::start(program);
+    for command in route {
+        session.expect(&COMMAND).await?;
+        session.send_line(command).await?;
+    }
+
+    let (idx, found) = session.expect_any(&[&PASSWORD, &EJECTED, &COMMAND]).await?;
+    let password = match idx {
+        0 => found.group(1).unwrap_or_default().to_string(),
+        // The alert compares the other droids with this one, so the droid
+        // is the opposite of what it reports
+        1 => bail!(
+            "droid was ejected for being {} than expected",
+            match found.group(1) {
+                Some("lighter") => "heavier",
+                Some("heavier") => "lighter",
+                _ => "different",
+            }
+        ),
+        _ => bail!(
+            "route ended without reaching the airlock:\n{}",
+            found.before
+        ),
+    };
+
+    session.finish().await?;
+    Ok(password)
+}
+
 pub fn run() -> Result<()> {
     let program: intcode::Memory = PUZZLE_INPUT.parse()?;
 
-    const INPUT: &[&str] = &[
-        "south",
-        "take cake",
-        "south",
-        "west",
-        "take mutex",
-        "east",
-        "north",
-        "north",
-        "west",
-        "take klein bottle",
-        "south",
-        "east",
-        "take monolith",
-        "south",
-        "take fuel cell",
-        "west",
-        "west",
-        "take astrolabe",
-        "east",
-        "east",
-        "north",
-        "west",
-        "north",
-        "west",
-        "north",
-        "take tambourine",
-        "south",
-        "west",
-        "take dark matter",
-        "west",
-        "drop mutex",
-        "drop klein bottle",
-        "drop fuel cell",
-        "drop cake",
-        "north",
-    ];
-
-    // let term = intcode::AsciiTerminal::new(program);
-    let term = intcode::AsciiTerminal::with_input(program, INPUT);
-
     let mut runtime = tokio::runtime::Runtime::new()?;
-    runtime.block_on(term.execute())?;
+    let password = runtime.block_on(find_password(program, ROUTE))?;
+    println!("Airlock password: {}", password);
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{find_password, PUZZLE_INPUT, ROUTE};
+    use anyhow::Result;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn route_reaches_airlock() -> Result<()> {
+        let program: intcode::Memory = PUZZLE_INPUT.parse()?;
+
+        let mut runtime = tokio::runtime::Runtime::new()?;
+        let password = runtime.block_on(find_password(program, ROUTE))?;
+
+        assert_eq!("67635328", password);
+
+        Ok(())
+    }
+
+    #[test]
+    fn carrying_too_much_is_reported() -> Result<()> {
+        let program: intcode::Memory = PUZZLE_INPUT.parse()?;
+        let (last, route) = ROUTE.split_last().expect("a non-empty route");
+        let route: Vec<_> = route
+            .iter()
+            .filter(|c| !c.starts_with("drop"))
+            .chain(Some(last))
+            .copied()
+            .collect();
+
+        let mut runtime = tokio::runtime::Runtime::new()?;
+        let err = runtime
+            .block_on(find_password(program, &route))
+            .expect_err("droid should be too heavy");
+
+        assert_eq!(
+            "droid was ejected for being heavier than expected",
+            err.to_string()
+        );
+
+        Ok(())
+    }
+}
@@ -53,7 +53,7 @@ impl<T> Grid<T> {
         Some(std::mem::replace(&mut self.elements[idx], element))
     }
 
-    pub fn enumerate(&self) -> GridIterator<T> {
+    pub fn enumerate(&self) -> GridIterator<'_, T> {
         GridIterator::new(self)
     }
 
@@ -65,15 +65,15 @@ impl<T> Grid<T> {
         self.rows
     }
 
-    pub fn column(&self, column: usize) -> ColumnIterator<T> {
+    pub fn column(&self, column: usize) -> ColumnIterator<'_, T> {
         ColumnIterator::new(self, column)
     }
 
-    pub fn row(&self, row: usize) -> RowIterator<T> {
+    pub fn row(&self, row: usize) -> RowIterator<'_, T> {
         RowIterator::new(self, row)
     }
 
-    pub fn neighbors(&self, position: GridPosition) -> NeighborIterator<T> {
+    pub fn neighbors(&self, position: GridPosition) -> NeighborIterator<'_, T> {
         NeighborIterator::new(self, position)
     }
 }
@@ -122,7 +122,7 @@ where
     T: std::fmt::Display,
 {
     fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
-        for line in self.elements.chunks_exact(self.columns as usize) {
+        for line in self.elements.chunks_exact(self.columns) {
             for element in line {
                 write!(f, "{}", element)?;
             }
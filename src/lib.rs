@@ -26,10 +26,12 @@ pub mod day23;
 pub mod day24;
 pub mod day25;
 
+mod dashboard;
 mod grid;
 mod orientation;
 mod position;
 
+pub use dashboard::{Dashboard, Probe, Screen};
 use grid::Grid;
 use orientation::{Orientation, Turn};
 use position::{GridPosition, Position2D};